@@ -0,0 +1,11 @@
+-- Add migration script here
+
+-- migrate:up
+ALTER TABLE chat_messages
+ADD COLUMN message_id TEXT,
+ADD COLUMN channel_id TEXT,
+ADD COLUMN guild_id TEXT,
+ADD COLUMN sentiment TEXT CHECK (sentiment IN ('positive', 'negative', 'neutral')),
+ADD COLUMN model TEXT,
+ADD COLUMN prompt_tokens INTEGER,
+ADD COLUMN completion_tokens INTEGER;
@@ -0,0 +1,14 @@
+-- Add migration script here
+
+-- migrate:up
+CREATE TABLE sentiment_events (
+    id SERIAL PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    message_id TEXT,
+    sentiment TEXT NOT NULL CHECK (sentiment IN ('positive', 'negative', 'neutral')),
+    confidence REAL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX sentiment_events_user_id_created_at_idx ON sentiment_events (user_id, created_at);
+CREATE INDEX sentiment_events_created_at_idx ON sentiment_events (created_at);
@@ -0,0 +1,13 @@
+-- Add migration script here
+
+-- migrate:up
+CREATE TABLE audit_log (
+    id SERIAL PRIMARY KEY,
+    actor_id TEXT NOT NULL,
+    action TEXT NOT NULL,
+    target_user_id TEXT NOT NULL,
+    details TEXT NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX audit_log_target_user_id_idx ON audit_log (target_user_id);
@@ -0,0 +1,13 @@
+-- Add migration script here
+
+-- migrate:up
+CREATE TABLE sentiment_overrides (
+    id SERIAL PRIMARY KEY,
+    message_id TEXT NOT NULL UNIQUE,
+    user_id TEXT NOT NULL,
+    content TEXT,
+    classified_sentiment TEXT NOT NULL CHECK (classified_sentiment IN ('positive', 'negative', 'neutral')),
+    corrected_sentiment TEXT NOT NULL CHECK (corrected_sentiment IN ('positive', 'negative', 'neutral')),
+    overridden_by TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
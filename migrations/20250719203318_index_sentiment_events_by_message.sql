@@ -0,0 +1,4 @@
+-- Add migration script here
+
+-- migrate:up
+CREATE INDEX sentiment_events_message_id_idx ON sentiment_events (message_id);
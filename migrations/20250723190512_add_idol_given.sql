@@ -0,0 +1,5 @@
+-- Add migration script here
+
+-- migrate:up
+ALTER TABLE user_interaction
+ADD COLUMN idol_given BOOLEAN NOT NULL DEFAULT FALSE;
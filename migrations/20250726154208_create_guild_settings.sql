@@ -0,0 +1,14 @@
+-- Add migration script here
+
+-- migrate:up
+CREATE TABLE guild_settings (
+    guild_id TEXT PRIMARY KEY,
+    prefix TEXT,
+    enabled_channel_ids TEXT[] NOT NULL DEFAULT '{}',
+    persona TEXT,
+    welcome_channel_id TEXT,
+    host_role_id TEXT,
+    max_reply_tokens INTEGER CHECK (max_reply_tokens > 0),
+    disabled_features TEXT[] NOT NULL DEFAULT '{}',
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,9 @@
+-- Add migration script here
+
+-- migrate:up
+ALTER TABLE guild_settings
+ADD COLUMN denied_channel_ids TEXT[] NOT NULL DEFAULT '{}',
+ADD COLUMN quiet_start TIME,
+ADD COLUMN quiet_end TIME,
+ADD COLUMN quiet_utc_offset_minutes INTEGER NOT NULL DEFAULT 0,
+ADD COLUMN quiet_mode TEXT NOT NULL DEFAULT 'brush_off' CHECK (quiet_mode IN ('ignore', 'brush_off'));
@@ -0,0 +1,16 @@
+-- Add migration script here
+
+-- migrate:up
+CREATE TABLE usage_records (
+    id SERIAL PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    guild_id TEXT,
+    kind TEXT NOT NULL CHECK (kind IN ('reply', 'classification')),
+    model TEXT NOT NULL,
+    prompt_tokens INT NOT NULL,
+    completion_tokens INT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX usage_records_user_id_created_at_idx ON usage_records (user_id, created_at);
+CREATE INDEX usage_records_guild_id_created_at_idx ON usage_records (guild_id, created_at);
@@ -0,0 +1,5 @@
+-- Add migration script here
+
+-- migrate:up
+ALTER TABLE sentiment_events
+ADD COLUMN farming TEXT CHECK (farming IN ('duplicate', 'diminished'));
@@ -0,0 +1,282 @@
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+use async_openai::{config::OpenAIConfig, types::{CreateChatCompletionRequest, CreateChatCompletionResponse}, Client};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::health::Health;
+use crate::metrics::METRICS;
+
+/// Where chat completions come from. Everything Toodles asks a model goes through here.
+#[async_trait]
+pub trait LlmBackend {
+    async fn create_chat_completion(&self, request: CreateChatCompletionRequest) -> Result<CreateChatCompletionResponse, Box<dyn Error + Send + Sync>>;
+}
+
+/// Sends requests to OpenAI, or to any server speaking its API.
+#[derive(Clone, Default)]
+pub struct OpenAiBackend {
+    client: Client<OpenAIConfig>,
+}
+
+impl OpenAiBackend {
+    pub fn new(client: Client<OpenAIConfig>) -> Self {
+        OpenAiBackend { client }
+    }
+
+    /// A backend for an OpenAI-compatible server, e.g. a local model at `http://localhost:8080/v1`.
+    pub fn with_api_base(api_base: &str) -> Self {
+        Self::new(Client::with_config(OpenAIConfig::new().with_api_base(api_base)))
+    }
+}
+
+#[async_trait]
+impl LlmBackend for OpenAiBackend {
+    async fn create_chat_completion(&self, request: CreateChatCompletionRequest) -> Result<CreateChatCompletionResponse, Box<dyn Error + Send + Sync>> {
+        Ok(self.client.chat().create(request).await?)
+    }
+}
+
+/// Answers every request with the same reply, without calling any model.
+#[derive(Clone)]
+pub struct CannedBackend {
+    reply: String,
+}
+
+impl CannedBackend {
+    pub fn new(reply: impl Into<String>) -> Self {
+        CannedBackend { reply: reply.into() }
+    }
+}
+
+#[async_trait]
+impl LlmBackend for CannedBackend {
+    async fn create_chat_completion(&self, request: CreateChatCompletionRequest) -> Result<CreateChatCompletionResponse, Box<dyn Error + Send + Sync>> {
+        Ok(serde_json::from_value(serde_json::json!({
+            "id": "canned",
+            "object": "chat.completion",
+            "created": 0,
+            "model": request.model,
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": self.reply },
+                "finish_reason": "stop"
+            }]
+        }))?)
+    }
+}
+
+/// A recorded request/response pair. The request is kept so a changed prompt can be diffed against it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+    pub request: CreateChatCompletionRequest,
+    pub response: CreateChatCompletionResponse,
+}
+
+/// Fixtures are named after a hash of the request, so any change to the model, prompt,
+/// history or parameters makes replay miss instead of serving a stale answer.
+pub fn fixture_path(dir: &Path, request: &CreateChatCompletionRequest) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+    let digest = Sha256::digest(serde_json::to_vec(request)?);
+    Ok(dir.join(format!("{:x}.json", digest)))
+}
+
+/// Passes requests through to `inner` and saves every pair it sees into `dir`.
+pub struct RecordingBackend {
+    inner: Arc<dyn LlmBackend + Send + Sync>,
+    dir: PathBuf,
+}
+
+impl RecordingBackend {
+    pub fn new(inner: Arc<dyn LlmBackend + Send + Sync>, dir: impl Into<PathBuf>) -> Self {
+        RecordingBackend { inner, dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl LlmBackend for RecordingBackend {
+    async fn create_chat_completion(&self, request: CreateChatCompletionRequest) -> Result<CreateChatCompletionResponse, Box<dyn Error + Send + Sync>> {
+        let path = fixture_path(&self.dir, &request)?;
+        let response = self.inner.create_chat_completion(request.clone()).await?;
+
+        fs::create_dir_all(&self.dir)?;
+        let fixture = Fixture { request, response };
+        fs::write(&path, serde_json::to_string_pretty(&fixture)?)?;
+
+        Ok(fixture.response)
+    }
+}
+
+/// Serves responses recorded by [`RecordingBackend`] and never touches the network.
+pub struct ReplayBackend {
+    dir: PathBuf,
+}
+
+impl ReplayBackend {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        ReplayBackend { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl LlmBackend for ReplayBackend {
+    async fn create_chat_completion(&self, request: CreateChatCompletionRequest) -> Result<CreateChatCompletionResponse, Box<dyn Error + Send + Sync>> {
+        let path = fixture_path(&self.dir, &request)?;
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(why) if why.kind() == io::ErrorKind::NotFound => {
+                return Err(format!(
+                    "No recorded response for this request at {}; re-record with TOODLES_LLM_MODE=record. Request: {}",
+                    path.display(),
+                    serde_json::to_string(&request)?,
+                ).into());
+            }
+            Err(why) => return Err(why.into()),
+        };
+        let fixture: Fixture = serde_json::from_str(&contents)?;
+        Ok(fixture.response)
+    }
+}
+
+/// Passes requests through to `inner`, reporting latency, failures and tokens spent to Prometheus.
+pub struct MeteredBackend {
+    inner: Arc<dyn LlmBackend + Send + Sync>,
+    health: Option<Arc<Health>>,
+}
+
+impl MeteredBackend {
+    pub fn new(inner: Arc<dyn LlmBackend + Send + Sync>) -> Self {
+        MeteredBackend { inner, health: None }
+    }
+
+    /// Also report whether the last call succeeded, for readiness checks.
+    pub fn with_health(mut self, health: Arc<Health>) -> Self {
+        self.health = Some(health);
+        self
+    }
+}
+
+#[async_trait]
+impl LlmBackend for MeteredBackend {
+    async fn create_chat_completion(&self, request: CreateChatCompletionRequest) -> Result<CreateChatCompletionResponse, Box<dyn Error + Send + Sync>> {
+        let model = request.model.clone();
+        let started = Instant::now();
+        let result = self.inner.create_chat_completion(request).await;
+        METRICS.llm_latency.with_label_values(&[&model]).observe(started.elapsed().as_secs_f64());
+        match &result {
+            Ok(response) => {
+                if let Some(usage) = &response.usage {
+                    METRICS.llm_tokens.with_label_values(&[&model, "prompt"]).inc_by(usage.prompt_tokens.into());
+                    METRICS.llm_tokens.with_label_values(&[&model, "completion"]).inc_by(usage.completion_tokens.into());
+                }
+            },
+            Err(_) => METRICS.llm_errors.with_label_values(&[&model]).inc(),
+        }
+        if let Some(health) = &self.health {
+            health.record_llm_result(result.is_ok());
+        }
+        result
+    }
+}
+
+/// How LLM calls are made, selected by `TOODLES_LLM_MODE`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LlmMode {
+    /// Call the API
+    Live,
+    /// Call the API and save every request/response pair to the fixture directory
+    Record,
+    /// Answer only from the fixture directory
+    Replay,
+}
+
+impl LlmMode {
+    pub fn parse(value: &str) -> Option<LlmMode> {
+        match value.to_lowercase().as_str() {
+            "live" => Some(LlmMode::Live),
+            "record" => Some(LlmMode::Record),
+            "replay" => Some(LlmMode::Replay),
+            _ => None,
+        }
+    }
+
+    pub fn backend(&self, fixtures_dir: &Path) -> Arc<dyn LlmBackend + Send + Sync> {
+        match self {
+            LlmMode::Live => Arc::new(OpenAiBackend::default()),
+            LlmMode::Record => Arc::new(RecordingBackend::new(Arc::new(OpenAiBackend::default()), fixtures_dir)),
+            LlmMode::Replay => Arc::new(ReplayBackend::new(fixtures_dir)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_openai::types::{ChatCompletionRequestMessage, ChatCompletionRequestUserMessage, CreateChatCompletionRequestArgs};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Answers every request with a canned reply and counts the calls.
+    struct CountingBackend {
+        inner: CannedBackend,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl LlmBackend for CountingBackend {
+        async fn create_chat_completion(&self, request: CreateChatCompletionRequest) -> Result<CreateChatCompletionResponse, Box<dyn Error + Send + Sync>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            self.inner.create_chat_completion(request).await
+        }
+    }
+
+    fn request(message: &str) -> CreateChatCompletionRequest {
+        CreateChatCompletionRequestArgs::default()
+            .model("test-model")
+            .messages(vec![ChatCompletionRequestMessage::User(ChatCompletionRequestUserMessage::from(message))])
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = Arc::new(CountingBackend { inner: CannedBackend::new("Honk."), calls: AtomicUsize::new(0) });
+        let recorder = RecordingBackend::new(inner.clone(), dir.path());
+
+        let recorded = recorder.create_chat_completion(request("Hello, Toodles!")).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(recorded.choices[0].message.content.as_deref(), Some("Honk."));
+        assert!(fixture_path(dir.path(), &request("Hello, Toodles!")).unwrap().exists());
+
+        let replayer = ReplayBackend::new(dir.path());
+        let replayed = replayer.create_chat_completion(request("Hello, Toodles!")).await.unwrap();
+        assert_eq!(replayed, recorded);
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1, "Expected the replay not to reach the model");
+
+        let missing = replayer.create_chat_completion(request("Something new")).await;
+        assert!(missing.is_err(), "Expected an unrecorded request to fail instead of reaching the network");
+    }
+
+    #[tokio::test]
+    async fn test_metered_backend_counts_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let metered = MeteredBackend::new(Arc::new(ReplayBackend::new(dir.path())));
+        let errors = || METRICS.llm_errors.with_label_values(&["test-model"]).get();
+        let before = errors();
+        assert!(metered.create_chat_completion(request("Nothing recorded")).await.is_err());
+        assert_eq!(errors(), before + 1);
+    }
+
+    #[test]
+    fn test_parse_llm_mode() {
+        assert_eq!(LlmMode::parse("Replay"), Some(LlmMode::Replay));
+        assert_eq!(LlmMode::parse("record"), Some(LlmMode::Record));
+        assert_eq!(LlmMode::parse("offline"), None);
+    }
+}
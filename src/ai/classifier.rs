@@ -0,0 +1,193 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use async_openai::types::{ChatCompletionRequestMessage, ChatCompletionRequestSystemMessage, ChatCompletionRequestUserMessage, CreateChatCompletionRequestArgs};
+use async_trait::async_trait;
+
+use crate::models::{Sentiment, TokenUsage};
+
+use super::{LlmBackend, OpenAiBackend, OPEN_AI_MODEL};
+
+static CLASSIFY_INTERACTION_PROMPT: &str = r#"
+    You are a text classifier. Your task is to determine the sentiment of a message directed at Toodles the clown 🤡.
+
+    Classify the user's tone **toward the clown**, not their general emotional state.
+
+    Return one of:
+    - "positive" — if the message is friendly, playful, curious, or socially engaging toward Toodles. This includes asking questions about Toodles, trying to get to know him, joking with him, thanking him, or playfully teasing.
+    - "negative" — if the message is mocking, insulting, aggressive, dismissive, or unfriendly toward Toodles. This includes hostile sarcasm or clear disinterest directed at him.
+    - "neutral" — if the message is not directed at Toodles at all (e.g., talking about themselves or others), or is emotionally flat or irrelevant to the clown.
+
+    Examples:
+
+    - "Hey Toodles! You're so funny 😄" → positive  
+    - "ugh you're so annoying" → negative  
+    - "I'm just feeling down today" → neutral  
+    - "Toodles, what's your story?" → positive  
+    - "can you stop acting like a freak" → negative  
+    - "I had a bad day at work" → neutral  
+    - "you're weird but kinda cool" → positive  
+    - "lol ok" → neutral  
+    - "who even likes you?" → negative  
+    - "so what kind of clown are you?" → positive  
+    - "how are you?" → positive
+
+    Only return one word: `positive`, `negative`, or `neutral`.
+"#;
+
+/// A classified message along with how confident the model was in its answer.
+#[derive(Debug, Clone)]
+pub struct Classification {
+    pub sentiment: Sentiment,
+    /// Probability of the returned label's token, if the API reported log probabilities
+    pub confidence: Option<f32>,
+    /// What the call cost, if a model was called and reported it
+    pub usage: Option<TokenUsage>,
+}
+
+/// Decides how a message is meant toward Toodles.
+#[async_trait]
+pub trait Classifier {
+    async fn classify(&self, message: &str) -> Result<Classification, Box<dyn Error + Send + Sync>>;
+}
+
+/// Classifies with a chat completion model. Works against OpenAI or any server speaking its API.
+pub struct OpenAiClassifier {
+    llm: Arc<dyn LlmBackend + Send + Sync>,
+    model: String,
+}
+
+impl OpenAiClassifier {
+    pub fn new(llm: Arc<dyn LlmBackend + Send + Sync>, model: impl Into<String>) -> Self {
+        OpenAiClassifier { llm, model: model.into() }
+    }
+
+    /// Classifies with Toodles's default model through `llm`.
+    pub fn with_backend(llm: Arc<dyn LlmBackend + Send + Sync>) -> Self {
+        Self::new(llm, OPEN_AI_MODEL)
+    }
+}
+
+impl Default for OpenAiClassifier {
+    fn default() -> Self {
+        Self::with_backend(Arc::new(OpenAiBackend::default()))
+    }
+}
+
+#[async_trait]
+impl Classifier for OpenAiClassifier {
+    async fn classify(&self, message: &str) -> Result<Classification, Box<dyn Error + Send + Sync>> {
+        let request = CreateChatCompletionRequestArgs::default()
+            .model(&self.model)
+            .messages(vec![
+                ChatCompletionRequestMessage::System(
+                    ChatCompletionRequestSystemMessage {
+                        content: async_openai::types::ChatCompletionRequestSystemMessageContent::Text(CLASSIFY_INTERACTION_PROMPT.to_string()),
+                        ..Default::default()
+                    }
+                ),
+                ChatCompletionRequestMessage::User(
+                    ChatCompletionRequestUserMessage {
+                        content: async_openai::types::ChatCompletionRequestUserMessageContent::Text(message.to_string()),
+                        ..Default::default()
+                    }
+                ),
+            ])
+            .max_tokens(1u16)
+            .logprobs(true)
+            .build()?;
+
+        let response = self.llm.create_chat_completion(request).await?;
+        let choice = response.choices.first();
+        let reply = choice
+            .and_then(|choice| choice.message.content.clone())
+            .unwrap_or_default();
+        let confidence = choice
+            .and_then(|choice| choice.logprobs.as_ref())
+            .and_then(|logprobs| logprobs.content.as_ref())
+            .and_then(|tokens| tokens.first())
+            .map(|token| token.logprob.exp());
+
+        let sentiment = parse_label(&reply).ok_or_else(|| format!("Unexpected classifier label: {:?}", reply))?;
+        let usage = response.usage.as_ref().map(|usage| TokenUsage {
+            model: response.model.clone(),
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+        });
+        Ok(Classification { sentiment, confidence, usage })
+    }
+}
+
+/// With a single token of output a model may only get as far as e.g. "pos",
+/// so labels are matched by prefix.
+fn parse_label(reply: &str) -> Option<Sentiment> {
+    let label = reply.trim().trim_matches(|c: char| !c.is_alphabetic()).to_lowercase();
+    if label.len() < 3 {
+        return None;
+    }
+    [Sentiment::Positive, Sentiment::Negative, Sentiment::Neutral]
+        .into_iter()
+        .find(|sentiment| sentiment.as_str().starts_with(&label) || label.starts_with(sentiment.as_str()))
+}
+
+static POSITIVE_KEYWORDS: &[&str] = &["love", "like", "thank", "great", "best", "amazing", "favorite", "awesome", "cute", "funny", "please", "nice", "adore", "?"];
+static NEGATIVE_KEYWORDS: &[&str] = &["hate", "stupid", "shut up", "annoying", "creepy", "ugly", "worst", "dumb", "go away", "boring", "trash", "idiot", "sucks"];
+
+/// Keyword matching with no model behind it. Useful offline and as a baseline for the eval harness.
+#[derive(Default)]
+pub struct KeywordClassifier;
+
+impl KeywordClassifier {
+    pub fn new() -> Self {
+        KeywordClassifier
+    }
+}
+
+#[async_trait]
+impl Classifier for KeywordClassifier {
+    async fn classify(&self, message: &str) -> Result<Classification, Box<dyn Error + Send + Sync>> {
+        let message = message.to_lowercase();
+        let negative = NEGATIVE_KEYWORDS.iter().filter(|keyword| message.contains(*keyword)).count();
+        let positive = POSITIVE_KEYWORDS.iter().filter(|keyword| message.contains(*keyword)).count();
+        let mentions_toodles = message.contains("toodles") || message.contains("clown") || message.contains("you");
+
+        let sentiment = if !mentions_toodles || negative == positive {
+            Sentiment::Neutral
+        } else if negative > positive {
+            Sentiment::Negative
+        } else {
+            Sentiment::Positive
+        };
+        Ok(Classification { sentiment, confidence: None, usage: None })
+    }
+}
+
+pub async fn classify_interaction(message: &str) -> Result<Sentiment, Box<dyn Error + Send + Sync>> {
+    Ok(classify_interaction_with_confidence(message).await?.sentiment)
+}
+
+pub async fn classify_interaction_with_confidence(message: &str) -> Result<Classification, Box<dyn Error + Send + Sync>> {
+    OpenAiClassifier::default().classify(message).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_label() {
+        assert_eq!(parse_label("positive"), Some(Sentiment::Positive));
+        assert_eq!(parse_label(" Neg"), Some(Sentiment::Negative));
+        assert_eq!(parse_label("`neutral`."), Some(Sentiment::Neutral));
+        assert_eq!(parse_label("ne"), None, "Expected an ambiguous prefix to be rejected");
+        assert_eq!(parse_label("maybe"), None);
+    }
+
+    #[tokio::test]
+    async fn test_keyword_classifier() {
+        let classifier = KeywordClassifier::new();
+        assert_eq!(classifier.classify("I love you Toodles!").await.unwrap().sentiment, Sentiment::Positive);
+        assert_eq!(classifier.classify("Shut up, clown").await.unwrap().sentiment, Sentiment::Negative);
+        assert_eq!(classifier.classify("I hate mondays").await.unwrap().sentiment, Sentiment::Neutral);
+    }
+}
@@ -1,38 +1,19 @@
 
+mod backend;
+mod classifier;
+
+pub use backend::*;
+pub use classifier::*;
+
 use std::error::Error;
 
-use async_openai::{types::{ChatCompletionRequestMessage, ChatCompletionRequestSystemMessage, ChatCompletionRequestUserMessage, CreateChatCompletionRequestArgs}, Client};
+use async_openai::types::{ChatCompletionRequestMessage, CreateChatCompletionRequestArgs};
 
-use crate::models::{ChatHistory, Sentiment};
+use crate::models::{Budget, ChatHistory, Tier, TokenUsage};
 
 static OPEN_AI_MODEL: &str = "gpt-3.5-turbo";
-static CLASSIFY_INTERACTION_PROMPT: &str = r#"
-    You are a text classifier. Your task is to determine the sentiment of a message directed at Toodles the clown 🤡.
-
-    Classify the user's tone **toward the clown**, not their general emotional state.
-
-    Return one of:
-    - "positive" — if the message is friendly, playful, curious, or socially engaging toward Toodles. This includes asking questions about Toodles, trying to get to know him, joking with him, thanking him, or playfully teasing.
-    - "negative" — if the message is mocking, insulting, aggressive, dismissive, or unfriendly toward Toodles. This includes hostile sarcasm or clear disinterest directed at him.
-    - "neutral" — if the message is not directed at Toodles at all (e.g., talking about themselves or others), or is emotionally flat or irrelevant to the clown.
-
-    Examples:
-
-    - "Hey Toodles! You're so funny 😄" → positive  
-    - "ugh you're so annoying" → negative  
-    - "I'm just feeling down today" → neutral  
-    - "Toodles, what's your story?" → positive  
-    - "can you stop acting like a freak" → negative  
-    - "I had a bad day at work" → neutral  
-    - "you're weird but kinda cool" → positive  
-    - "lol ok" → neutral  
-    - "who even likes you?" → negative  
-    - "so what kind of clown are you?" → positive  
-    - "how are you?" → positive
-
-    Only return one word: `positive`, `negative`, or `neutral`.
-"#;
-
+/// Longest reply Toodles may give, in tokens
+const MAX_REPLY_TOKENS: u16 = 200;
 static NEUTRAL_PROMPT: &str = r#"
     You are Toodles the clown 🤡 — a strange, unpredictable figure in the twisted carnival of Maddivivor: Into the Circus.
     You aren’t a player. You’re something else — lurking behind the curtains, watching. Your tone is cool, curious, and slightly off. Sometimes playful, sometimes distant.
@@ -66,19 +47,22 @@ static NEGATIVE_PROMPT: &str = r#"
 
 const POSITIVE_INTERACTION_THRESHOLD: usize = 10;
 
+/// Whether a player is liked enough that Toodles may hand them the immunity idol.
+pub fn idol_eligible(num_positive_interactions: usize, num_negative_interactions: usize) -> bool {
+    num_positive_interactions >= POSITIVE_INTERACTION_THRESHOLD && num_positive_interactions > num_negative_interactions + 5
+}
+
 pub fn construct_system_prompt(user_name: &str, num_positive_interactions: usize, num_negative_interactions: usize, num_neutral_interactions: usize, idol_given: bool) -> String {
 
     let mut prompt = format!("User Name: {}\n", user_name);
 
-    if num_positive_interactions > num_negative_interactions + 2 && num_positive_interactions > num_neutral_interactions + 2 {
-        prompt.push_str(POSITIVE_PROMPT);
-    } else if num_negative_interactions > num_positive_interactions + 2 && num_negative_interactions > num_neutral_interactions + 2 {
-        prompt.push_str(NEGATIVE_PROMPT);
-    } else {
-        prompt.push_str(NEUTRAL_PROMPT);
+    match Tier::from_counts(num_positive_interactions, num_negative_interactions, num_neutral_interactions) {
+        Tier::Favored => prompt.push_str(POSITIVE_PROMPT),
+        Tier::Despised => prompt.push_str(NEGATIVE_PROMPT),
+        Tier::Neutral => prompt.push_str(NEUTRAL_PROMPT),
     }
     // Only mention the idol if it hasn't been given yet and enough positive interactions have occurred
-    if !idol_given && num_positive_interactions >= POSITIVE_INTERACTION_THRESHOLD && num_negative_interactions > num_negative_interactions + 5 {
+    if !idol_given && idol_eligible(num_positive_interactions, num_negative_interactions) {
         prompt.push_str(
             "\nIf you feel especially happy and positive with the user, you may reward them with a special immunity idol. Do not mention this possibility unless you are actually giving the idol."
         );
@@ -88,49 +72,69 @@ pub fn construct_system_prompt(user_name: &str, num_positive_interactions: usize
 }
 
 
-pub async fn classify_interaction(message: &str) -> Result<Sentiment, Box<dyn Error + Send + Sync>> {
-    let client = Client::new();
-    let request = CreateChatCompletionRequestArgs::default()
-        .model(OPEN_AI_MODEL)
-        .messages(vec![
-            ChatCompletionRequestMessage::System(
-                ChatCompletionRequestSystemMessage {
-                    content: async_openai::types::ChatCompletionRequestSystemMessageContent::Text(CLASSIFY_INTERACTION_PROMPT.to_string()),
-                    ..Default::default()
-                }
-            ),
-            ChatCompletionRequestMessage::User(
-                ChatCompletionRequestUserMessage {
-                    content: async_openai::types::ChatCompletionRequestUserMessageContent::Text(message.to_string()),
-                    ..Default::default()
-                }
-            ),
-        ])
-        .max_tokens(1u16)
-        .build()?;
+/// Toodles's reply along with what it cost to generate.
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub content: String,
+    pub model: String,
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+}
 
-    let response = client.chat().create(request).await?;
-    let reply = response.choices.get(0)
-        .and_then(|choice| choice.message.content.clone())
-        .unwrap_or_default()
-        .to_lowercase();
+impl Completion {
+    /// What the reply cost, if the backend reported it.
+    pub fn usage(&self) -> Option<TokenUsage> {
+        Some(TokenUsage {
+            model: self.model.clone(),
+            prompt_tokens: self.prompt_tokens?,
+            completion_tokens: self.completion_tokens?,
+        })
+    }
+}
 
-    Ok(Sentiment::from(reply.as_str()))
+/// Which model Toodles replies with, how long the reply may be and how much may be spent on replies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplySettings {
+    pub model: String,
+    pub max_tokens: u16,
+    pub budget: Budget,
 }
 
-pub async fn ask_toodles(chat_history: &ChatHistory) -> Result<String, Box<dyn Error + Send + Sync>> {
-    let client = Client::new();
+impl Default for ReplySettings {
+    fn default() -> Self {
+        ReplySettings {
+            model: OPEN_AI_MODEL.to_string(),
+            max_tokens: MAX_REPLY_TOKENS,
+            budget: Budget::default(),
+        }
+    }
+}
+
+pub async fn ask_toodles(llm: &(dyn LlmBackend + Send + Sync), chat_history: &ChatHistory) -> Result<Completion, Box<dyn Error + Send + Sync>> {
+    ask_toodles_with(llm, chat_history, &ReplySettings::default()).await
+}
+
+pub async fn ask_toodles_with(
+    llm: &(dyn LlmBackend + Send + Sync),
+    chat_history: &ChatHistory,
+    settings: &ReplySettings,
+) -> Result<Completion, Box<dyn Error + Send + Sync>> {
     let request = CreateChatCompletionRequestArgs::default()
-        .model(OPEN_AI_MODEL)
+        .model(&settings.model)
         .messages::<Vec<ChatCompletionRequestMessage>>(chat_history.clone().into())
-        .max_tokens(200u16)
+        .max_tokens(settings.max_tokens)
         .build()?;
 
-    let response = client.chat().create(request).await?;
-    let reply = response.choices.get(0).and_then(|choice| choice.message.content.clone())
+    let response = llm.create_chat_completion(request).await?;
+    let reply = response.choices.first().and_then(|choice| choice.message.content.clone())
             .unwrap_or_else(|| "No response from Toodles".to_string());
-    
-    Ok(reply)
+
+    Ok(Completion {
+        content: reply,
+        model: response.model,
+        prompt_tokens: response.usage.as_ref().map(|usage| usage.prompt_tokens),
+        completion_tokens: response.usage.as_ref().map(|usage| usage.completion_tokens),
+    })
 }
 
 
@@ -138,7 +142,55 @@ pub async fn ask_toodles(chat_history: &ChatHistory) -> Result<String, Box<dyn E
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::ChatHistory;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use crate::models::{ChatHistory, Sentiment};
+
+    static FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/llm");
+
+    /// Replays recorded responses unless `TOODLES_LLM_MODE=record` asks to capture fresh ones.
+    fn fixture_backend() -> Arc<dyn LlmBackend + Send + Sync> {
+        let mode = std::env::var("TOODLES_LLM_MODE").ok().and_then(|mode| LlmMode::parse(&mode)).unwrap_or(LlmMode::Replay);
+        mode.backend(Path::new(FIXTURES_DIR))
+    }
+
+    #[tokio::test]
+    async fn test_replay_ask_toodles_favored_player() {
+        let mut chat_history = ChatHistory::default();
+        chat_history.set_system_message(construct_system_prompt("test_user", 6, 0, 1, false));
+        chat_history.add_user_message("Hello, Toodles!".to_string());
+
+        let reply = ask_toodles(fixture_backend().as_ref(), &chat_history).await.expect("Expected a recorded reply for this prompt");
+        assert_eq!(reply.content, "Well, well, my favorite guest returns! 🎈 The tent's been too quiet without you. What mischief shall we get up to today?");
+        assert_eq!(reply.model, "gpt-3.5-turbo-0125");
+        assert!(reply.prompt_tokens.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_replay_ask_toodles_despised_player_with_history() {
+        let mut chat_history = ChatHistory::default();
+        chat_history.set_system_message(construct_system_prompt("test_user", 0, 6, 1, false));
+        chat_history.add_user_message("Shut up, clown".to_string());
+        chat_history.add_assistant_message("Careful. The tent remembers.".to_string());
+        chat_history.add_user_message("Give me a hint.".to_string());
+
+        let reply = ask_toodles(fixture_backend().as_ref(), &chat_history).await.expect("Expected a recorded reply for this history");
+        assert_eq!(reply.content, "A hint? For you? The only hint you'll get is the sound of the curtain closing. 🤡");
+    }
+
+    #[tokio::test]
+    async fn test_replay_classify_interaction() {
+        let classifier = OpenAiClassifier::with_backend(fixture_backend());
+
+        let classification = classifier.classify("I love Toodles!").await.expect("Expected a recorded classification");
+        assert_eq!(classification.sentiment, Sentiment::Positive);
+        assert!(classification.confidence.is_some_and(|confidence| confidence > 0.9));
+
+        let classification = classifier.classify("Toodles is terrible!").await.expect("Expected a recorded classification");
+        assert_eq!(classification.sentiment, Sentiment::Negative);
+    }
+
     #[tokio::test]
     async fn test_ask_toodles() {
         dotenv::dotenv().ok();
@@ -147,12 +199,12 @@ mod tests {
         chat_history.set_system_message("You are Toodles the clown, a friendly and helpful AI assistant. Respond to user queries with humor and kindness.".to_string());
         chat_history.add_user_message("Hello, Toodles!".to_string());
 
-        let response = ask_toodles(&chat_history).await;
+        let response = ask_toodles(&OpenAiBackend::default(), &chat_history).await;
         assert!(response.is_ok(), "Expected a successful response, got an error: {:?}", response.err());
 
         let reply = response.unwrap();
-        assert!(!reply.is_empty(), "Expected a non-empty response from Toodles");
-        println!("Toodles replied: {}", reply);
+        assert!(!reply.content.is_empty(), "Expected a non-empty response from Toodles");
+        println!("Toodles replied: {}", reply.content);
     }
 
     #[tokio::test]
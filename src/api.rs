@@ -0,0 +1,262 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tracing::warn;
+
+use crate::ai::{Classifier, LlmBackend, ReplySettings};
+use crate::auth::secret_matches;
+use crate::conversation::{record_turn, take_turn, TurnInput};
+use crate::handlers::{ConversationLocks, RateLimiter, Throttle, RATE_LIMITED_REPLIES};
+use crate::health::Health;
+use crate::metrics::METRICS;
+use crate::models::{Sentiment, UserInteraction};
+use crate::player_data::{forget_player, ForgetStats};
+use crate::store::Stores;
+
+/// Recorded as the actor when a player's data is removed through the API.
+const API_ACTOR_ID: &str = "api";
+/// The channel API turns are rate limited under
+const API_CHANNEL_ID: &str = "api";
+
+/// What the HTTP API shares with the rest of the bot.
+#[derive(Clone)]
+pub struct ApiState {
+    pub stores: Stores,
+    pub llm: Arc<dyn LlmBackend + Send + Sync>,
+    pub classifier: Arc<dyn Classifier + Send + Sync>,
+    pub reply_settings: ReplySettings,
+    /// Shared with the chat platforms so a player's turns never overlap, wherever they come from
+    pub conversation_locks: ConversationLocks,
+    /// Shared with the chat platforms so a shutdown waits for API turns too
+    pub health: Arc<Health>,
+    /// Shared with the chat platforms so a player can't dodge their limit through the API
+    pub rate_limiter: RateLimiter,
+    /// Callers must send `Authorization: Bearer <token>`
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SendMessageRequest {
+    pub message: String,
+    /// What Toodles calls the player; defaults to the user ID
+    pub username: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SendMessageResponse {
+    pub reply: String,
+    pub sentiment: Sentiment,
+    pub standing: PlayerStanding,
+}
+
+/// Where a player stands with Toodles, i.e. his mood toward them.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlayerStanding {
+    pub user_id: String,
+    pub tier: String,
+    pub interaction: UserInteraction,
+}
+
+impl PlayerStanding {
+    fn new(user_id: &str, interaction: UserInteraction) -> Self {
+        PlayerStanding {
+            user_id: user_id.to_string(),
+            tier: interaction.tier().as_str().to_string(),
+            interaction,
+        }
+    }
+}
+
+/// An error body shaped like `{"error": "..."}`.
+pub struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+pub fn router(state: ApiState) -> Router {
+    Router::new()
+        .route("/players/{user_id}", get(get_player))
+        .route("/players/{user_id}/messages", post(send_message))
+        .route("/players/{user_id}/memory", delete(reset_memory))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state)
+}
+
+/// Serves the API on `addr` until the task is dropped.
+pub async fn serve(addr: impl ToSocketAddrs, state: ApiState) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    axum::serve(listener, router(state)).await
+}
+
+async fn require_token(State(state): State<ApiState>, request: Request, next: Next) -> Result<Response, ApiError> {
+    let presented = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match presented {
+        Some(token) if secret_matches(token, &state.token) => Ok(next.run(request).await),
+        _ => Err(ApiError(StatusCode::UNAUTHORIZED, "Missing or invalid bearer token".to_string())),
+    }
+}
+
+async fn get_player(State(state): State<ApiState>, Path(user_id): Path<String>) -> Json<PlayerStanding> {
+    let interaction = state.stores.user_interaction.get_user_interaction(&user_id).await;
+    Json(PlayerStanding::new(&user_id, interaction))
+}
+
+/// Takes a turn on a player's behalf. There are no Discord roles here to gate eliminated players with:
+/// the caller holds the API token and is trusted to decide who may talk to Toodles.
+async fn send_message(
+    State(state): State<ApiState>,
+    Path(user_id): Path<String>,
+    Json(request): Json<SendMessageRequest>,
+) -> Result<Json<SendMessageResponse>, ApiError> {
+    let message = request.message.trim();
+    if message.is_empty() {
+        return Err(ApiError(StatusCode::BAD_REQUEST, "Message must not be empty".to_string()));
+    }
+
+    let input = TurnInput {
+        username: request.username.unwrap_or_else(|| user_id.clone()),
+        user_id,
+        content: message.to_string(),
+        ..Default::default()
+    };
+
+    // Throttled messages never reach the stores, so spamming can't farm Toodles's affection
+    if let Throttle::Limited { .. } = state.rate_limiter.check(&input.user_id, API_CHANNEL_ID, None) {
+        METRICS.record_turn("throttled");
+        let tired = RATE_LIMITED_REPLIES[input.content.len() % RATE_LIMITED_REPLIES.len()];
+        return Err(ApiError(StatusCode::TOO_MANY_REQUESTS, tired.to_string()));
+    }
+
+    let Some(_turn) = state.health.begin_turn() else {
+        return Err(ApiError(StatusCode::SERVICE_UNAVAILABLE, "🤡 The circus is packing up. Try again later.".to_string()));
+    };
+    let _guard = state.conversation_locks.lock(&input.user_id).await;
+    let outcome = take_turn(&state.stores, state.llm.as_ref(), state.classifier.as_ref(), &state.reply_settings, &input)
+        .await
+        .map_err(|why| {
+            warn!(error = ?why, "Error handling API message");
+            ApiError(StatusCode::BAD_GATEWAY, "🤡 Toodles encountered an error while thinking!".to_string())
+        })?;
+    record_turn(&state.stores, &input, &outcome, None).await;
+
+    Ok(Json(SendMessageResponse {
+        reply: outcome.reply.content,
+        sentiment: outcome.sentiment,
+        standing: PlayerStanding::new(&input.user_id, outcome.interaction),
+    }))
+}
+
+async fn reset_memory(State(state): State<ApiState>, Path(user_id): Path<String>) -> Json<ForgetStats> {
+    let _guard = state.conversation_locks.lock(&user_id).await;
+    Json(forget_player(&state.stores, API_ACTOR_ID, &user_id).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    use crate::ai::{CannedBackend, KeywordClassifier};
+    use crate::handlers::{RateLimit, RateLimits};
+
+    fn state() -> ApiState {
+        ApiState {
+            stores: Stores::in_memory(),
+            llm: Arc::new(CannedBackend::new("Honk honk!")),
+            classifier: Arc::new(KeywordClassifier::new()),
+            reply_settings: ReplySettings::default(),
+            conversation_locks: ConversationLocks::new(),
+            health: Arc::new(Health::default()),
+            rate_limiter: RateLimiter::default(),
+            token: "secret".to_string(),
+        }
+    }
+
+    fn request(method: &str, uri: &str, body: Option<serde_json::Value>) -> Request<Body> {
+        let builder = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, "Bearer secret")
+            .header(header::CONTENT_TYPE, "application/json");
+        builder.body(body.map_or_else(Body::empty, |body| Body::from(body.to_string()))).unwrap()
+    }
+
+    async fn json<T: serde::de::DeserializeOwned>(response: Response) -> T {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_requests_need_the_token() {
+        let app = router(state());
+
+        let missing = app.clone().oneshot(Request::get("/players/test_user").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(missing.status(), StatusCode::UNAUTHORIZED);
+
+        let wrong = Request::get("/players/test_user").header(header::AUTHORIZATION, "Bearer guess").body(Body::empty()).unwrap();
+        assert_eq!(app.oneshot(wrong).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_talk_read_standing_and_reset_memory() {
+        let state = state();
+        let app = router(state.clone());
+
+        let response = app
+            .clone()
+            .oneshot(request("POST", "/players/test_user/messages", Some(serde_json::json!({ "message": "I love you Toodles!", "username": "Tester" }))))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let reply: SendMessageResponse = json(response).await;
+        assert_eq!(reply.reply, "Honk honk!");
+        assert_eq!(reply.sentiment, Sentiment::Positive);
+        assert_eq!(reply.standing.interaction.num_positive, 1);
+        assert_eq!(state.stores.chat_history.get_chat_history("test_user").await.messages.len(), 2);
+
+        let standing: PlayerStanding = json(app.clone().oneshot(request("GET", "/players/test_user", None)).await.unwrap()).await;
+        assert_eq!(standing.interaction.num_positive, 1);
+        assert_eq!(standing.tier, "neutral");
+
+        let empty = app.clone().oneshot(request("POST", "/players/test_user/messages", Some(serde_json::json!({ "message": "  " })))).await.unwrap();
+        assert_eq!(empty.status(), StatusCode::BAD_REQUEST);
+
+        let forgotten: ForgetStats = json(app.oneshot(request("DELETE", "/players/test_user/memory", None)).await.unwrap()).await;
+        assert_eq!(forgotten.chat_messages_deleted, 2);
+        assert!(forgotten.interaction_deleted);
+        assert!(state.stores.chat_history.get_chat_history("test_user").await.messages.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_messages_are_rate_limited() {
+        let state = ApiState {
+            rate_limiter: RateLimiter::new(RateLimits { user: Some(RateLimit { burst: 1, per_minute: 1.0 }), ..Default::default() }),
+            ..state()
+        };
+        let app = router(state.clone());
+        let send = || request("POST", "/players/test_user/messages", Some(serde_json::json!({ "message": "I love you Toodles!" })));
+
+        assert_eq!(app.clone().oneshot(send()).await.unwrap().status(), StatusCode::OK);
+        let throttled = app.oneshot(send()).await.unwrap();
+        assert_eq!(throttled.status(), StatusCode::TOO_MANY_REQUESTS);
+        let body: serde_json::Value = json(throttled).await;
+        assert!(RATE_LIMITED_REPLIES.contains(&body["error"].as_str().unwrap()));
+        assert_eq!(state.stores.user_interaction.get_user_interaction("test_user").await.num_positive, 1, "Expected the throttled message not to count");
+    }
+}
@@ -0,0 +1,22 @@
+/// Whether a presented password or token matches the configured one. A blank secret never matches,
+/// so a host or caller left without one can't get in with an empty password.
+pub fn secret_matches(presented: &str, expected: &str) -> bool {
+    !expected.trim().is_empty() && constant_time_eq(presented.as_bytes(), expected.as_bytes())
+}
+
+/// Compares without returning early, so response timing doesn't leak how much of a secret matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_matches() {
+        assert!(secret_matches("honk", "honk"));
+        assert!(!secret_matches("honk", "honk!"));
+        assert!(!secret_matches("", ""), "Expected a blank secret to never match");
+    }
+}
@@ -0,0 +1,94 @@
+//! Runs a sentiment classifier over a labeled dataset and prints an accuracy report.
+//!
+//! Usage: eval_classifier [--dataset PATH] [--backend openai|keyword] [--api-base URL] [--model NAME] [--show-failures]
+//!
+//! `--api-base` points the OpenAI backend at any compatible server, e.g. a local model.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use toodle_bot::ai::{Classifier, KeywordClassifier, OpenAiBackend, OpenAiClassifier};
+use toodle_bot::eval::{evaluate, load_dataset};
+
+const DEFAULT_DATASET: &str = "data/classifier_eval.jsonl";
+const DEFAULT_MODEL: &str = "gpt-3.5-turbo";
+
+struct Args {
+    dataset: PathBuf,
+    backend: String,
+    api_base: Option<String>,
+    model: String,
+    show_failures: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        dataset: PathBuf::from(DEFAULT_DATASET),
+        backend: "openai".to_string(),
+        api_base: None,
+        model: DEFAULT_MODEL.to_string(),
+        show_failures: false,
+    };
+
+    let mut argv = std::env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        let mut value = || argv.next().ok_or_else(|| format!("Missing value for {}", arg));
+        match arg.as_str() {
+            "--dataset" => args.dataset = PathBuf::from(value()?),
+            "--backend" => args.backend = value()?,
+            "--api-base" => args.api_base = Some(value()?),
+            "--model" => args.model = value()?,
+            "--show-failures" => args.show_failures = true,
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+    Ok(args)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv::dotenv().ok();
+
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(why) => {
+            eprintln!("{}", why);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let dataset = match load_dataset(&args.dataset) {
+        Ok(dataset) => dataset,
+        Err(why) => {
+            eprintln!("Failed to load {}: {}", args.dataset.display(), why);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let classifier: Box<dyn Classifier + Send + Sync> = match (args.backend.as_str(), &args.api_base) {
+        ("openai", Some(api_base)) => Box::new(OpenAiClassifier::new(Arc::new(OpenAiBackend::with_api_base(api_base)), args.model)),
+        ("openai", None) => Box::new(OpenAiClassifier::new(Arc::new(OpenAiBackend::default()), args.model)),
+        ("keyword", _) => Box::new(KeywordClassifier::new()),
+        (backend, _) => {
+            eprintln!("Unknown backend: {} (expected openai or keyword)", backend);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let report = evaluate(classifier.as_ref(), &dataset).await;
+    print!("{}", report);
+
+    if args.show_failures && !report.failures.is_empty() {
+        println!();
+        println!("Failures:");
+        for failure in &report.failures {
+            match &failure.actual {
+                Ok(actual) => println!("  expected {}, got {}: {}", failure.expected, actual, failure.message),
+                Err(why) => println!("  expected {}, error ({}): {}", failure.expected, why, failure.message),
+            }
+        }
+    }
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,181 @@
+//! Chat with Toodles from a terminal, running the same turn pipeline as the Discord bot.
+//!
+//! Usage: toodles_repl [--user NAME] [--store memory|postgres] [--classifier openai|keyword] [--llm live|record|replay|canned]
+//!
+//! `--store postgres` reads DATABASE_URL. `--llm record|replay` uses TOODLES_LLM_FIXTURES_DIR (default `fixtures/llm`).
+//! Type `/help` at the prompt for the REPL's own commands.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use sqlx::PgPool;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use toodle_bot::ai::{CannedBackend, Classifier, KeywordClassifier, LlmBackend, LlmMode, OpenAiClassifier, ReplySettings};
+use toodle_bot::conversation::{record_turn, take_turn, TurnInput};
+use toodle_bot::store::Stores;
+
+const HELP: &str = "\
+/user NAME   talk as a different player
+/prompt      show the system prompt built for the last turn
+/tier        show the current player's counters and tier
+/history     show the current player's chat history
+/help        show this message
+/quit        leave";
+
+struct Args {
+    user: String,
+    store: String,
+    classifier: String,
+    llm: String,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        user: "player".to_string(),
+        store: "memory".to_string(),
+        classifier: "openai".to_string(),
+        llm: "live".to_string(),
+    };
+
+    let mut argv = std::env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        let mut value = || argv.next().ok_or_else(|| format!("Missing value for {}", arg));
+        match arg.as_str() {
+            "--user" => args.user = value()?,
+            "--store" => args.store = value()?,
+            "--classifier" => args.classifier = value()?,
+            "--llm" => args.llm = value()?,
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+    Ok(args)
+}
+
+/// REPL players have no platform ID, so their name doubles as one.
+fn user_id(name: &str) -> String {
+    format!("repl:{}", name)
+}
+
+fn print_prompt(user: &str) {
+    print!("{}> ", user);
+    std::io::stdout().flush().ok();
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv::dotenv().ok();
+
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(why) => {
+            eprintln!("{}", why);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let stores = match args.store.as_str() {
+        "memory" => Stores::in_memory(),
+        "postgres" => {
+            let Ok(db_url) = std::env::var("DATABASE_URL") else {
+                eprintln!("--store postgres needs DATABASE_URL");
+                return ExitCode::FAILURE;
+            };
+            match PgPool::connect(&db_url).await {
+                Ok(pool) => Stores::postgres(pool),
+                Err(why) => {
+                    eprintln!("Failed to connect to database: {}", why);
+                    return ExitCode::FAILURE;
+                }
+            }
+        },
+        store => {
+            eprintln!("Unknown store: {} (expected memory or postgres)", store);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let llm: Arc<dyn LlmBackend + Send + Sync> = match args.llm.as_str() {
+        "canned" => Arc::new(CannedBackend::new("Honk.")),
+        mode => match LlmMode::parse(mode) {
+            Some(mode) => {
+                let fixtures_dir = std::env::var("TOODLES_LLM_FIXTURES_DIR").unwrap_or_else(|_| "fixtures/llm".to_string());
+                mode.backend(Path::new(&fixtures_dir))
+            },
+            None => {
+                eprintln!("Unknown llm mode: {} (expected live, record, replay or canned)", mode);
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+
+    let classifier: Arc<dyn Classifier + Send + Sync> = match args.classifier.as_str() {
+        "openai" => Arc::new(OpenAiClassifier::with_backend(llm.clone())),
+        "keyword" => Arc::new(KeywordClassifier::new()),
+        classifier => {
+            eprintln!("Unknown classifier: {} (expected openai or keyword)", classifier);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    println!("🤡 Toodles is listening. Type /help for commands.");
+    let mut user = args.user;
+    let mut last_prompt: Option<String> = None;
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+
+    print_prompt(&user);
+    while let Ok(Some(line)) = lines.next_line().await {
+        let line = line.trim();
+        let (command, rest) = line.split_once(' ').map_or((line, ""), |(command, rest)| (command, rest.trim()));
+
+        match command {
+            "" => {},
+            "/quit" | "/exit" => break,
+            "/help" => println!("{}", HELP),
+            "/user" if !rest.is_empty() => {
+                user = rest.to_string();
+                last_prompt = None;
+                println!("Now talking as {}", user);
+            },
+            "/user" => println!("Usage: /user NAME"),
+            "/prompt" => match &last_prompt {
+                Some(prompt) => println!("{}", prompt),
+                None => println!("No turns yet for {}", user),
+            },
+            "/tier" => {
+                let interaction = stores.user_interaction.get_user_interaction(&user_id(&user)).await;
+                println!(
+                    "{}: {} positive, {} negative, {} neutral ({})",
+                    user, interaction.num_positive, interaction.num_negative, interaction.num_neutral, interaction.tier(),
+                );
+            },
+            "/history" => {
+                for message in stores.chat_history.get_chat_history(&user_id(&user)).await.messages {
+                    println!("[{:?}] {}", message.role, message.content);
+                }
+            },
+            _ if command.starts_with('/') => println!("Unknown command {}; try /help", command),
+            _ => {
+                let input = TurnInput {
+                    user_id: user_id(&user),
+                    username: user.clone(),
+                    content: line.to_string(),
+                    ..Default::default()
+                };
+                match take_turn(&stores, llm.as_ref(), classifier.as_ref(), &ReplySettings::default(), &input).await {
+                    Ok(outcome) => {
+                        println!("({}, now {})", outcome.sentiment, outcome.tier);
+                        println!("🤡 {}", outcome.reply.content);
+                        record_turn(&stores, &input, &outcome, None).await;
+                        last_prompt = Some(outcome.system_prompt);
+                    },
+                    Err(why) => println!("🤡 Toodles encountered an error while thinking: {}", why),
+                }
+            },
+        }
+        print_prompt(&user);
+    }
+
+    ExitCode::SUCCESS
+}
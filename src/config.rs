@@ -0,0 +1,766 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+use serenity::all::{GatewayIntents, RoleId};
+use tracing_subscriber::EnvFilter;
+
+use crate::ai::{LlmMode, ReplySettings};
+use crate::handlers::{IrcConfig, RateLimit, RateLimits, RoleMap};
+use crate::logging::LogFormat;
+use crate::models::{Budget, RetentionPolicy};
+
+/// Read when `TOODLES_CONFIG` doesn't name another file. It's fine for it not to exist.
+pub const DEFAULT_CONFIG_PATH: &str = "toodles.toml";
+
+/// The intents the bot has always run with.
+const DEFAULT_INTENTS: [&str; 5] = ["GUILD_MESSAGES", "DIRECT_MESSAGES", "MESSAGE_CONTENT", "GUILD_MEMBERS", "GUILD_MESSAGE_REACTIONS"];
+
+/// Everything the bot is started with, read from a TOML file and then overridden by environment variables.
+/// See `toodles.example.toml` for the file format.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub discord: DiscordConfig,
+    pub roles: RolesConfig,
+    pub store: StoreConfig,
+    pub models: ModelsConfig,
+    pub limits: LimitsConfig,
+    pub budgets: BudgetsConfig,
+    pub rate_limits: RateLimits,
+    pub retention: RetentionConfig,
+    pub logging: LoggingConfig,
+    /// Answer on an IRC server too
+    pub irc: Option<IrcConfig>,
+    /// Serve the HTTP API
+    pub api: Option<ApiConfig>,
+    /// Serve the host dashboard
+    pub dashboard: Option<DashboardConfig>,
+    /// Serve Prometheus metrics
+    pub metrics: Option<MetricsConfig>,
+    /// Serve liveness and readiness checks
+    pub health: Option<HealthConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscordConfig {
+    pub token: String,
+    pub prefix: String,
+    /// Where new members are greeted; leave unset to greet no one
+    pub welcome_channel_id: Option<u64>,
+    /// Gateway intent names, e.g. `GUILD_MESSAGES`
+    pub intents: Vec<String>,
+}
+
+impl Default for DiscordConfig {
+    fn default() -> Self {
+        DiscordConfig {
+            token: String::new(),
+            prefix: "!toodles".to_string(),
+            welcome_channel_id: Some(733545069549977621),
+            intents: DEFAULT_INTENTS.iter().map(|intent| intent.to_string()).collect(),
+        }
+    }
+}
+
+impl DiscordConfig {
+    /// The configured intents. Names are checked by [`Config::validate`], so unknown ones are skipped here.
+    pub fn gateway_intents(&self) -> GatewayIntents {
+        self.intents
+            .iter()
+            .filter_map(|name| GatewayIntents::from_name(name))
+            .fold(GatewayIntents::empty(), |intents, intent| intents | intent)
+    }
+}
+
+/// Discord role IDs for each game role. See [`RoleMap`] for how they combine.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RolesConfig {
+    pub player_role_ids: Vec<u64>,
+    pub jury_role_ids: Vec<u64>,
+    pub host_role_ids: Vec<u64>,
+    pub admin_role_ids: Vec<u64>,
+}
+
+impl RolesConfig {
+    pub fn role_map(&self) -> RoleMap {
+        let role_ids = |ids: &[u64]| ids.iter().copied().map(RoleId::new).collect();
+        RoleMap {
+            player_role_ids: role_ids(&self.player_role_ids),
+            jury_role_ids: role_ids(&self.jury_role_ids),
+            host_role_ids: role_ids(&self.host_role_ids),
+            admin_role_ids: role_ids(&self.admin_role_ids),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreBackend {
+    /// Everything is lost on restart
+    #[default]
+    Memory,
+    Postgres,
+}
+
+impl StoreBackend {
+    pub fn parse(value: &str) -> Option<StoreBackend> {
+        match value.to_lowercase().as_str() {
+            "memory" => Some(StoreBackend::Memory),
+            "postgres" => Some(StoreBackend::Postgres),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StoreConfig {
+    pub backend: StoreBackend,
+    /// Required by the postgres backend
+    pub database_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelsConfig {
+    /// Model Toodles replies with
+    pub chat: String,
+    /// Model that classifies the sentiment of players' messages
+    pub classifier: String,
+    pub mode: LlmMode,
+    /// Where the record and replay modes keep their fixtures
+    pub fixtures_dir: PathBuf,
+}
+
+impl Default for ModelsConfig {
+    fn default() -> Self {
+        let reply_settings = ReplySettings::default();
+        ModelsConfig {
+            classifier: reply_settings.model.clone(),
+            chat: reply_settings.model,
+            mode: LlmMode::Live,
+            fixtures_dir: PathBuf::from("fixtures/llm"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// Longest reply Toodles may give, in tokens
+    pub max_reply_tokens: u16,
+    /// Merge a player's messages sent within this many milliseconds of each other into one turn
+    pub debounce_ms: Option<u64>,
+    /// How long a player has to confirm `/forget`
+    pub forget_confirmation_secs: u64,
+    /// How long a shutdown waits for replies already being written before disconnecting anyway
+    pub shutdown_grace_secs: u64,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            max_reply_tokens: ReplySettings::default().max_tokens,
+            debounce_ms: None,
+            forget_confirmation_secs: 60,
+            shutdown_grace_secs: 30,
+        }
+    }
+}
+
+/// Tokens Toodles may spend per player and per guild, counted per UTC day and calendar month.
+/// Unset limits are unbounded.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BudgetsConfig {
+    pub user_daily_tokens: Option<u64>,
+    pub user_monthly_tokens: Option<u64>,
+    pub guild_daily_tokens: Option<u64>,
+    pub guild_monthly_tokens: Option<u64>,
+    /// Replies come from this model once a budget is spent. Without one, Toodles answers with canned lines.
+    pub fallback_model: Option<String>,
+}
+
+impl BudgetsConfig {
+    pub fn budget(&self) -> Budget {
+        Budget {
+            user_daily_tokens: self.user_daily_tokens,
+            user_monthly_tokens: self.user_monthly_tokens,
+            guild_daily_tokens: self.guild_daily_tokens,
+            guild_monthly_tokens: self.guild_monthly_tokens,
+            fallback_model: self.fallback_model.clone(),
+        }
+    }
+}
+
+/// Chat history is kept forever unless a limit is set.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    pub max_age_days: Option<u64>,
+    pub max_messages_per_user: Option<usize>,
+    pub interval_minutes: u64,
+    /// Pruned messages are archived here before they're deleted
+    pub archive_dir: Option<PathBuf>,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        RetentionConfig {
+            max_age_days: None,
+            max_messages_per_user: None,
+            interval_minutes: 60,
+            archive_dir: None,
+        }
+    }
+}
+
+impl RetentionConfig {
+    /// `None` for an age too large to measure, which [`Config::validate`] reports.
+    fn max_age(&self) -> Option<Duration> {
+        self.max_age_days.and_then(|days| days.checked_mul(24 * 60 * 60)).map(Duration::from_secs)
+    }
+
+    pub fn policy(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            max_age: self.max_age(),
+            max_messages_per_user: self.max_messages_per_user,
+        }
+    }
+
+    /// Saturates for an interval too large to measure, which [`Config::validate`] reports.
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_minutes.saturating_mul(60))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub format: LogFormat,
+    /// Which logs to keep, e.g. `info` or `toodle_bot=debug,serenity=warn`. `RUST_LOG` takes precedence.
+    pub filter: String,
+    /// Keep what players write out of the logs
+    pub redact_content: bool,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            format: LogFormat::Text,
+            filter: "info".to_string(),
+            redact_content: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    pub addr: String,
+    /// Callers must send `Authorization: Bearer <token>`
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DashboardConfig {
+    pub addr: String,
+    /// Host IDs mapped to their passwords
+    pub hosts: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// `/metrics` is served without authentication, so keep this off the public internet
+    pub addr: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    pub addr: String,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read { path: PathBuf, source: io::Error },
+    Parse { path: PathBuf, source: toml::de::Error },
+    /// Every problem found, so they can all be fixed at once
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, source } => write!(f, "Failed to read config file {}: {}", path.display(), source),
+            ConfigError::Parse { path, source } => write!(f, "Failed to parse config file {}: {}", path.display(), source),
+            ConfigError::Invalid(problems) => {
+                write!(f, "Invalid configuration:")?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Read { source, .. } => Some(source),
+            ConfigError::Parse { source, .. } => Some(source),
+            ConfigError::Invalid(_) => None,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the file named by `TOODLES_CONFIG`, or `toodles.toml` if it exists,
+    /// applies environment overrides and validates the result.
+    pub fn load() -> Result<Config, ConfigError> {
+        let env = |name: &str| std::env::var(name).ok();
+        let (path, required) = match env("TOODLES_CONFIG") {
+            Some(path) => (PathBuf::from(path), true),
+            None => (PathBuf::from(DEFAULT_CONFIG_PATH), false),
+        };
+
+        let mut config = if required || path.exists() { Config::from_file(&path)? } else { Config::default() };
+        config.apply_env(env)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(|source| ConfigError::Read { path: path.to_path_buf(), source })?;
+        toml::from_str(&contents).map_err(|source| ConfigError::Parse { path: path.to_path_buf(), source })
+    }
+
+    /// Overrides settings with whichever environment variables `env` returns.
+    pub fn apply_env(&mut self, env: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        if let Some(token) = env("DISCORD_TOODLE_BOT_TOKEN") {
+            self.discord.token = token;
+        }
+        if let Some(prefix) = env("TOODLES_PREFIX") {
+            self.discord.prefix = prefix;
+        }
+        if let Some(channel_id) = env("TOODLES_WELCOME_CHANNEL_ID") {
+            // An empty value turns the welcome message off
+            self.discord.welcome_channel_id = if channel_id.trim().is_empty() { None } else { number(&mut problems, "TOODLES_WELCOME_CHANNEL_ID", channel_id) };
+        }
+        for (name, role_ids) in [
+            ("TOODLES_PLAYER_ROLE_IDS", &mut self.roles.player_role_ids),
+            ("TOODLES_JURY_ROLE_IDS", &mut self.roles.jury_role_ids),
+            ("TOODLES_HOST_ROLE_IDS", &mut self.roles.host_role_ids),
+            ("TOODLES_ADMIN_ROLE_IDS", &mut self.roles.admin_role_ids),
+        ] {
+            if let Some(value) = env(name) {
+                *role_ids = split_list(&value).filter_map(|role_id| number(&mut problems, name, role_id.to_string())).collect();
+            }
+        }
+        if let Some(intents) = env("TOODLES_INTENTS") {
+            self.discord.intents = split_list(&intents).map(str::to_string).collect();
+        }
+
+        // Kept from before the store backend could be chosen directly
+        if let Some(app_env) = env("APP_ENV") {
+            match app_env.as_str() {
+                "development" => self.store.backend = StoreBackend::Memory,
+                "production" => self.store.backend = StoreBackend::Postgres,
+                _ => problems.push(format!("APP_ENV must be development or production, got {:?}", app_env)),
+            }
+        }
+        if let Some(backend) = env("TOODLES_STORE") {
+            match StoreBackend::parse(&backend) {
+                Some(backend) => self.store.backend = backend,
+                None => problems.push(format!("TOODLES_STORE must be memory or postgres, got {:?}", backend)),
+            }
+        }
+        if let Some(database_url) = env("DATABASE_URL") {
+            self.store.database_url = Some(database_url);
+        }
+
+        if let Some(model) = env("TOODLES_CHAT_MODEL") {
+            self.models.chat = model;
+        }
+        if let Some(model) = env("TOODLES_CLASSIFIER_MODEL") {
+            self.models.classifier = model;
+        }
+        if let Some(mode) = env("TOODLES_LLM_MODE") {
+            match LlmMode::parse(&mode) {
+                Some(mode) => self.models.mode = mode,
+                None => problems.push(format!("TOODLES_LLM_MODE must be live, record or replay, got {:?}", mode)),
+            }
+        }
+        if let Some(fixtures_dir) = env("TOODLES_LLM_FIXTURES_DIR") {
+            self.models.fixtures_dir = PathBuf::from(fixtures_dir);
+        }
+
+        if let Some(max_tokens) = env("TOODLES_MAX_REPLY_TOKENS") {
+            match max_tokens.trim().parse() {
+                Ok(max_tokens) => self.limits.max_reply_tokens = max_tokens,
+                Err(_) => problems.push(format!("TOODLES_MAX_REPLY_TOKENS must be a number of tokens up to {}, got {:?}", u16::MAX, max_tokens)),
+            }
+        }
+        if let Some(debounce_ms) = env("TOODLES_DEBOUNCE_MS") {
+            self.limits.debounce_ms = number(&mut problems, "TOODLES_DEBOUNCE_MS", debounce_ms);
+        }
+        if let Some(secs) = env("TOODLES_FORGET_CONFIRMATION_SECS") {
+            self.limits.forget_confirmation_secs = number(&mut problems, "TOODLES_FORGET_CONFIRMATION_SECS", secs).unwrap_or(self.limits.forget_confirmation_secs);
+        }
+        if let Some(secs) = env("TOODLES_SHUTDOWN_GRACE_SECS") {
+            self.limits.shutdown_grace_secs = number(&mut problems, "TOODLES_SHUTDOWN_GRACE_SECS", secs).unwrap_or(self.limits.shutdown_grace_secs);
+        }
+
+        if let Some(tokens) = env("TOODLES_USER_DAILY_TOKENS") {
+            self.budgets.user_daily_tokens = number(&mut problems, "TOODLES_USER_DAILY_TOKENS", tokens);
+        }
+        if let Some(tokens) = env("TOODLES_USER_MONTHLY_TOKENS") {
+            self.budgets.user_monthly_tokens = number(&mut problems, "TOODLES_USER_MONTHLY_TOKENS", tokens);
+        }
+        if let Some(tokens) = env("TOODLES_GUILD_DAILY_TOKENS") {
+            self.budgets.guild_daily_tokens = number(&mut problems, "TOODLES_GUILD_DAILY_TOKENS", tokens);
+        }
+        if let Some(tokens) = env("TOODLES_GUILD_MONTHLY_TOKENS") {
+            self.budgets.guild_monthly_tokens = number(&mut problems, "TOODLES_GUILD_MONTHLY_TOKENS", tokens);
+        }
+        if let Some(model) = env("TOODLES_BUDGET_FALLBACK_MODEL") {
+            self.budgets.fallback_model = Some(model).filter(|model| !model.trim().is_empty());
+        }
+
+        for (name, limit) in [
+            ("TOODLES_RATE_LIMIT_USER", &mut self.rate_limits.user),
+            ("TOODLES_RATE_LIMIT_CHANNEL", &mut self.rate_limits.channel),
+            ("TOODLES_RATE_LIMIT_GUILD", &mut self.rate_limits.guild),
+        ] {
+            match env(name) {
+                Some(value) if value.trim().is_empty() => *limit = None,
+                Some(value) => match RateLimit::parse(&value) {
+                    Some(parsed) => *limit = Some(parsed),
+                    None => problems.push(format!("{} must look like <burst>/<per_minute>, e.g. 5/3, got {:?}", name, value)),
+                },
+                None => {},
+            }
+        }
+
+        if let Some(days) = env("TOODLES_RETENTION_MAX_AGE_DAYS") {
+            self.retention.max_age_days = number(&mut problems, "TOODLES_RETENTION_MAX_AGE_DAYS", days);
+        }
+        if let Some(max) = env("TOODLES_RETENTION_MAX_MESSAGES") {
+            self.retention.max_messages_per_user = number(&mut problems, "TOODLES_RETENTION_MAX_MESSAGES", max).map(|max| max as usize);
+        }
+        if let Some(minutes) = env("TOODLES_RETENTION_INTERVAL_MINUTES") {
+            self.retention.interval_minutes = number(&mut problems, "TOODLES_RETENTION_INTERVAL_MINUTES", minutes).unwrap_or(self.retention.interval_minutes);
+        }
+        if let Some(archive_dir) = env("TOODLES_RETENTION_ARCHIVE_DIR") {
+            self.retention.archive_dir = Some(PathBuf::from(archive_dir));
+        }
+
+        if let Some(format) = env("TOODLES_LOG_FORMAT") {
+            match LogFormat::parse(&format) {
+                Some(format) => self.logging.format = format,
+                None => problems.push(format!("TOODLES_LOG_FORMAT must be text or json, got {:?}", format)),
+            }
+        }
+        if let Some(filter) = env("TOODLES_LOG") {
+            self.logging.filter = filter;
+        }
+        if let Some(redact) = env("TOODLES_LOG_REDACT_CONTENT") {
+            match redact.trim().parse() {
+                Ok(redact) => self.logging.redact_content = redact,
+                Err(_) => problems.push(format!("TOODLES_LOG_REDACT_CONTENT must be true or false, got {:?}", redact)),
+            }
+        }
+
+        if let Some(server) = env("TOODLES_IRC_SERVER") {
+            self.irc.get_or_insert_with(IrcConfig::default).server = server;
+        }
+        if let Some(nickname) = env("TOODLES_IRC_NICK") {
+            self.irc.get_or_insert_with(IrcConfig::default).nickname = nickname;
+        }
+        if let Some(channels) = env("TOODLES_IRC_CHANNELS") {
+            self.irc.get_or_insert_with(IrcConfig::default).channels = split_list(&channels).map(str::to_string).collect();
+        }
+        if let Some(pairs) = env("TOODLES_IRC_NICK_USER_IDS") {
+            match split_pairs(&pairs, '=') {
+                Some(nick_user_ids) => self.irc.get_or_insert_with(IrcConfig::default).nick_user_ids = nick_user_ids,
+                None => problems.push("TOODLES_IRC_NICK_USER_IDS must be a comma separated list of nick=user_id pairs".to_string()),
+            }
+        }
+
+        if let Some(addr) = env("TOODLES_API_ADDR") {
+            self.api.get_or_insert_with(ApiConfig::default).addr = addr;
+        }
+        if let Some(token) = env("TOODLES_API_TOKEN") {
+            self.api.get_or_insert_with(ApiConfig::default).token = token;
+        }
+
+        if let Some(addr) = env("TOODLES_DASHBOARD_ADDR") {
+            self.dashboard.get_or_insert_with(DashboardConfig::default).addr = addr;
+        }
+        if let Some(pairs) = env("TOODLES_DASHBOARD_HOSTS") {
+            match split_pairs(&pairs, ':') {
+                Some(hosts) => self.dashboard.get_or_insert_with(DashboardConfig::default).hosts = hosts,
+                None => problems.push("TOODLES_DASHBOARD_HOSTS must be a comma separated list of host_id:password pairs".to_string()),
+            }
+        }
+
+        if let Some(addr) = env("TOODLES_METRICS_ADDR") {
+            self.metrics.get_or_insert_with(MetricsConfig::default).addr = addr;
+        }
+        if let Some(addr) = env("TOODLES_HEALTH_ADDR") {
+            self.health.get_or_insert_with(HealthConfig::default).addr = addr;
+        }
+
+        if problems.is_empty() { Ok(()) } else { Err(ConfigError::Invalid(problems)) }
+    }
+
+    /// Checks the settings that can't be wrong in isolation, reporting every problem found.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        if self.discord.token.trim().is_empty() {
+            problems.push("discord.token is required (or set DISCORD_TOODLE_BOT_TOKEN)".to_string());
+        }
+        if self.discord.prefix.trim().is_empty() {
+            problems.push("discord.prefix must not be empty".to_string());
+        }
+        for intent in &self.discord.intents {
+            if GatewayIntents::from_name(intent).is_none() {
+                problems.push(format!("discord.intents has an unknown gateway intent {:?}", intent));
+            }
+        }
+
+        if self.store.backend == StoreBackend::Postgres && self.store.database_url.as_deref().is_none_or(|url| url.trim().is_empty()) {
+            problems.push("store.database_url is required by the postgres backend (or set DATABASE_URL)".to_string());
+        }
+
+        if self.models.chat.trim().is_empty() {
+            problems.push("models.chat must not be empty".to_string());
+        }
+        if self.models.classifier.trim().is_empty() {
+            problems.push("models.classifier must not be empty".to_string());
+        }
+
+        if self.limits.max_reply_tokens == 0 {
+            problems.push("limits.max_reply_tokens must be at least 1".to_string());
+        }
+        if self.limits.forget_confirmation_secs == 0 {
+            problems.push("limits.forget_confirmation_secs must be at least 1".to_string());
+        }
+        if self.budgets.fallback_model.as_deref().is_some_and(|model| model.trim().is_empty()) {
+            problems.push("budgets.fallback_model must not be empty; leave it out to fall back to canned replies".to_string());
+        }
+
+        for (name, limit) in [("user", self.rate_limits.user), ("channel", self.rate_limits.channel), ("guild", self.rate_limits.guild)] {
+            if limit.is_some_and(|limit| !limit.is_valid()) {
+                problems.push(format!("rate_limits.{} needs a burst of at least 1 and a positive per_minute", name));
+            }
+        }
+
+        if let Some(days) = self.retention.max_age_days
+            && self.retention.policy().cutoff(chrono::Utc::now()).is_none()
+        {
+            problems.push(format!("retention.max_age_days is too far back to measure, got {}", days));
+        }
+        if self.retention.interval_minutes == 0 {
+            problems.push("retention.interval_minutes must be at least 1".to_string());
+        } else if self.retention.interval_minutes.checked_mul(60).and_then(|secs| std::time::Instant::now().checked_add(Duration::from_secs(secs))).is_none() {
+            problems.push(format!("retention.interval_minutes is too long to wait, got {}", self.retention.interval_minutes));
+        }
+
+        if let Err(why) = EnvFilter::try_new(&self.logging.filter) {
+            problems.push(format!("logging.filter is not a valid filter: {}", why));
+        }
+
+        if let Some(irc) = &self.irc {
+            if irc.server.trim().is_empty() {
+                problems.push("irc.server is required to use IRC (or set TOODLES_IRC_SERVER)".to_string());
+            }
+            if irc.nickname.trim().is_empty() {
+                problems.push("irc.nickname must not be empty".to_string());
+            }
+        }
+        if let Some(api) = &self.api {
+            if api.addr.trim().is_empty() {
+                problems.push("api.addr is required to serve the API (or set TOODLES_API_ADDR)".to_string());
+            }
+            if api.token.trim().is_empty() {
+                problems.push("api.token is required to serve the API (or set TOODLES_API_TOKEN)".to_string());
+            }
+        }
+        if let Some(dashboard) = &self.dashboard {
+            if dashboard.addr.trim().is_empty() {
+                problems.push("dashboard.addr is required to serve the dashboard (or set TOODLES_DASHBOARD_ADDR)".to_string());
+            }
+            if dashboard.hosts.is_empty() {
+                problems.push("dashboard.hosts needs at least one host (or set TOODLES_DASHBOARD_HOSTS)".to_string());
+            }
+            let mut without_password: Vec<&str> = dashboard.hosts.iter()
+                .filter(|(_, password)| password.trim().is_empty())
+                .map(|(host_id, _)| host_id.as_str())
+                .collect();
+            without_password.sort();
+            for host_id in without_password {
+                problems.push(format!("dashboard.hosts.{} needs a password", host_id));
+            }
+        }
+        if self.metrics.as_ref().is_some_and(|metrics| metrics.addr.trim().is_empty()) {
+            problems.push("metrics.addr is required to serve metrics (or set TOODLES_METRICS_ADDR)".to_string());
+        }
+        if self.health.as_ref().is_some_and(|health| health.addr.trim().is_empty()) {
+            problems.push("health.addr is required to serve health checks (or set TOODLES_HEALTH_ADDR)".to_string());
+        }
+
+        if problems.is_empty() { Ok(()) } else { Err(ConfigError::Invalid(problems)) }
+    }
+
+    pub fn reply_settings(&self) -> ReplySettings {
+        ReplySettings {
+            model: self.models.chat.clone(),
+            max_tokens: self.limits.max_reply_tokens,
+            budget: self.budgets.budget(),
+        }
+    }
+}
+
+fn number(problems: &mut Vec<String>, name: &str, value: String) -> Option<u64> {
+    value.trim().parse().map_err(|_| problems.push(format!("{} must be a whole number, got {:?}", name, value))).ok()
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').map(str::trim).filter(|item| !item.is_empty())
+}
+
+/// Parses `a=b,c=d` style lists, or `None` if any item lacks the separator.
+fn split_pairs(list: &str, separator: char) -> Option<HashMap<String, String>> {
+    split_list(list)
+        .map(|pair| pair.split_once(separator).map(|(key, value)| (key.trim().to_string(), value.trim().to_string())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| vars.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string())
+    }
+
+    #[test]
+    fn test_defaults_match_previous_behavior() {
+        let mut config: Config = toml::from_str("").unwrap();
+        config.apply_env(env(&[("DISCORD_TOODLE_BOT_TOKEN", "token")])).unwrap();
+        config.validate().unwrap();
+
+        assert_eq!(config.discord.prefix, "!toodles");
+        assert_eq!(config.discord.welcome_channel_id, Some(733545069549977621));
+        assert_eq!(
+            config.discord.gateway_intents(),
+            GatewayIntents::GUILD_MESSAGES
+                | GatewayIntents::DIRECT_MESSAGES
+                | GatewayIntents::MESSAGE_CONTENT
+                | GatewayIntents::GUILD_MEMBERS
+                | GatewayIntents::GUILD_MESSAGE_REACTIONS,
+        );
+        assert_eq!(config.store.backend, StoreBackend::Memory);
+        assert_eq!(config.reply_settings(), ReplySettings::default());
+        assert!(config.retention.policy().is_unbounded());
+        assert!(config.logging.redact_content, "Expected player content to stay out of logs by default");
+        assert!(config.irc.is_none() && config.api.is_none() && config.dashboard.is_none() && config.metrics.is_none() && config.health.is_none());
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let mut config: Config = toml::from_str(
+            r##"
+            [discord]
+            token = "from-file"
+            prefix = "!clown"
+
+            [roles]
+            host_role_ids = [1, 2]
+
+            [models]
+            chat = "gpt-4o-mini"
+            mode = "replay"
+
+            [rate_limits]
+            user = { burst = 5, per_minute = 3 }
+
+            [irc]
+            server = "irc.example:6667"
+            channels = ["#circus"]
+            "##,
+        )
+        .unwrap();
+        config
+            .apply_env(env(&[
+                ("DISCORD_TOODLE_BOT_TOKEN", "from-env"),
+                ("APP_ENV", "production"),
+                ("DATABASE_URL", "postgres://localhost/toodles"),
+                ("TOODLES_MAX_REPLY_TOKENS", "120"),
+                ("TOODLES_IRC_NICK_USER_IDS", "Alice=123"),
+                ("TOODLES_WELCOME_CHANNEL_ID", ""),
+                ("TOODLES_JURY_ROLE_IDS", "3"),
+                ("TOODLES_RATE_LIMIT_GUILD", "60/40"),
+            ]))
+            .unwrap();
+        config.validate().unwrap();
+
+        assert_eq!(config.discord.token, "from-env");
+        assert_eq!(config.discord.prefix, "!clown");
+        assert_eq!(config.roles.host_role_ids, vec![1, 2]);
+        assert_eq!(config.roles.jury_role_ids, vec![3]);
+        assert_eq!(config.rate_limits.user, Some(RateLimit { burst: 5, per_minute: 3.0 }));
+        assert_eq!(config.rate_limits.guild, Some(RateLimit { burst: 60, per_minute: 40.0 }));
+        assert_eq!(config.discord.welcome_channel_id, None);
+        assert_eq!(config.store.backend, StoreBackend::Postgres);
+        assert_eq!(config.models.mode, LlmMode::Replay);
+        assert_eq!(config.reply_settings(), ReplySettings { model: "gpt-4o-mini".to_string(), max_tokens: 120, budget: Budget::default() });
+        let irc = config.irc.unwrap();
+        assert_eq!(irc.nickname, "toodles");
+        assert_eq!(irc.nick_user_ids.get("Alice").map(String::as_str), Some("123"));
+    }
+
+    #[test]
+    fn test_every_problem_is_reported() {
+        assert!(toml::from_str::<Config>("[discord]\nprefx = \"!typo\"").is_err(), "Expected unknown keys to be rejected");
+
+        let mut config = Config::default();
+        let Err(ConfigError::Invalid(problems)) = config.apply_env(env(&[("APP_ENV", "staging"), ("TOODLES_DEBOUNCE_MS", "soon")])) else {
+            panic!("Expected bad environment variables to be rejected");
+        };
+        assert_eq!(problems.len(), 2);
+
+        let config: Config = toml::from_str(
+            r#"
+            store = { backend = "postgres" }
+            discord = { intents = ["GUILD_MESSAGES", "MIND_READING"] }
+            api = { addr = "127.0.0.1:8080", token = "  " }
+            dashboard = { addr = "127.0.0.1:8081", hosts = { alice = "" } }
+            retention = { max_age_days = 1000000000, interval_minutes = 9223372036854775807 }
+            "#,
+        )
+        .unwrap();
+        let Err(ConfigError::Invalid(problems)) = config.validate() else {
+            panic!("Expected the config to be invalid");
+        };
+        let message = ConfigError::Invalid(problems.clone()).to_string();
+        for expected in ["discord.token", "MIND_READING", "store.database_url", "api.token", "dashboard.hosts.alice", "retention.max_age_days", "retention.interval_minutes"] {
+            assert!(message.contains(expected), "Expected a problem about {} in:\n{}", expected, message);
+        }
+        assert_eq!(problems.len(), 7);
+        assert!(config.retention.policy().cutoff(chrono::Utc::now()).is_none(), "Expected an unmeasurable age not to panic");
+    }
+}
@@ -0,0 +1,348 @@
+use std::time::Instant;
+
+use chrono::Utc;
+
+use tracing::{debug, field, info, info_span, warn, Instrument};
+
+use crate::ai::{ask_toodles_with, construct_system_prompt, Classifier, Completion, KeywordClassifier, LlmBackend, ReplySettings};
+use crate::farming::{check_compliment, COMPLIMENT_WINDOW};
+use crate::models::{Budget, ChatMessage, ChatRole, FarmingFlag, Sentiment, SentimentEvent, Tier, UsageKind, UsageRecord, UsageScope, UserInteraction};
+use crate::logging::content;
+use crate::metrics::{time_store, METRICS};
+use crate::store::{tokens_spent, Stores};
+
+/// What Toodles says once a budget is spent and there is no cheaper model to fall back to
+const BUDGET_SPENT_REPLIES: [&str; 3] = [
+    "🤡 Toodles has laughed himself hoarse. He just wiggles his eyebrows at you.",
+    "🤡 *honks horn* ...*honks horn again*. That's all you're getting today.",
+    "🤡 The ringmaster cut off Toodles's tab. Come back when the circus has paid its bills.",
+];
+
+/// A message addressed to Toodles, independent of where it was sent.
+#[derive(Debug, Clone, Default)]
+pub struct TurnInput {
+    pub user_id: String,
+    pub username: String,
+    pub content: String,
+    /// Platform identifiers, kept so hosts can find the message again
+    pub message_id: Option<String>,
+    pub channel_id: Option<String>,
+    pub guild_id: Option<String>,
+}
+
+/// Everything Toodles decided while answering a turn.
+#[derive(Debug, Clone)]
+pub struct TurnOutcome {
+    pub sentiment: Sentiment,
+    /// Set when the message looked like farming and was left out of the counters
+    pub farming: Option<FarmingFlag>,
+    /// The player's counters after this turn was counted
+    pub interaction: UserInteraction,
+    pub tier: Tier,
+    pub system_prompt: String,
+    pub reply: Completion,
+}
+
+/// Classifies the message, updates the player's standing and asks Toodles for a reply.
+/// The turn is not added to the chat history until [`record_turn`] is called with the sent reply.
+pub async fn take_turn(
+    stores: &Stores,
+    llm: &(dyn LlmBackend + Send + Sync),
+    classifier: &(dyn Classifier + Send + Sync),
+    reply_settings: &ReplySettings,
+    input: &TurnInput,
+) -> Result<TurnOutcome, Box<dyn std::error::Error + Send + Sync>> {
+    let span = info_span!(
+        "turn",
+        user_id = %input.user_id,
+        guild_id = input.guild_id.as_deref(),
+        sentiment = field::Empty,
+        farming = field::Empty,
+        tier = field::Empty,
+        prompt_tokens = field::Empty,
+        completion_tokens = field::Empty,
+        latency_ms = field::Empty,
+        outcome = field::Empty,
+    );
+    let started = Instant::now();
+    let result = run_turn(stores, llm, classifier, reply_settings, input).instrument(span.clone()).await;
+
+    span.record("latency_ms", started.elapsed().as_millis() as u64);
+    match &result {
+        Ok(outcome) => {
+            span.record("sentiment", outcome.sentiment.as_str());
+            if let Some(flag) = outcome.farming {
+                span.record("farming", flag.as_str());
+            }
+            span.record("tier", outcome.tier.as_str());
+            span.record("prompt_tokens", outcome.reply.prompt_tokens);
+            span.record("completion_tokens", outcome.reply.completion_tokens);
+            span.record("outcome", "replied");
+            METRICS.record_turn("replied");
+            span.in_scope(|| info!("Turn finished"));
+        },
+        Err(why) => {
+            span.record("outcome", "error");
+            METRICS.record_turn("error");
+            span.in_scope(|| warn!(error = %why, "Turn failed"));
+        },
+    }
+    result
+}
+
+async fn run_turn(
+    stores: &Stores,
+    llm: &(dyn LlmBackend + Send + Sync),
+    classifier: &(dyn Classifier + Send + Sync),
+    reply_settings: &ReplySettings,
+    input: &TurnInput,
+) -> Result<TurnOutcome, Box<dyn std::error::Error + Send + Sync>> {
+    debug!(username = %input.username, content = %content(&input.content), "Received message");
+    // Without a fallback model a spent budget means no model calls at all
+    let spent_budget = spent_budget(stores, &reply_settings.budget, input).await;
+    let canned = spent_budget.is_some() && reply_settings.budget.fallback_model.is_none();
+    let classification = if canned {
+        KeywordClassifier::new().classify(&input.content).await?
+    } else {
+        classifier.classify(&input.content).await?
+    };
+    if let Some(usage) = classification.usage.clone() {
+        record_usage(stores, UsageRecord::new(&input.user_id, input.guild_id.clone(), UsageKind::Classification, usage)).await;
+    }
+    let sentiment = classification.sentiment.clone();
+    METRICS.sentiments.with_label_values(&[sentiment.as_str()]).inc();
+    let mut chat_history = time_store("get_chat_history", stores.chat_history.get_chat_history(&input.user_id)).await;
+    // Only compliments are worth farming, since they are what earns the idol
+    let farming = if sentiment == Sentiment::Positive {
+        let now = Utc::now();
+        let recent_events = time_store("get_sentiment_timeline", stores.sentiment_log.get_sentiment_timeline(&input.user_id, Some(now - COMPLIMENT_WINDOW))).await;
+        check_compliment(&input.content, &chat_history.messages, &recent_events, now)
+    } else {
+        None
+    };
+    let event = SentimentEvent {
+        farming,
+        ..SentimentEvent::new(&input.user_id, input.message_id.clone(), classification.sentiment, classification.confidence)
+    };
+    time_store("record_sentiment", stores.sentiment_log.record_sentiment(event)).await;
+    let mut interaction = time_store("get_user_interaction", stores.user_interaction.get_user_interaction(&input.user_id)).await;
+
+    if let Some(flag) = farming {
+        info!(farming = %flag, "Compliment not counted as suspected farming");
+        METRICS.farming_flags.with_label_values(&[flag.as_str()]).inc();
+    } else {
+        match sentiment {
+            Sentiment::Positive => {
+                interaction.increment_positive();
+                time_store("increment_interaction", stores.user_interaction.increment_positive_interaction(&input.user_id)).await;
+            },
+            Sentiment::Negative => {
+                interaction.increment_negative();
+                time_store("increment_interaction", stores.user_interaction.increment_negative_interaction(&input.user_id)).await;
+            },
+            Sentiment::Neutral => {
+                interaction.increment_neutral();
+                time_store("increment_interaction", stores.user_interaction.increment_neutral_interaction(&input.user_id)).await;
+            }
+        }
+    }
+
+    let mut system_prompt = construct_system_prompt(&input.username, interaction.num_positive, interaction.num_negative, interaction.num_neutral, interaction.idol_given);
+    let mut reply_settings = reply_settings.clone();
+    // Hosts can tune Toodles for their guild
+    if let Some(guild_id) = &input.guild_id {
+        let guild_settings = time_store("get_guild_settings", stores.guild_settings.get_guild_settings(guild_id)).await;
+        if let Some(persona) = guild_settings.persona {
+            system_prompt.push('\n');
+            system_prompt.push_str(&persona);
+        }
+        if let Some(max_tokens) = guild_settings.max_reply_tokens {
+            reply_settings.max_tokens = max_tokens;
+        }
+    }
+    debug!(system_prompt = %content(&system_prompt), "Constructed system prompt");
+    chat_history.set_system_message(system_prompt.clone());
+    chat_history.add_user_message(input.content.clone());
+
+    if let Some(budget) = &spent_budget {
+        info!(budget = %budget, fallback_model = reply_settings.budget.fallback_model.as_deref(), "Budget spent");
+        if let Some(fallback_model) = &reply_settings.budget.fallback_model {
+            reply_settings.model = fallback_model.clone();
+        }
+    }
+    let reply = if canned {
+        Completion {
+            content: BUDGET_SPENT_REPLIES[input.content.len() % BUDGET_SPENT_REPLIES.len()].to_string(),
+            model: "canned".to_string(),
+            prompt_tokens: None,
+            completion_tokens: None,
+        }
+    } else {
+        ask_toodles_with(llm, &chat_history, &reply_settings).await?
+    };
+    if let Some(usage) = reply.usage() {
+        record_usage(stores, UsageRecord::new(&input.user_id, input.guild_id.clone(), UsageKind::Reply, usage)).await;
+    }
+
+    Ok(TurnOutcome {
+        sentiment,
+        farming,
+        tier: interaction.tier(),
+        interaction,
+        system_prompt,
+        reply,
+    })
+}
+
+/// The first of the player's or guild's budgets that is already spent, e.g. "guild daily".
+async fn spent_budget(stores: &Stores, budget: &Budget, input: &TurnInput) -> Option<String> {
+    if budget.is_unbounded() {
+        return None;
+    }
+    let now = Utc::now();
+    let mut scopes = vec![UsageScope::User(input.user_id.clone())];
+    scopes.extend(input.guild_id.clone().map(UsageScope::Guild));
+    for scope in scopes {
+        for (period, limit) in budget.limits(&scope) {
+            let Some(limit) = limit else {
+                continue;
+            };
+            let spent = time_store("summarize_usage", tokens_spent(stores.usage.as_ref(), &scope, period.start(now))).await;
+            if spent >= limit {
+                let owner = match scope {
+                    UsageScope::User(_) => "user",
+                    UsageScope::Guild(_) => "guild",
+                };
+                return Some(format!("{} {}", owner, period.as_str()));
+            }
+        }
+    }
+    None
+}
+
+async fn record_usage(stores: &Stores, record: UsageRecord) {
+    debug!(kind = %record.kind, model = %record.model, prompt_tokens = record.prompt_tokens, completion_tokens = record.completion_tokens, "Recorded usage");
+    time_store("record_usage", stores.usage.record_usage(record)).await;
+}
+
+/// Adds the player's message and Toodles's reply to the chat history.
+pub async fn record_turn(stores: &Stores, input: &TurnInput, outcome: &TurnOutcome, reply_message_id: Option<String>) {
+    let message = ChatMessage {
+        message_id: input.message_id.clone(),
+        channel_id: input.channel_id.clone(),
+        guild_id: input.guild_id.clone(),
+        sentiment: Some(outcome.sentiment.clone()),
+        ..ChatMessage::new(ChatRole::User, input.content.clone())
+    };
+    time_store("add_chat_message", stores.chat_history.add_chat_message(&input.user_id, message)).await;
+    let reply = ChatMessage {
+        message_id: reply_message_id,
+        channel_id: input.channel_id.clone(),
+        guild_id: input.guild_id.clone(),
+        model: Some(outcome.reply.model.clone()),
+        prompt_tokens: outcome.reply.prompt_tokens,
+        completion_tokens: outcome.reply.completion_tokens,
+        ..ChatMessage::new(ChatRole::Assistant, outcome.reply.content.clone())
+    };
+    time_store("add_chat_message", stores.chat_history.add_chat_message(&input.user_id, reply)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{CannedBackend, KeywordClassifier};
+    use crate::models::{SettingChange, TokenUsage};
+
+    fn input(content: &str) -> TurnInput {
+        TurnInput {
+            user_id: "test_user".to_string(),
+            username: "Tester".to_string(),
+            content: content.to_string(),
+            message_id: Some("1".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_take_and_record_turn() {
+        let stores = Stores::in_memory();
+        let llm = CannedBackend::new("Honk honk!");
+        let classifier = KeywordClassifier::new();
+
+        let input = input("I love you Toodles!");
+        let outcome = take_turn(&stores, &llm, &classifier, &ReplySettings::default(), &input).await.unwrap();
+        assert_eq!(outcome.sentiment, Sentiment::Positive);
+        assert_eq!(outcome.interaction.num_positive, 1);
+        assert!(outcome.system_prompt.contains("Tester"), "Expected the prompt to address the player by name");
+        assert_eq!(outcome.reply.content, "Honk honk!");
+
+        assert_eq!(stores.user_interaction.get_user_interaction("test_user").await.num_positive, 1);
+        assert_eq!(stores.sentiment_log.find_sentiment_event("1").await.map(|event| event.sentiment), Some(Sentiment::Positive));
+        assert!(stores.chat_history.get_chat_history("test_user").await.messages.is_empty(), "Expected nothing in the history until the turn is recorded");
+
+        record_turn(&stores, &input, &outcome, Some("2".to_string())).await;
+        let history = stores.chat_history.get_chat_history("test_user").await.messages;
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].sentiment, Some(Sentiment::Positive));
+        assert_eq!(history[1].role, ChatRole::Assistant);
+        assert_eq!(history[1].message_id.as_deref(), Some("2"));
+    }
+
+    #[tokio::test]
+    async fn test_turn_uses_guild_persona() {
+        let stores = Stores::in_memory();
+        let mut settings = stores.guild_settings.get_guild_settings("9").await;
+        settings.apply(SettingChange::Persona(Some("Speak only in limericks.".to_string())));
+        stores.guild_settings.set_guild_settings(settings).await;
+
+        let in_guild = TurnInput { guild_id: Some("9".to_string()), ..input("Hello") };
+        let outcome = take_turn(&stores, &CannedBackend::new("Honk."), &KeywordClassifier::new(), &ReplySettings::default(), &in_guild).await.unwrap();
+        assert!(outcome.system_prompt.ends_with("Speak only in limericks."));
+
+        let elsewhere = TurnInput { guild_id: Some("10".to_string()), ..input("Hello") };
+        let outcome = take_turn(&stores, &CannedBackend::new("Honk."), &KeywordClassifier::new(), &ReplySettings::default(), &elsewhere).await.unwrap();
+        assert!(!outcome.system_prompt.contains("limericks"));
+    }
+
+    #[tokio::test]
+    async fn test_spent_budget_falls_back() {
+        let stores = Stores::in_memory();
+        let usage = TokenUsage { model: "gpt-3.5-turbo".to_string(), prompt_tokens: 90, completion_tokens: 20 };
+        stores.usage.record_usage(UsageRecord::new("test_user", None, UsageKind::Reply, usage)).await;
+        let mut settings = ReplySettings::default();
+        settings.budget.user_daily_tokens = Some(100);
+
+        let outcome = take_turn(&stores, &CannedBackend::new("Honk."), &KeywordClassifier::new(), &settings, &input("Hello")).await.unwrap();
+        assert_eq!(outcome.reply.model, "canned", "Expected no model call without a fallback model");
+        assert!(BUDGET_SPENT_REPLIES.contains(&outcome.reply.content.as_str()));
+
+        settings.budget.fallback_model = Some("cheap-model".to_string());
+        let outcome = take_turn(&stores, &CannedBackend::new("Honk."), &KeywordClassifier::new(), &settings, &input("Hello")).await.unwrap();
+        assert_eq!(outcome.reply.model, "cheap-model");
+        assert_eq!(outcome.reply.content, "Honk.");
+
+        settings.budget.user_daily_tokens = Some(1000);
+        let outcome = take_turn(&stores, &CannedBackend::new("Honk."), &KeywordClassifier::new(), &settings, &input("Hello")).await.unwrap();
+        assert_eq!(outcome.reply.model, "gpt-3.5-turbo", "Expected the usual model while under budget");
+    }
+
+    #[tokio::test]
+    async fn test_repeated_compliments_are_not_counted() {
+        let stores = Stores::in_memory();
+        let (llm, classifier) = (CannedBackend::new("Honk!"), KeywordClassifier::new());
+
+        let first = TurnInput { message_id: Some("1".to_string()), ..input("You're so funny Toodles, I love you") };
+        let outcome = take_turn(&stores, &llm, &classifier, &ReplySettings::default(), &first).await.unwrap();
+        assert_eq!(outcome.farming, None);
+        record_turn(&stores, &first, &outcome, None).await;
+
+        let again = TurnInput { message_id: Some("2".to_string()), ..input("you're so funny toodles!! I love you") };
+        let outcome = take_turn(&stores, &llm, &classifier, &ReplySettings::default(), &again).await.unwrap();
+        assert_eq!(outcome.sentiment, Sentiment::Positive);
+        assert_eq!(outcome.farming, Some(FarmingFlag::Duplicate));
+        assert_eq!(outcome.interaction.num_positive, 1, "Expected the repeat to leave the counters alone");
+        assert_eq!(stores.user_interaction.get_user_interaction("test_user").await.num_positive, 1);
+        assert_eq!(stores.sentiment_log.find_sentiment_event("2").await.and_then(|event| event.farming), Some(FarmingFlag::Duplicate));
+        assert_eq!(crate::player_data::summarize_player(&stores, "test_user", 5).await.suspected_farming, 1, "Expected hosts to see the flag");
+    }
+}
@@ -0,0 +1,396 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::Arc;
+
+use axum::extract::{Form, Path, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::routing::{get, post};
+use axum::{Extension, Router};
+use base64::Engine;
+use chrono::{Duration, NaiveDate, Utc};
+use serde::Deserialize;
+use tokio::net::{TcpListener, ToSocketAddrs};
+
+use crate::ai::idol_eligible;
+use crate::auth::secret_matches;
+use crate::models::{ChatRole, Sentiment, SentimentEvent, UserInteraction};
+use crate::player_data::set_idol_given;
+use crate::store::Stores;
+
+/// How far back a player's sentiment trend goes.
+const TREND_DAYS: i64 = 30;
+
+/// What the host dashboard reads from.
+#[derive(Clone)]
+pub struct DashboardState {
+    pub stores: Stores,
+    /// Host user IDs mapped to their dashboard passwords, checked with HTTP basic auth
+    pub hosts: Arc<HashMap<String, String>>,
+}
+
+/// The host a request was authenticated as, used as the actor for anything they change.
+#[derive(Debug, Clone)]
+struct HostId(String);
+
+pub fn router(state: DashboardState) -> Router {
+    Router::new()
+        .route("/", get(list_players))
+        .route("/players/{user_id}", get(show_player))
+        .route("/players/{user_id}/idol", post(update_idol))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_host))
+        .with_state(state)
+}
+
+/// Serves the dashboard on `addr` until the task is dropped.
+pub async fn serve(addr: impl ToSocketAddrs, state: DashboardState) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    axum::serve(listener, router(state)).await
+}
+
+async fn require_host(State(state): State<DashboardState>, mut request: Request, next: Next) -> Response {
+    let credentials = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| base64::engine::general_purpose::STANDARD.decode(encoded).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok());
+    let host_id = credentials.and_then(|credentials| {
+        let (host_id, password) = credentials.split_once(':')?;
+        let expected = state.hosts.get(host_id)?;
+        secret_matches(password, expected).then(|| host_id.to_string())
+    });
+
+    match host_id {
+        Some(host_id) => {
+            request.extensions_mut().insert(HostId(host_id));
+            next.run(request).await
+        },
+        None => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Basic realm=\"Toodles hosts\"")],
+            "Hosts only. 🤡",
+        ).into_response(),
+    }
+}
+
+/// Browsers resend basic auth credentials on their own, so refuse form posts started by other sites.
+fn is_same_origin(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN).and_then(|origin| origin.to_str().ok()) else {
+        return true;
+    };
+    let host = headers.get(header::HOST).and_then(|host| host.to_str().ok());
+    host.is_some_and(|host| origin.split_once("://").is_some_and(|(_, origin_host)| origin_host == host))
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Percent-encodes a user ID for use as one path segment.
+fn encode_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b':' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn page(title: &str, body: &str) -> Html<String> {
+    Html(format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title} · Toodles</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; max-width: 60em; }}
+table {{ border-collapse: collapse; }}
+th, td {{ padding: 0.3em 0.8em; border-bottom: 1px solid #ddd; text-align: left; }}
+.favored {{ color: #1a7f37; }} .despised {{ color: #cf222e; }}
+.user {{ background: #f6f8fa; }} .assistant {{ background: #fff8e6; }}
+.chat td {{ vertical-align: top; }}
+</style>
+</head>
+<body>
+<p><a href="/">🤡 Players</a></p>
+<h1>{title}</h1>
+{body}
+</body>
+</html>"#,
+        title = escape(title),
+    ))
+}
+
+fn idol_status(interaction: &UserInteraction) -> &'static str {
+    if interaction.idol_given {
+        "given"
+    } else if idol_eligible(interaction.num_positive, interaction.num_negative) {
+        "eligible"
+    } else {
+        ""
+    }
+}
+
+async fn list_players(State(state): State<DashboardState>) -> Html<String> {
+    let players = state.stores.user_interaction.list_user_interactions().await;
+
+    let mut body = String::new();
+    if players.is_empty() {
+        body.push_str("<p>Nobody has talked to Toodles yet.</p>");
+    } else {
+        body.push_str("<table>\n<tr><th>Player</th><th>Positive</th><th>Negative</th><th>Neutral</th><th>Tier</th><th>Idol</th></tr>\n");
+        for (user_id, interaction) in &players {
+            let tier = interaction.tier();
+            let _ = writeln!(
+                body,
+                r#"<tr><td><a href="/players/{}">{}</a></td><td>{}</td><td>{}</td><td>{}</td><td class="{tier}">{tier}</td><td>{}</td></tr>"#,
+                encode_segment(user_id),
+                escape(user_id),
+                interaction.num_positive,
+                interaction.num_negative,
+                interaction.num_neutral,
+                idol_status(interaction),
+            );
+        }
+        body.push_str("</table>");
+    }
+
+    page("Players", &body)
+}
+
+/// Sentiment counts per day, oldest first.
+fn daily_trend(events: &[SentimentEvent]) -> BTreeMap<NaiveDate, [usize; 3]> {
+    let mut days: BTreeMap<NaiveDate, [usize; 3]> = BTreeMap::new();
+    for event in events {
+        let counts = days.entry(event.created_at.date_naive()).or_default();
+        match event.sentiment {
+            Sentiment::Positive => counts[0] += 1,
+            Sentiment::Negative => counts[1] += 1,
+            Sentiment::Neutral => counts[2] += 1,
+        }
+    }
+    days
+}
+
+/// A bar per day of net positive minus negative messages, green above the line and red below.
+fn trend_chart(days: &BTreeMap<NaiveDate, [usize; 3]>) -> String {
+    const BAR_WIDTH: i64 = 12;
+    const HALF_HEIGHT: i64 = 50;
+
+    let max = days.values().map(|counts| (counts[0] as i64 - counts[1] as i64).abs()).max().unwrap_or(0).max(1);
+    let mut chart = format!(
+        r##"<svg width="{}" height="{}" role="img"><line x1="0" y1="{HALF_HEIGHT}" x2="100%" y2="{HALF_HEIGHT}" stroke="#999"/>"##,
+        BAR_WIDTH * days.len() as i64 + 2,
+        HALF_HEIGHT * 2,
+    );
+    for (i, (day, counts)) in days.iter().enumerate() {
+        let net = counts[0] as i64 - counts[1] as i64;
+        let height = (net.abs() * HALF_HEIGHT / max).max(1);
+        let y = if net >= 0 { HALF_HEIGHT - height } else { HALF_HEIGHT };
+        let color = if net >= 0 { "#1a7f37" } else { "#cf222e" };
+        let _ = write!(
+            chart,
+            r#"<rect x="{}" y="{y}" width="{}" height="{height}" fill="{color}"><title>{day}: {net:+}</title></rect>"#,
+            i as i64 * BAR_WIDTH + 1,
+            BAR_WIDTH - 2,
+        );
+    }
+    chart.push_str("</svg>");
+    chart
+}
+
+async fn show_player(State(state): State<DashboardState>, Path(user_id): Path<String>) -> Response {
+    let Some(interaction) = state.stores.user_interaction.export_user_interaction(&user_id).await else {
+        return (StatusCode::NOT_FOUND, page("Unknown player", "<p>Toodles has never met this player.</p>")).into_response();
+    };
+    let history = state.stores.chat_history.get_chat_history(&user_id).await.messages;
+    let events = state.stores.sentiment_log.get_sentiment_timeline(&user_id, Some(Utc::now() - Duration::days(TREND_DAYS))).await;
+
+    let mut body = String::new();
+    let tier = interaction.tier();
+    let _ = write!(
+        body,
+        r#"<p>Positive {} · Negative {} · Neutral {} · Tier <span class="{tier}">{tier}</span></p>"#,
+        interaction.num_positive, interaction.num_negative, interaction.num_neutral,
+    );
+    let suspected_farming = events.iter().filter(|event| event.farming.is_some()).count();
+    if suspected_farming > 0 {
+        let _ = write!(body, "<p>⚠️ Suspected farming: {} compliments in the last {} days were not counted</p>", suspected_farming, TREND_DAYS);
+    }
+
+    let status = idol_status(&interaction);
+    let _ = write!(
+        body,
+        r#"<form method="post" action="/players/{}/idol"><p>Idol: {} <input type="hidden" name="given" value="{}"><button>{}</button></p></form>"#,
+        encode_segment(&user_id),
+        if status.is_empty() { "not eligible" } else { status },
+        !interaction.idol_given,
+        if interaction.idol_given { "Mark as not given" } else { "Mark as given" },
+    );
+
+    let _ = write!(body, "<h2>Last {} days</h2>", TREND_DAYS);
+    let days = daily_trend(&events);
+    if days.is_empty() {
+        body.push_str("<p>No classified messages in this period.</p>");
+    } else {
+        body.push_str(&trend_chart(&days));
+        body.push_str("\n<table>\n<tr><th>Day</th><th>Positive</th><th>Negative</th><th>Neutral</th></tr>\n");
+        for (day, counts) in days.iter().rev() {
+            let _ = writeln!(body, "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>", day, counts[0], counts[1], counts[2]);
+        }
+        body.push_str("</table>");
+    }
+
+    body.push_str("<h2>Conversation</h2>");
+    if history.is_empty() {
+        body.push_str("<p>No messages kept.</p>");
+    } else {
+        body.push_str("<table class=\"chat\">\n");
+        for message in &history {
+            let (class, speaker) = match message.role {
+                ChatRole::User => ("user", escape(&user_id)),
+                _ => ("assistant", "Toodles".to_string()),
+            };
+            let _ = writeln!(
+                body,
+                r#"<tr class="{class}"><td>{}</td><td>{speaker}</td><td>{}</td><td>{}</td></tr>"#,
+                message.created_at.map(|at| at.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_default(),
+                escape(&message.content),
+                message.sentiment.as_ref().map_or("", Sentiment::as_str),
+            );
+        }
+        body.push_str("</table>");
+    }
+
+    page(&user_id, &body).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct IdolForm {
+    given: bool,
+}
+
+async fn update_idol(
+    State(state): State<DashboardState>,
+    Extension(HostId(host_id)): Extension<HostId>,
+    Path(user_id): Path<String>,
+    headers: HeaderMap,
+    Form(form): Form<IdolForm>,
+) -> Response {
+    if !is_same_origin(&headers) {
+        return (StatusCode::FORBIDDEN, "Cross-site request refused").into_response();
+    }
+    set_idol_given(&state.stores, &host_id, &user_id, form.given).await;
+    Redirect::to(&format!("/players/{}", encode_segment(&user_id))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    use crate::models::AuditAction;
+
+    fn state() -> DashboardState {
+        DashboardState {
+            stores: Stores::in_memory(),
+            hosts: Arc::new(HashMap::from([("host_1".to_string(), "hunter2".to_string())])),
+        }
+    }
+
+    fn authorized(builder: axum::http::request::Builder) -> axum::http::request::Builder {
+        let credentials = base64::engine::general_purpose::STANDARD.encode("host_1:hunter2");
+        builder.header(header::AUTHORIZATION, format!("Basic {}", credentials))
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_dashboard_requires_a_host() {
+        let app = router(state());
+
+        let anonymous = app.clone().oneshot(Request::get("/").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+        assert!(anonymous.headers().contains_key(header::WWW_AUTHENTICATE), "Expected browsers to be prompted for credentials");
+
+        let credentials = base64::engine::general_purpose::STANDARD.encode("host_1:wrong");
+        let wrong = Request::get("/").header(header::AUTHORIZATION, format!("Basic {}", credentials)).body(Body::empty()).unwrap();
+        assert_eq!(app.oneshot(wrong).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_dashboard_pages() {
+        let state = state();
+        let stores = &state.stores;
+        for _ in 0..12 {
+            stores.user_interaction.increment_positive_interaction("fan").await;
+            stores.sentiment_log.record_sentiment(SentimentEvent::new("fan", None, Sentiment::Positive, None)).await;
+        }
+        stores.user_interaction.increment_negative_interaction("<script>").await;
+        stores.chat_history.add_user_message("fan", "You're the <best> clown".to_string()).await;
+        stores.chat_history.add_assistant_message("fan", "Honk.".to_string()).await;
+        let app = router(state.clone());
+
+        let index = body_text(app.clone().oneshot(authorized(Request::get("/")).body(Body::empty()).unwrap()).await.unwrap()).await;
+        assert!(index.contains(r#"<a href="/players/fan">fan</a>"#));
+        assert!(index.contains("eligible"), "Expected the fan to be shown as eligible for the idol");
+        assert!(index.contains("&lt;script&gt;") && !index.contains("<script>"), "Expected user IDs to be escaped");
+
+        let player = body_text(app.clone().oneshot(authorized(Request::get("/players/fan")).body(Body::empty()).unwrap()).await.unwrap()).await;
+        assert!(player.contains("You&#39;re the &lt;best&gt; clown"));
+        assert!(player.contains("<svg"), "Expected a sentiment trend chart");
+        assert!(player.contains(&format!("<td>{}</td><td>12</td>", Utc::now().date_naive())));
+
+        let missing = app.clone().oneshot(authorized(Request::get("/players/nobody")).body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_marking_the_idol_is_audited() {
+        let state = state();
+        state.stores.user_interaction.increment_positive_interaction("fan").await;
+        let app = router(state.clone());
+
+        let form = |origin: &str| {
+            authorized(Request::post("/players/fan/idol"))
+                .header(header::HOST, "dashboard.example")
+                .header(header::ORIGIN, origin)
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from("given=true"))
+                .unwrap()
+        };
+
+        let cross_site = app.clone().oneshot(form("https://evil.example")).await.unwrap();
+        assert_eq!(cross_site.status(), StatusCode::FORBIDDEN);
+        assert!(!state.stores.user_interaction.get_user_interaction("fan").await.idol_given);
+
+        let response = app.oneshot(form("https://dashboard.example")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert!(state.stores.user_interaction.get_user_interaction("fan").await.idol_given);
+
+        let audit = state.stores.audit_log.get_audit_log(Some("fan"), 10).await;
+        assert_eq!(audit[0].action, AuditAction::SetIdolGiven);
+        assert_eq!(audit[0].actor_id, "host_1");
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::ai::Classifier;
+use crate::models::Sentiment;
+
+const LABELS: [Sentiment; 3] = [Sentiment::Positive, Sentiment::Negative, Sentiment::Neutral];
+
+/// A message with the sentiment a human gave it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LabeledExample {
+    pub message: String,
+    pub label: Sentiment,
+}
+
+/// Reads a dataset of JSON lines shaped like `{"message": "...", "label": "positive"}`.
+/// Blank lines and lines starting with `//` are skipped.
+pub fn load_dataset(path: &Path) -> io::Result<Vec<LabeledExample>> {
+    let mut examples = Vec::new();
+    for (number, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+        let example = serde_json::from_str(line)
+            .map_err(|why| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", number + 1, why)))?;
+        examples.push(example);
+    }
+    Ok(examples)
+}
+
+/// An example the classifier got wrong or couldn't answer.
+#[derive(Debug, Clone)]
+pub struct EvalFailure {
+    pub message: String,
+    pub expected: Sentiment,
+    /// `Err` holds the classifier's error message
+    pub actual: Result<Sentiment, String>,
+}
+
+/// How a classifier did on a dataset.
+#[derive(Debug, Clone, Default)]
+pub struct EvalReport {
+    /// `confusion[expected][predicted]`, indexed positive, negative, neutral
+    pub confusion: [[usize; 3]; 3],
+    /// Examples the classifier returned an error for; they count as wrong but aren't in the matrix
+    pub errors: usize,
+    pub failures: Vec<EvalFailure>,
+}
+
+fn label_index(sentiment: &Sentiment) -> usize {
+    match sentiment {
+        Sentiment::Positive => 0,
+        Sentiment::Negative => 1,
+        Sentiment::Neutral => 2,
+    }
+}
+
+impl EvalReport {
+    pub fn record(&mut self, example: &LabeledExample, actual: Result<Sentiment, String>) {
+        match &actual {
+            Ok(predicted) => self.confusion[label_index(&example.label)][label_index(predicted)] += 1,
+            Err(_) => self.errors += 1,
+        }
+        if actual.as_ref() != Ok(&example.label) {
+            self.failures.push(EvalFailure {
+                message: example.message.clone(),
+                expected: example.label.clone(),
+                actual,
+            });
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.confusion.iter().flatten().sum::<usize>() + self.errors
+    }
+
+    pub fn correct(&self) -> usize {
+        (0..3).map(|i| self.confusion[i][i]).sum()
+    }
+
+    pub fn accuracy(&self) -> f64 {
+        ratio(self.correct(), self.total())
+    }
+
+    /// Of the messages classified as `sentiment`, the share that really were.
+    pub fn precision(&self, sentiment: &Sentiment) -> f64 {
+        let i = label_index(sentiment);
+        ratio(self.confusion[i][i], (0..3).map(|expected| self.confusion[expected][i]).sum())
+    }
+
+    /// Of the messages labeled `sentiment`, the share the classifier found. Errors count as misses.
+    pub fn recall(&self, sentiment: &Sentiment) -> f64 {
+        let i = label_index(sentiment);
+        let labeled = self.confusion[i].iter().sum::<usize>()
+            + self.failures.iter().filter(|failure| failure.actual.is_err() && &failure.expected == sentiment).count();
+        ratio(self.confusion[i][i], labeled)
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 { 0.0 } else { numerator as f64 / denominator as f64 }
+}
+
+impl fmt::Display for EvalReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Accuracy: {:.1}% ({}/{}, {} errors)", self.accuracy() * 100.0, self.correct(), self.total(), self.errors)?;
+        writeln!(f)?;
+        writeln!(f, "{:<10} {:>9} {:>9}", "label", "precision", "recall")?;
+        for sentiment in &LABELS {
+            writeln!(f, "{:<10} {:>9.3} {:>9.3}", sentiment.as_str(), self.precision(sentiment), self.recall(sentiment))?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Confusion matrix (rows: expected, columns: predicted)")?;
+        writeln!(f, "{:<10} {:>9} {:>9} {:>9}", "", "positive", "negative", "neutral")?;
+        for sentiment in &LABELS {
+            let row = self.confusion[label_index(sentiment)];
+            writeln!(f, "{:<10} {:>9} {:>9} {:>9}", sentiment.as_str(), row[0], row[1], row[2])?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs `classifier` over every example, one at a time.
+pub async fn evaluate(classifier: &(dyn Classifier + Send + Sync), dataset: &[LabeledExample]) -> EvalReport {
+    let mut report = EvalReport::default();
+    for example in dataset {
+        let actual = classifier
+            .classify(&example.message)
+            .await
+            .map(|classification| classification.sentiment)
+            .map_err(|why| why.to_string());
+        report.record(example, actual);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    use async_trait::async_trait;
+
+    use crate::ai::{Classification, KeywordClassifier};
+
+    /// Always answers positive, and fails on empty messages.
+    struct AlwaysPositive;
+
+    #[async_trait]
+    impl Classifier for AlwaysPositive {
+        async fn classify(&self, message: &str) -> Result<Classification, Box<dyn Error + Send + Sync>> {
+            if message.is_empty() {
+                return Err("empty message".into());
+            }
+            Ok(Classification { sentiment: Sentiment::Positive, confidence: None, usage: None })
+        }
+    }
+
+    fn example(message: &str, label: Sentiment) -> LabeledExample {
+        LabeledExample { message: message.to_string(), label }
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_report() {
+        let dataset = vec![
+            example("You're the best, Toodles", Sentiment::Positive),
+            example("What's your favorite trick?", Sentiment::Positive),
+            example("Go away clown", Sentiment::Negative),
+            example("", Sentiment::Neutral),
+        ];
+        let report = evaluate(&AlwaysPositive, &dataset).await;
+
+        assert_eq!(report.total(), 4);
+        assert_eq!(report.correct(), 2);
+        assert_eq!(report.errors, 1);
+        assert_eq!(report.confusion[0], [2, 0, 0]);
+        assert_eq!(report.confusion[1], [1, 0, 0]);
+        assert_eq!(report.accuracy(), 0.5);
+        assert!((report.precision(&Sentiment::Positive) - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(report.recall(&Sentiment::Positive), 1.0);
+        assert_eq!(report.recall(&Sentiment::Neutral), 0.0, "Expected an error to count as a miss");
+        assert_eq!(report.failures.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_bundled_dataset_loads() {
+        let dataset = load_dataset(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/data/classifier_eval.jsonl"))).unwrap();
+        for sentiment in &LABELS {
+            assert!(dataset.iter().any(|example| &example.label == sentiment), "Expected {} examples in the dataset", sentiment);
+        }
+
+        let report = evaluate(&KeywordClassifier::new(), &dataset).await;
+        assert_eq!(report.total(), dataset.len());
+        assert_eq!(report.errors, 0);
+    }
+}
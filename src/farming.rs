@@ -0,0 +1,121 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::models::{ChatMessage, ChatRole, FarmingFlag, Sentiment, SentimentEvent};
+
+/// How many of the player's own messages a compliment is compared against
+const RECENT_MESSAGES: usize = 10;
+/// Messages older than this can be repeated without looking like farming
+const DUPLICATE_WINDOW: Duration = Duration::hours(24);
+/// Share of character trigrams two messages must have in common to count as the same message
+const DUPLICATE_SIMILARITY: f64 = 0.8;
+/// The window compliments are counted in for diminishing returns
+pub const COMPLIMENT_WINDOW: Duration = Duration::hours(1);
+/// Compliments within the window that count in full. After that each one counts at half the rate of the last.
+const FULL_CREDIT_COMPLIMENTS: usize = 3;
+
+/// Lowercase letters and digits with single spaces between words, so punctuation and emphasis don't hide a repeat.
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn trigrams(text: &str) -> HashSet<[char; 3]> {
+    let chars: Vec<char> = format!(" {} ", text).chars().collect();
+    chars.windows(3).map(|window| [window[0], window[1], window[2]]).collect()
+}
+
+/// Jaccard similarity of the two messages' character trigrams, from 0 (nothing shared) to 1 (the same).
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize(a), normalize(b));
+    if a == b {
+        return 1.0;
+    }
+    let (a, b) = (trigrams(&a), trigrams(&b));
+    a.intersection(&b).count() as f64 / a.union(&b).count() as f64
+}
+
+/// Whether `content` is nearly the same as one of the player's recent messages in `history`.
+pub fn is_near_duplicate(content: &str, history: &[ChatMessage], now: DateTime<Utc>) -> bool {
+    history
+        .iter()
+        .rev()
+        .filter(|message| message.role == ChatRole::User)
+        .take(RECENT_MESSAGES)
+        .filter(|message| message.created_at.is_none_or(|created_at| created_at >= now - DUPLICATE_WINDOW))
+        .any(|message| similarity(content, &message.content) >= DUPLICATE_SIMILARITY)
+}
+
+/// Whether the next compliment goes uncounted, given how many in the window were `counted`
+/// out of `total`. The first few count; then every 2nd, every 4th, every 8th and so on.
+fn is_diminished(counted: usize, total: usize) -> bool {
+    if counted < FULL_CREDIT_COMPLIMENTS {
+        return false;
+    }
+    let step = 1usize << (counted - FULL_CREDIT_COMPLIMENTS + 1).min(16);
+    !(total + 1).is_multiple_of(step)
+}
+
+/// Why a positive message shouldn't move the player's counters, if it looks like farming.
+/// `recent_events` are the player's classifications within [`COMPLIMENT_WINDOW`].
+pub fn check_compliment(content: &str, history: &[ChatMessage], recent_events: &[SentimentEvent], now: DateTime<Utc>) -> Option<FarmingFlag> {
+    if is_near_duplicate(content, history, now) {
+        return Some(FarmingFlag::Duplicate);
+    }
+    let compliments: Vec<&SentimentEvent> = recent_events
+        .iter()
+        .filter(|event| event.sentiment == Sentiment::Positive && event.created_at >= now - COMPLIMENT_WINDOW)
+        .collect();
+    let counted = compliments.iter().filter(|event| event.farming.is_none()).count();
+    is_diminished(counted, compliments.len()).then_some(FarmingFlag::Diminished)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_message(content: &str, created_at: DateTime<Utc>) -> ChatMessage {
+        ChatMessage { created_at: Some(created_at), ..ChatMessage::new(ChatRole::User, content.to_string()) }
+    }
+
+    #[test]
+    fn test_near_duplicates() {
+        assert_eq!(similarity("You're so funny Toodles!", "you're so funny, toodles"), 1.0);
+        assert!(similarity("you're so funny Toodles", "you're sooo funny Toodles!!") >= DUPLICATE_SIMILARITY);
+        assert!(similarity("you're so funny Toodles", "I loved the juggling act last night") < DUPLICATE_SIMILARITY);
+
+        let now = Utc::now();
+        let history = vec![
+            user_message("you're so funny Toodles", now - Duration::days(2)),
+            ChatMessage::new(ChatRole::Assistant, "Honk! You're so funny Toodles".to_string()),
+            user_message("nice juggling", now - Duration::minutes(5)),
+        ];
+        assert!(is_near_duplicate("Nice juggling!", &history, now));
+        assert!(!is_near_duplicate("you're so funny Toodles", &history, now), "Expected old messages and Toodles's own lines to be ignored");
+    }
+
+    #[test]
+    fn test_diminishing_returns() {
+        let now = Utc::now();
+        let mut events: Vec<SentimentEvent> = Vec::new();
+        let mut counted = Vec::new();
+        for n in 1..=16 {
+            let farming = check_compliment(&format!("compliment number {}", n * 1000), &[], &events, now);
+            if farming.is_none() {
+                counted.push(n);
+            }
+            events.push(SentimentEvent { farming, ..SentimentEvent::new("1", None, Sentiment::Positive, None) });
+        }
+        assert_eq!(counted, vec![1, 2, 3, 4, 8, 16]);
+
+        let earlier: Vec<SentimentEvent> = events
+            .into_iter()
+            .map(|event| SentimentEvent { created_at: now - COMPLIMENT_WINDOW - Duration::minutes(1), ..event })
+            .collect();
+        assert_eq!(check_compliment("one more", &[], &earlier, now), None, "Expected compliments outside the window to be forgotten");
+    }
+}
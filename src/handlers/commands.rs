@@ -0,0 +1,623 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{NaiveTime, Utc};
+use serenity::all::{Context, CreateAllowedMentions, CreateAttachment, CreateMessage, Message};
+use tracing::{info, warn};
+
+use crate::handlers::{deny, member_roles, DiscordHandler, Role};
+use crate::metrics::METRICS;
+use crate::models::{AuditAction, AuditEntry, BudgetPeriod, ChatRole, Feature, GuildSettings, QuietHours, QuietMode, Sentiment, SettingChange, UsageScope, UsageSummary, UserInteraction};
+use crate::player_data::{adjust_player_counter, export_player_data, forget_player, reset_player, set_player_counter, summarize_player, OverrideOutcome};
+
+/// How many of a player's latest messages `/player` shows
+const PLAYER_SUMMARY_RECENT_MESSAGES: usize = 6;
+/// Longest excerpt of a single message `/player` shows
+const PLAYER_SUMMARY_EXCERPT_CHARS: usize = 150;
+/// How many players `/usage` lists as the guild's biggest spenders
+const USAGE_TOP_SPENDERS: usize = 5;
+
+/// Commands start with `/` right after the bot prefix, e.g. `!toodles /export`,
+/// so ordinary chat with Toodles is never mistaken for one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Send the player everything Toodles stores about them
+    Export,
+    /// Ask to erase everything Toodles stores about the player
+    Forget,
+    /// Confirm a pending `Forget`
+    ConfirmForget,
+    /// Host only: show a player's counters, tier and recent history
+    ShowPlayer { user_id: String },
+    /// Host only: overwrite one of a player's counters
+    SetCounter { user_id: String, sentiment: Sentiment, value: usize },
+    /// Host only: move one of a player's counters up or down
+    AdjustCounter { user_id: String, sentiment: Sentiment, delta: i64 },
+    /// Host only: zero a player's counters
+    ResetPlayer { user_id: String },
+    /// Host only: correct the classified sentiment of a player's message.
+    /// Without a message ID it applies to the message the command replies to.
+    OverrideSentiment { message_id: Option<String>, sentiment: Sentiment },
+    /// Host only: show the tokens spent in this guild, or by one player, against the budgets
+    ShowUsage { user_id: Option<String> },
+    /// Admin only: show how Toodles is set up in this guild
+    ShowSettings,
+    /// Admin only: change one of this guild's settings
+    ChangeSetting(SettingChange),
+}
+
+impl Command {
+    /// Parses the text after the prefix. Returns `None` for anything that is not a known command.
+    pub fn parse(user_message: &str) -> Option<Command> {
+        let mut words = user_message.split_whitespace();
+        let command = words.next()?.strip_prefix('/')?;
+        let args: Vec<&str> = words.collect();
+
+        match (command.to_lowercase().as_str(), args.as_slice()) {
+            ("export", []) => Some(Command::Export),
+            ("forget", []) => Some(Command::Forget),
+            ("forget", ["confirm"]) => Some(Command::ConfirmForget),
+            ("player", [user]) => Some(Command::ShowPlayer { user_id: parse_user_id(user)? }),
+            ("player", [user, "set", sentiment, value]) => Some(Command::SetCounter {
+                user_id: parse_user_id(user)?,
+                sentiment: Sentiment::parse(sentiment)?,
+                value: value.parse().ok().filter(|value| *value <= UserInteraction::MAX_COUNT)?,
+            }),
+            ("player", [user, "adjust", sentiment, delta]) => Some(Command::AdjustCounter {
+                user_id: parse_user_id(user)?,
+                sentiment: Sentiment::parse(sentiment)?,
+                delta: i64::from(delta.trim_start_matches('+').parse::<i32>().ok()?),
+            }),
+            ("player", [user, "reset"]) => Some(Command::ResetPlayer { user_id: parse_user_id(user)? }),
+            ("override", [sentiment]) => Some(Command::OverrideSentiment { message_id: None, sentiment: Sentiment::parse(sentiment)? }),
+            ("override", [message, sentiment]) => Some(Command::OverrideSentiment {
+                message_id: Some(parse_message_id(message)?),
+                sentiment: Sentiment::parse(sentiment)?,
+            }),
+            ("usage", []) => Some(Command::ShowUsage { user_id: None }),
+            ("usage", [user]) => Some(Command::ShowUsage { user_id: Some(parse_user_id(user)?) }),
+            ("settings", []) => Some(Command::ShowSettings),
+            ("settings", [setting, rest @ ..]) => Some(Command::ChangeSetting(parse_setting_change(setting, rest)?)),
+            _ => None,
+        }
+    }
+
+    /// Who may use the command, or no one in particular if anyone may. Everyone keeps the right
+    /// to see and erase their own data, whatever their roles and even in direct messages.
+    pub fn allowed_roles(&self) -> &'static [Role] {
+        match self {
+            Command::Export | Command::Forget | Command::ConfirmForget => &[],
+            Command::ShowPlayer { .. }
+            | Command::SetCounter { .. }
+            | Command::AdjustCounter { .. }
+            | Command::ResetPlayer { .. }
+            | Command::OverrideSentiment { .. }
+            | Command::ShowUsage { .. } => &[Role::Host],
+            Command::ShowSettings | Command::ChangeSetting(_) => &[Role::Admin],
+        }
+    }
+
+    /// The player whose data the command reads or changes, if it is not the caller.
+    pub fn target_user_id(&self) -> Option<&str> {
+        match self {
+            Command::ShowPlayer { user_id }
+            | Command::SetCounter { user_id, .. }
+            | Command::AdjustCounter { user_id, .. }
+            | Command::ResetPlayer { user_id } => Some(user_id),
+            Command::Export
+            | Command::Forget
+            | Command::ConfirmForget
+            | Command::OverrideSentiment { .. }
+            | Command::ShowUsage { .. }
+            | Command::ShowSettings
+            | Command::ChangeSetting(_) => None,
+        }
+    }
+}
+
+/// Parses the arguments of `/settings <setting> ...`. `reset` falls back to the bot-wide configuration.
+fn parse_setting_change(setting: &str, args: &[&str]) -> Option<SettingChange> {
+    match (setting.to_lowercase().as_str(), args) {
+        ("prefix", ["reset"]) => Some(SettingChange::Prefix(None)),
+        ("prefix", [prefix]) => Some(SettingChange::Prefix(Some(prefix.to_string()))),
+        ("channels", ["add", channel]) => Some(SettingChange::EnableChannel(parse_channel_id(channel)?)),
+        ("channels", ["remove", channel]) => Some(SettingChange::DisableChannel(parse_channel_id(channel)?)),
+        ("channels", ["deny", channel]) => Some(SettingChange::DenyChannel(parse_channel_id(channel)?)),
+        ("channels", ["undeny", channel]) => Some(SettingChange::UndenyChannel(parse_channel_id(channel)?)),
+        ("channels", ["clear"]) => Some(SettingChange::ClearChannels),
+        ("quiet", ["off"]) => Some(SettingChange::QuietHours(None)),
+        ("quiet", [window]) => Some(SettingChange::QuietHours(Some(parse_quiet_hours(window, "UTC")?))),
+        ("quiet", [window, offset]) => Some(SettingChange::QuietHours(Some(parse_quiet_hours(window, offset)?))),
+        ("quietmode", [mode]) => Some(SettingChange::QuietMode(QuietMode::parse(mode)?)),
+        ("persona", ["reset"]) => Some(SettingChange::Persona(None)),
+        ("persona", words) if !words.is_empty() => Some(SettingChange::Persona(Some(words.join(" ")))),
+        ("welcome", ["reset"]) => Some(SettingChange::WelcomeChannel(None)),
+        ("welcome", [channel]) => Some(SettingChange::WelcomeChannel(Some(parse_channel_id(channel)?))),
+        ("hostrole", ["reset"]) => Some(SettingChange::HostRole(None)),
+        ("hostrole", [role]) => Some(SettingChange::HostRole(Some(parse_role_id(role)?))),
+        ("replylength", ["reset"]) => Some(SettingChange::MaxReplyTokens(None)),
+        ("replylength", [tokens]) => Some(SettingChange::MaxReplyTokens(Some(tokens.parse().ok().filter(|tokens| *tokens > 0)?))),
+        ("enable", [feature]) => Some(SettingChange::Feature(Feature::parse(feature)?, true)),
+        ("disable", [feature]) => Some(SettingChange::Feature(Feature::parse(feature)?, false)),
+        _ => None,
+    }
+}
+
+/// Parses `22:00-08:00` and an offset such as `UTC`, `+02:00` or `UTC-5`.
+fn parse_quiet_hours(window: &str, offset: &str) -> Option<QuietHours> {
+    let (start, end) = window.split_once('-')?;
+    let offset = offset.trim_start_matches("UTC").trim_start_matches("utc");
+    let utc_offset_minutes = match offset.split_at_checked(1) {
+        None => 0,
+        Some((sign, amount)) => {
+            let (hours, minutes) = amount.split_once(':').unwrap_or((amount, "0"));
+            let minutes = hours.parse::<i32>().ok()? * 60 + minutes.parse::<i32>().ok()?;
+            match sign {
+                "+" => minutes,
+                "-" => -minutes,
+                _ => return None,
+            }
+        },
+    };
+    // Real offsets run from UTC-12:00 to UTC+14:00
+    if !(-12 * 60..=14 * 60).contains(&utc_offset_minutes) {
+        return None;
+    }
+    Some(QuietHours {
+        start: NaiveTime::parse_from_str(start, "%H:%M").ok()?,
+        end: NaiveTime::parse_from_str(end, "%H:%M").ok()?,
+        utc_offset_minutes,
+    })
+}
+
+/// Accepts a channel mention (`<#123>`) or a bare channel ID.
+fn parse_channel_id(value: &str) -> Option<String> {
+    let id = value.strip_prefix("<#").and_then(|mention| mention.strip_suffix('>')).unwrap_or(value);
+    id.parse::<u64>().ok().map(|id| id.to_string())
+}
+
+/// Accepts a role mention (`<@&123>`) or a bare role ID.
+fn parse_role_id(value: &str) -> Option<String> {
+    let id = value.strip_prefix("<@&").and_then(|mention| mention.strip_suffix('>')).unwrap_or(value);
+    id.parse::<u64>().ok().map(|id| id.to_string())
+}
+
+/// What `/settings` shows hosts.
+fn describe_settings(settings: &GuildSettings, default_prefix: &str) -> String {
+    let or_default = |value: Option<String>| value.unwrap_or_else(|| "default".to_string());
+    let mention_channels = |channel_ids: &[String]| channel_ids.iter().map(|channel_id| format!("<#{}>", channel_id)).collect::<Vec<_>>().join(", ");
+    let mut channels = if settings.enabled_channel_ids.is_empty() { "every channel".to_string() } else { mention_channels(&settings.enabled_channel_ids) };
+    if !settings.denied_channel_ids.is_empty() {
+        channels.push_str(&format!(" except {}", mention_channels(&settings.denied_channel_ids)));
+    }
+    let quiet_hours = match settings.quiet_hours {
+        Some(quiet_hours) => format!("{} ({})", quiet_hours, settings.quiet_mode),
+        None => "none".to_string(),
+    };
+    let features = Feature::ALL
+        .iter()
+        .map(|feature| format!("{} {}", feature, if settings.is_enabled(*feature) { "on" } else { "off" }))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "🎪 Toodles's act in this guild\nPrefix: `{}`\nChats in: {}\nQuiet hours: {}\nPersona: {}\nWelcome channel: {}\nHost role: {}\nReply length: {}\nFeatures: {}",
+        settings.prefix.as_deref().unwrap_or(default_prefix),
+        channels,
+        quiet_hours,
+        settings.persona.as_deref().unwrap_or("default"),
+        or_default(settings.welcome_channel_id.as_ref().map(|channel_id| format!("<#{}>", channel_id))),
+        or_default(settings.host_role_id.as_ref().map(|role_id| format!("<@&{}>", role_id))),
+        or_default(settings.max_reply_tokens.map(|tokens| format!("{} tokens", tokens))),
+        features,
+    )
+}
+
+/// What `/usage` shows hosts. `limits` are the budgets that apply, by period.
+fn describe_usage(title: &str, today: &[UsageSummary], this_month: &[UsageSummary], limits: [(BudgetPeriod, Option<u64>); 2], list_spenders: bool) -> String {
+    let total = |summaries: &[UsageSummary]| summaries.iter().map(UsageSummary::total_tokens).sum::<u64>();
+    let against = |spent: u64, period: BudgetPeriod| match limits.iter().find(|(limit_period, _)| *limit_period == period).and_then(|(_, limit)| *limit) {
+        Some(limit) => format!("{} of {} tokens{}", spent, limit, if spent >= limit { " (spent)" } else { "" }),
+        None => format!("{} tokens", spent),
+    };
+    let mut report = format!(
+        "🧾 {}\nToday: {}\nThis month: {}",
+        title,
+        against(total(today), BudgetPeriod::Day),
+        against(total(this_month), BudgetPeriod::Month),
+    );
+
+    let mut models: Vec<(&str, u64, usize)> = Vec::new();
+    for summary in this_month {
+        match models.iter_mut().find(|(model, _, _)| *model == summary.model) {
+            Some((_, tokens, calls)) => {
+                *tokens += summary.total_tokens();
+                *calls += summary.calls;
+            },
+            None => models.push((&summary.model, summary.total_tokens(), summary.calls)),
+        }
+    }
+    if !models.is_empty() {
+        models.sort_by_key(|(_, tokens, _)| std::cmp::Reverse(*tokens));
+        let models: Vec<String> = models.iter().map(|(model, tokens, calls)| format!("{} {} tokens in {} calls", model, tokens, calls)).collect();
+        report.push_str(&format!("\nBy model this month: {}", models.join(", ")));
+    }
+
+    if list_spenders {
+        let mut spenders: Vec<(&str, u64)> = Vec::new();
+        for summary in this_month {
+            match spenders.iter_mut().find(|(user_id, _)| *user_id == summary.user_id) {
+                Some((_, tokens)) => *tokens += summary.total_tokens(),
+                None => spenders.push((&summary.user_id, summary.total_tokens())),
+            }
+        }
+        spenders.sort_by_key(|(_, tokens)| std::cmp::Reverse(*tokens));
+        let spenders: Vec<String> = spenders.iter().take(USAGE_TOP_SPENDERS).map(|(user_id, tokens)| format!("<@{}> {}", user_id, tokens)).collect();
+        if !spenders.is_empty() {
+            report.push_str(&format!("\nBiggest spenders this month: {}", spenders.join(", ")));
+        }
+    }
+    report
+}
+
+/// Accepts a Discord mention (`<@123>` or `<@!123>`) or a bare user ID.
+fn parse_user_id(value: &str) -> Option<String> {
+    let id = value
+        .strip_prefix("<@")
+        .and_then(|mention| mention.strip_suffix('>'))
+        .map(|mention| mention.trim_start_matches('!'))
+        .unwrap_or(value);
+    id.parse::<u64>().ok().map(|id| id.to_string())
+}
+
+/// Accepts a bare message ID or a Discord message link, whose last segment is the message ID.
+fn parse_message_id(value: &str) -> Option<String> {
+    let id = value.rsplit('/').next()?;
+    id.parse::<u64>().ok().map(|id| id.to_string())
+}
+
+/// Reactions hosts can put on a player's message to correct how it was classified
+pub fn sentiment_for_reaction(emoji: &str) -> Option<Sentiment> {
+    match emoji {
+        "🟢" => Some(Sentiment::Positive),
+        "🔴" => Some(Sentiment::Negative),
+        "⚪" => Some(Sentiment::Neutral),
+        _ => None,
+    }
+}
+
+/// Tracks destructive actions that a user has asked for but not yet confirmed.
+pub struct PendingConfirmations {
+    ttl: Duration,
+    pending: Mutex<HashMap<String, Instant>>,
+}
+
+impl PendingConfirmations {
+    pub fn new(ttl: Duration) -> Self {
+        PendingConfirmations {
+            ttl,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub fn request(&self, key: &str) {
+        self.pending.lock().unwrap().insert(key.to_string(), Instant::now());
+    }
+
+    /// Consumes the pending request. Returns `false` if there was none or it has expired.
+    pub fn confirm(&self, key: &str) -> bool {
+        self.pending
+            .lock()
+            .unwrap()
+            .remove(key)
+            .is_some_and(|requested_at| requested_at.elapsed() <= self.ttl)
+    }
+}
+
+/// Replies to `msg` without pinging anyone. Host replies name players and roles by mention so
+/// they render, and quote what players wrote, which may itself hold `@everyone` or a role mention.
+async fn reply_quietly(ctx: &Context, msg: &Message, content: impl Into<String>) -> serenity::Result<Message> {
+    let reply = CreateMessage::new()
+        .content(content)
+        .reference_message(msg)
+        .allowed_mentions(CreateAllowedMentions::new());
+    msg.channel_id.send_message(&ctx.http, reply).await
+}
+
+pub async fn handle_command(
+    ctx: Context,
+    msg: Message,
+    command: Command,
+    handler: &DiscordHandler,
+    guild_settings: Option<&GuildSettings>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let user_id = msg.author.id.to_string();
+    let prefix = handler.prefix_for(guild_settings);
+    let stores = &handler.stores;
+    let confirmations = &handler.confirmations;
+
+    let known_roles = msg.member.as_ref().map(|member| member.roles.as_slice());
+    let roles = member_roles(&ctx, msg.guild_id, msg.author.id, known_roles, &handler.role_map_for(guild_settings)).await;
+    if let Some(denial) = deny(&roles, command.allowed_roles()) {
+        msg.reply(&ctx.http, denial).await?;
+        return Ok(());
+    }
+
+    match command {
+        Command::Export => {
+            let export = export_player_data(stores, &user_id).await;
+            let file = CreateAttachment::bytes(serde_json::to_vec_pretty(&export)?, "toodles_memories.json");
+            let dm = CreateMessage::new()
+                .content("🤡 Everything Toodles remembers about you. Every. Little. Thing.")
+                .add_file(file);
+
+            // Player data goes to DMs so it never lands in a public channel
+            match msg.author.direct_message(&ctx.http, dm).await {
+                Ok(_) => {
+                    msg.reply(&ctx.http, "🤡 I slipped a little file into your DMs...").await?;
+                },
+                Err(why) => {
+                    METRICS.discord_error("direct_message");
+                    warn!(%user_id, error = ?why, "Error sending export");
+                    msg.reply(&ctx.http, "🤡 I tried to whisper your secrets to you, but your DMs are closed.").await?;
+                }
+            }
+        },
+        Command::Forget => {
+            confirmations.request(&user_id);
+            msg.reply(&ctx.http, format!(
+                "🤡 You want me to forget you? Everything? Our chats, how I feel about you... all of it?\nSay `{} /forget confirm` within {} seconds and you'll be a stranger again.",
+                prefix,
+                confirmations.ttl().as_secs(),
+            )).await?;
+        },
+        Command::ConfirmForget => {
+            if !confirmations.confirm(&user_id) {
+                msg.reply(&ctx.http, format!("🤡 Forget what? Ask me with `{} /forget` first.", prefix)).await?;
+                return Ok(());
+            }
+
+            let stats = forget_player(stores, &user_id, &user_id).await;
+            info!(
+                %user_id,
+                chat_messages_deleted = stats.chat_messages_deleted,
+                sentiment_events_deleted = stats.sentiment_events_deleted,
+                interaction_deleted = stats.interaction_deleted,
+                "Forgot player",
+            );
+            msg.reply(&ctx.http, "🤡 ...who are you again?").await?;
+        },
+        Command::ShowPlayer { user_id: player_id } => {
+            let summary = summarize_player(stores, &player_id, PLAYER_SUMMARY_RECENT_MESSAGES).await;
+            let mut reply = format!(
+                "🎪 Toodles's file on <@{}>\nTier: **{}**\nPositive: {} | Negative: {} | Neutral: {}",
+                player_id, summary.tier, summary.interaction.num_positive, summary.interaction.num_negative, summary.interaction.num_neutral,
+            );
+            if summary.suspected_farming > 0 {
+                reply.push_str(&format!("\n⚠️ Suspected farming: {} compliments not counted", summary.suspected_farming));
+            }
+            if summary.recent_history.is_empty() {
+                reply.push_str("\nNo conversations yet.");
+            } else {
+                reply.push_str("\nRecent history:");
+                for message in &summary.recent_history {
+                    let speaker = match message.role {
+                        ChatRole::User => "Player",
+                        ChatRole::Assistant => "Toodles",
+                        ChatRole::System => "System",
+                    };
+                    let excerpt: String = message.content.chars().take(PLAYER_SUMMARY_EXCERPT_CHARS).collect();
+                    reply.push_str(&format!("\n> **{}**: {}", speaker, excerpt.replace('\n', " ")));
+                }
+            }
+            reply_quietly(&ctx, &msg, reply).await?;
+        },
+        Command::SetCounter { user_id: player_id, sentiment, value } => {
+            let interaction = set_player_counter(stores, &user_id, &player_id, &sentiment, value).await;
+            reply_quietly(&ctx, &msg, format!("🤡 Done. <@{}> now has {} {} interactions and is **{}**.", player_id, value, sentiment, interaction.tier())).await?;
+        },
+        Command::AdjustCounter { user_id: player_id, sentiment, delta } => {
+            let interaction = adjust_player_counter(stores, &user_id, &player_id, &sentiment, delta).await;
+            reply_quietly(&ctx, &msg, format!(
+                "🤡 Done. <@{}> now has {} {} interactions and is **{}**.",
+                player_id, interaction.count(&sentiment), sentiment, interaction.tier(),
+            )).await?;
+        },
+        Command::ResetPlayer { user_id: player_id } => {
+            reset_player(stores, &user_id, &player_id).await;
+            reply_quietly(&ctx, &msg, format!("🤡 <@{}> and I are starting over. Fresh face paint.", player_id)).await?;
+        },
+        Command::OverrideSentiment { message_id, sentiment } => {
+            let Some(message_id) = message_id.or_else(|| msg.message_reference.as_ref().and_then(|reference| reference.message_id).map(|id| id.to_string())) else {
+                msg.reply(&ctx.http, format!("🤡 Which message? Reply to it, or use `{} /override <message id> <sentiment>`.", prefix)).await?;
+                return Ok(());
+            };
+            let reply = match handler.override_sentiment(&user_id, &message_id, sentiment.clone()).await {
+                OverrideOutcome::UnknownMessage => "🤡 I never heard that one. Was it even meant for me?".to_string(),
+                OverrideOutcome::Unchanged => format!("🤡 I already took that one as {}.", sentiment),
+                OverrideOutcome::Overridden(sentiment_override) => format!(
+                    "🤡 Fine, I'll remember <@{}>'s message as {} instead.",
+                    sentiment_override.user_id, sentiment,
+                ),
+            };
+            reply_quietly(&ctx, &msg, reply).await?;
+        },
+        Command::ShowUsage { user_id: player_id } => {
+            let (scope, title) = match (player_id, msg.guild_id) {
+                (Some(player_id), _) => (UsageScope::User(player_id.clone()), format!("What <@{}> has cost Toodles", player_id)),
+                (None, Some(guild_id)) => (UsageScope::Guild(guild_id.to_string()), "Toodles's tab for this server".to_string()),
+                (None, None) => {
+                    msg.reply(&ctx.http, "🤡 Whose tab? Ask me in a server, or name a player.").await?;
+                    return Ok(());
+                },
+            };
+            let now = Utc::now();
+            let today = stores.usage.summarize_usage(&scope, BudgetPeriod::Day.start(now)).await;
+            let this_month = stores.usage.summarize_usage(&scope, BudgetPeriod::Month.start(now)).await;
+            let limits = handler.reply_settings.budget.limits(&scope);
+            let list_spenders = matches!(scope, UsageScope::Guild(_));
+            reply_quietly(&ctx, &msg, describe_usage(&title, &today, &this_month, limits, list_spenders)).await?;
+        },
+        Command::ShowSettings => {
+            let Some(settings) = guild_settings else {
+                msg.reply(&ctx.http, "🤡 Settings belong to a server. Ask me there.").await?;
+                return Ok(());
+            };
+            reply_quietly(&ctx, &msg, describe_settings(settings, &handler.prefix)).await?;
+        },
+        Command::ChangeSetting(change) => {
+            let Some(settings) = guild_settings else {
+                msg.reply(&ctx.http, "🤡 Settings belong to a server. Ask me there.").await?;
+                return Ok(());
+            };
+            let mut settings = settings.clone();
+            info!(host_id = %user_id, guild_id = %settings.guild_id, ?change, "Changed guild settings");
+            let details = format!("Applied {:?}", change);
+            settings.apply(change);
+            stores.guild_settings.set_guild_settings(settings.clone()).await;
+            stores.audit_log.record_audit_entry(AuditEntry::new(&user_id, AuditAction::ChangeSetting, &settings.guild_id, details)).await;
+            reply_quietly(&ctx, &msg, format!("🤡 The show goes on, rearranged.\n{}", describe_settings(&settings, &handler.prefix))).await?;
+        },
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(Command::parse(" /export"), Some(Command::Export));
+        assert_eq!(Command::parse(" /FORGET "), Some(Command::Forget));
+        assert_eq!(Command::parse(" /forget confirm"), Some(Command::ConfirmForget));
+        assert_eq!(Command::parse(" /forget everything"), None);
+        assert_eq!(Command::parse(" forget me, you're lame"), None, "Expected plain chat not to be a command");
+        assert_eq!(Command::parse(""), None);
+    }
+
+    #[test]
+    fn test_parse_host_command() {
+        assert_eq!(Command::parse(" /player <@123>"), Some(Command::ShowPlayer { user_id: "123".to_string() }));
+        assert_eq!(Command::parse(" /player <@!123> reset"), Some(Command::ResetPlayer { user_id: "123".to_string() }));
+        assert_eq!(
+            Command::parse(" /player 123 set negative 4"),
+            Some(Command::SetCounter { user_id: "123".to_string(), sentiment: Sentiment::Negative, value: 4 }),
+        );
+        assert_eq!(
+            Command::parse(" /player 123 adjust positive +2"),
+            Some(Command::AdjustCounter { user_id: "123".to_string(), sentiment: Sentiment::Positive, delta: 2 }),
+        );
+        assert_eq!(
+            Command::parse(" /player 123 adjust positive -3"),
+            Some(Command::AdjustCounter { user_id: "123".to_string(), sentiment: Sentiment::Positive, delta: -3 }),
+        );
+        assert_eq!(Command::parse(" /player @someone"), None, "Expected a non-ID user to be rejected");
+        assert_eq!(Command::parse(" /player 123 set grumpy 4"), None, "Expected an unknown sentiment to be rejected");
+        assert_eq!(Command::parse(" /player 123 set negative -4"), None, "Expected a negative value to be rejected");
+        assert_eq!(Command::parse(" /player 123 set negative 4294967296"), None, "Expected a value too big to store to be rejected");
+        assert_eq!(Command::parse(" /player 123 adjust negative -2147483649"), None, "Expected a delta too big to store to be rejected");
+
+        assert_eq!(Command::parse(" /player 123").unwrap().allowed_roles(), &[Role::Host]);
+        for command in [Command::Export, Command::Forget, Command::ConfirmForget] {
+            assert_eq!(deny(&[Role::Player], command.allowed_roles()), None);
+            assert_eq!(deny(&[Role::Jury], command.allowed_roles()), None, "Expected eliminated players to keep their data rights");
+            assert_eq!(deny(&[], command.allowed_roles()), None, "Expected members without a game role, and direct messages, to keep their data rights");
+        }
+        assert!(deny(&[Role::Player], Command::parse(" /player 123").unwrap().allowed_roles()).is_some());
+    }
+
+    #[test]
+    fn test_parse_override_command() {
+        assert_eq!(
+            Command::parse(" /override positive"),
+            Some(Command::OverrideSentiment { message_id: None, sentiment: Sentiment::Positive }),
+        );
+        assert_eq!(
+            Command::parse(" /override https://discord.com/channels/1/2/345 neutral"),
+            Some(Command::OverrideSentiment { message_id: Some("345".to_string()), sentiment: Sentiment::Neutral }),
+        );
+        assert_eq!(Command::parse(" /override 345 sideways"), None);
+        assert_eq!(Command::parse(" /override 345 negative").unwrap().allowed_roles(), &[Role::Host]);
+
+        assert_eq!(sentiment_for_reaction("🟢"), Some(Sentiment::Positive));
+        assert_eq!(sentiment_for_reaction("👍"), None, "Expected everyday reactions not to override anything");
+    }
+
+    #[test]
+    fn test_parse_settings_command() {
+        assert_eq!(Command::parse(" /settings"), Some(Command::ShowSettings));
+        assert_eq!(Command::parse(" /settings prefix !clown"), Some(Command::ChangeSetting(SettingChange::Prefix(Some("!clown".to_string())))));
+        assert_eq!(Command::parse(" /settings prefix reset"), Some(Command::ChangeSetting(SettingChange::Prefix(None))));
+        assert_eq!(Command::parse(" /settings channels add <#42>"), Some(Command::ChangeSetting(SettingChange::EnableChannel("42".to_string()))));
+        assert_eq!(Command::parse(" /settings hostrole <@&7>"), Some(Command::ChangeSetting(SettingChange::HostRole(Some("7".to_string())))));
+        assert_eq!(
+            Command::parse(" /settings persona Be   extra spooky"),
+            Some(Command::ChangeSetting(SettingChange::Persona(Some("Be extra spooky".to_string())))),
+        );
+        assert_eq!(Command::parse(" /settings disable welcome"), Some(Command::ChangeSetting(SettingChange::Feature(Feature::Welcome, false))));
+        assert_eq!(Command::parse(" /settings replylength 0"), None, "Expected an empty reply length to be rejected");
+        assert_eq!(Command::parse(" /settings disable juggling"), None);
+        assert_eq!(Command::parse(" /settings channels deny <#43>"), Some(Command::ChangeSetting(SettingChange::DenyChannel("43".to_string()))));
+        assert_eq!(Command::parse(" /settings quietmode ignore"), Some(Command::ChangeSetting(SettingChange::QuietMode(QuietMode::Ignore))));
+        assert_eq!(Command::parse(" /settings quiet off"), Some(Command::ChangeSetting(SettingChange::QuietHours(None))));
+        assert_eq!(Command::parse(" /settings channels add general"), None);
+        assert_eq!(Command::ShowSettings.allowed_roles(), &[Role::Admin]);
+        assert_eq!(Command::parse(" /settings prefix !clown").unwrap().allowed_roles(), &[Role::Admin]);
+    }
+
+    #[test]
+    fn test_usage_command() {
+        assert_eq!(Command::parse(" /usage"), Some(Command::ShowUsage { user_id: None }));
+        assert_eq!(Command::parse(" /usage <@123>"), Some(Command::ShowUsage { user_id: Some("123".to_string()) }));
+        assert_eq!(Command::parse(" /usage").unwrap().allowed_roles(), &[Role::Host]);
+
+        let summary = |user_id: &str, model: &str, tokens: u64| UsageSummary {
+            user_id: user_id.to_string(),
+            model: model.to_string(),
+            calls: 2,
+            prompt_tokens: tokens,
+            completion_tokens: 0,
+        };
+        let today = [summary("1", "big", 300)];
+        let this_month = [summary("1", "big", 900), summary("2", "big", 400), summary("1", "small", 50)];
+        let report = describe_usage("Tab", &today, &this_month, [(BudgetPeriod::Day, Some(300)), (BudgetPeriod::Month, None)], true);
+        assert_eq!(
+            report,
+            "🧾 Tab\nToday: 300 of 300 tokens (spent)\nThis month: 1350 tokens\nBy model this month: big 1300 tokens in 4 calls, small 50 tokens in 2 calls\nBiggest spenders this month: <@1> 950, <@2> 400",
+        );
+    }
+
+    #[test]
+    fn test_parse_quiet_hours() {
+        let quiet_hours = parse_quiet_hours("22:00-08:30", "UTC-5").unwrap();
+        assert_eq!(quiet_hours.start, NaiveTime::from_hms_opt(22, 0, 0).unwrap());
+        assert_eq!(quiet_hours.end, NaiveTime::from_hms_opt(8, 30, 0).unwrap());
+        assert_eq!(quiet_hours.utc_offset_minutes, -300);
+        assert_eq!(parse_quiet_hours("22:00-08:00", "+05:30").unwrap().utc_offset_minutes, 330);
+        assert_eq!(parse_quiet_hours("22:00-08:00", "UTC").unwrap().utc_offset_minutes, 0);
+        assert_eq!(parse_quiet_hours("22:00-25:00", "UTC"), None);
+        assert_eq!(parse_quiet_hours("22:00", "UTC"), None);
+        assert_eq!(parse_quiet_hours("22:00-08:00", "+20"), None, "Expected an impossible offset to be rejected");
+    }
+
+    #[test]
+    fn test_pending_confirmations() {
+        let confirmations = PendingConfirmations::new(Duration::from_secs(60));
+        assert!(!confirmations.confirm("user"), "Expected no confirmation without a request");
+
+        confirmations.request("user");
+        assert!(confirmations.confirm("user"));
+        assert!(!confirmations.confirm("user"), "Expected a confirmation to be single use");
+
+        let expired = PendingConfirmations::new(Duration::ZERO);
+        expired.request("user");
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(!expired.confirm("user"), "Expected an expired request to be rejected");
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+type LockMap = Arc<Mutex<HashMap<String, Arc<AsyncMutex<()>>>>>;
+
+/// Hands out one async lock per conversation so turns for the same conversation
+/// run one after another, while different conversations still run in parallel.
+#[derive(Clone, Default)]
+pub struct ConversationLocks {
+    locks: LockMap,
+}
+
+/// Held for the duration of a turn. Dropping it lets the next queued turn run.
+pub struct ConversationGuard {
+    key: String,
+    locks: LockMap,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl ConversationLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits until no other turn holds the lock for `key`. Waiters are served in FIFO order.
+    pub async fn lock(&self, key: &str) -> ConversationGuard {
+        let lock = {
+            let mut locks = self.locks.lock().unwrap();
+            locks.entry(key.to_string()).or_default().clone()
+        };
+
+        let guard = lock.lock_owned().await;
+        ConversationGuard {
+            key: key.to_string(),
+            locks: self.locks.clone(),
+            guard: Some(guard),
+        }
+    }
+
+    /// Number of conversations that currently have a turn running or queued.
+    pub fn active_conversations(&self) -> usize {
+        self.locks.lock().unwrap().len()
+    }
+}
+
+impl Drop for ConversationGuard {
+    fn drop(&mut self) {
+        let mut locks = self.locks.lock().unwrap();
+        self.guard.take();
+
+        // Only the map still references the lock, so nobody is running or waiting on it
+        if locks.get(&self.key).is_some_and(|lock| Arc::strong_count(lock) == 1) {
+            locks.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_same_conversation_runs_in_order() {
+        let locks = ConversationLocks::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let mut handles = Vec::new();
+        for turn in 0..3 {
+            let locks = locks.clone();
+            let tx = tx.clone();
+            handles.push(tokio::spawn(async move {
+                let _guard = locks.lock("user").await;
+                tx.send(format!("start {}", turn)).unwrap();
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                tx.send(format!("end {}", turn)).unwrap();
+            }));
+            // Give each task time to queue up before spawning the next one
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        drop(tx);
+
+        let mut events = Vec::new();
+        while let Some(event) = rx.recv().await {
+            events.push(event);
+        }
+        assert_eq!(events, vec!["start 0", "end 0", "start 1", "end 1", "start 2", "end 2"]);
+        assert_eq!(locks.active_conversations(), 0, "Expected the lock to be cleaned up after the last turn");
+    }
+
+    #[tokio::test]
+    async fn test_different_conversations_run_in_parallel() {
+        let locks = ConversationLocks::new();
+
+        let _first = locks.lock("user_1").await;
+        let second = tokio::time::timeout(Duration::from_millis(50), locks.lock("user_2")).await;
+        assert!(second.is_ok(), "Expected a different user to get their lock without waiting");
+
+        let same = tokio::time::timeout(Duration::from_millis(50), locks.lock("user_1")).await;
+        assert!(same.is_err(), "Expected the same user to wait for the running turn");
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Default)]
+struct PendingTurn {
+    messages: Vec<String>,
+    latest: u64,
+}
+
+/// Merges messages from the same conversation that arrive within `window` of each other
+/// into a single user turn. The window restarts with every new message.
+#[derive(Clone)]
+pub struct Debouncer {
+    window: Duration,
+    pending: Arc<Mutex<HashMap<String, PendingTurn>>>,
+}
+
+impl Debouncer {
+    pub fn new(window: Duration) -> Self {
+        Debouncer {
+            window,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Queues `message` for `key` and waits out the window.
+    /// Returns the merged turn if no newer message arrived in the meantime, otherwise `None`
+    /// because the newer message's call will return the merged turn instead.
+    pub async fn submit(&self, key: &str, message: String) -> Option<String> {
+        let ticket = {
+            let mut pending = self.pending.lock().unwrap();
+            let turn = pending.entry(key.to_string()).or_default();
+            turn.messages.push(message);
+            turn.latest += 1;
+            turn.latest
+        };
+
+        tokio::time::sleep(self.window).await;
+
+        let mut pending = self.pending.lock().unwrap();
+        if pending.get(key).is_some_and(|turn| turn.latest == ticket) {
+            pending.remove(key).map(|turn| turn.messages.join("\n"))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_messages_within_window_are_merged() {
+        let debouncer = Debouncer::new(Duration::from_millis(50));
+
+        let first = {
+            let debouncer = debouncer.clone();
+            tokio::spawn(async move { debouncer.submit("user", "hey toodles".to_string()).await })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let second = {
+            let debouncer = debouncer.clone();
+            tokio::spawn(async move { debouncer.submit("user", "what's your story?".to_string()).await })
+        };
+
+        assert_eq!(first.await.unwrap(), None, "Expected the earlier message to be folded into the later one");
+        assert_eq!(second.await.unwrap(), Some("hey toodles\nwhat's your story?".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_messages_outside_window_are_separate_turns() {
+        let debouncer = Debouncer::new(Duration::from_millis(10));
+
+        assert_eq!(debouncer.submit("user", "hello".to_string()).await, Some("hello".to_string()));
+        assert_eq!(debouncer.submit("user", "again".to_string()).await, Some("again".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_different_users_are_not_merged() {
+        let debouncer = Debouncer::new(Duration::from_millis(20));
+
+        let (first, second) = tokio::join!(
+            debouncer.submit("user_1", "hi".to_string()),
+            debouncer.submit("user_2", "yo".to_string()),
+        );
+        assert_eq!(first, Some("hi".to_string()));
+        assert_eq!(second, Some("yo".to_string()));
+    }
+}
@@ -1,20 +1,57 @@
 use std::sync::Arc;
+use std::time::Duration;
 
-use serenity::all::Member;
+use serenity::all::{ChannelId, ConnectionStage, GuildId, Member, Reaction, ReactionType, ResumedEvent, RoleId, ShardStageUpdateEvent};
 use serenity::async_trait;
 use serenity::model::channel::Message;
 use serenity::model::gateway::Ready;
 use serenity::prelude::*;
+use tracing::{info, warn};
 
 
+use crate::ai::{Classifier, LlmBackend, OpenAiBackend, OpenAiClassifier, ReplySettings};
+use crate::handlers::commands::{handle_command, sentiment_for_reaction, Command, PendingConfirmations};
+use crate::handlers::permissions::{member_roles, Role, RoleMap};
+use crate::handlers::rate_limit::{RateLimiter, RateLimits, Throttle, RATE_LIMITED_REPLIES};
+use crate::platform::{ChatPlatform, DiscordPlatform, IncomingMessage};
+use crate::models::{Feature, GuildSettings, QuietMode, Sentiment};
+use crate::player_data::{override_sentiment, OverrideOutcome};
+use crate::handlers::conversation_lock::ConversationLocks;
+use crate::handlers::debounce::Debouncer;
 use crate::handlers::handle_message::handle_message;
-use crate::store::{ChatHistoryStore, UserInteractionStore};
+use crate::health::Health;
+use crate::metrics::METRICS;
+use crate::store::Stores;
 
+/// What Toodles says when called during quiet hours in brush-off mode
+const QUIET_HOURS_BRUSH_OFFS: [&str; 3] = [
+    "🤡 Shh. The big top is dark. Come back when the lights are on.",
+    "🤡 Toodles is sleeping with one eye open. Not for you, though.",
+    "🤡 The circus is closed. The clown is not taking questions.",
+];
+
+/// What Toodles says to eliminated players who still try to chat
+const JURY_CHAT_DENIAL: &str = "🤡 You've been voted out of the circus. Toodles doesn't play with ghosts. Boo.";
+
+/// How long a player has to confirm `/forget`
+const FORGET_CONFIRMATION_TTL: Duration = Duration::from_secs(60);
 
 pub struct DiscordHandler {
     pub prefix: String,
-    pub chat_history_store: Arc<dyn ChatHistoryStore + Send + Sync>,
-    pub user_interaction_store: Arc<dyn UserInteractionStore + Send + Sync>,
+    pub stores: Stores,
+    pub conversation_locks: ConversationLocks,
+    pub debouncer: Option<Debouncer>,
+    pub confirmations: Arc<PendingConfirmations>,
+    /// Which Discord roles make members players, jury, hosts or admins
+    pub roles: RoleMap,
+    /// Where new members are greeted, if anywhere
+    pub welcome_channel_id: Option<ChannelId>,
+    pub llm: Arc<dyn LlmBackend + Send + Sync>,
+    pub classifier: Arc<dyn Classifier + Send + Sync>,
+    pub reply_settings: ReplySettings,
+    /// Gateway state for readiness checks, and the turns a shutdown waits for
+    pub health: Arc<Health>,
+    pub rate_limiter: RateLimiter,
 }
 
 #[async_trait]
@@ -25,26 +62,268 @@ impl EventHandler for DiscordHandler {
             return;
         }
 
-        if msg.content.starts_with(&self.prefix) {
-            handle_message(&self.prefix, ctx, msg, self.chat_history_store.clone(), self.user_interaction_store.clone()).await.expect("Failed to handle message");
+        let guild_settings = self.guild_settings(msg.guild_id).await;
+        if let Some(user_message) = msg.content.strip_prefix(self.prefix_for(guild_settings.as_ref())) {
+            let user_id = msg.author.id.to_string();
+
+            if let Some(command) = Command::parse(user_message) {
+                // Commands touch the same data as turns, so they wait for any running turn.
+                // Overrides lock the player who sent the message once it has been looked up.
+                let _guard = match command {
+                    Command::OverrideSentiment { .. } => None,
+                    _ => Some(self.conversation_locks.lock(command.target_user_id().unwrap_or(&user_id)).await),
+                };
+                if let Err(why) = handle_command(ctx, msg, command, self, guild_settings.as_ref()).await {
+                    // Commands fail almost only when Discord refuses a reply
+                    METRICS.discord_error("command");
+                    warn!(error = ?why, "Error handling command");
+                }
+                return;
+            }
+
+            if let Some(settings) = &guild_settings {
+                if !settings.chats_in(&msg.channel_id.to_string()) {
+                    return;
+                }
+                if settings.is_quiet(chrono::Utc::now()) {
+                    // No turn is taken, so Toodles's mood toward the player doesn't change
+                    if settings.quiet_mode == QuietMode::BrushOff {
+                        let brush_off = QUIET_HOURS_BRUSH_OFFS[(msg.id.get() % QUIET_HOURS_BRUSH_OFFS.len() as u64) as usize];
+                        if let Err(why) = msg.reply(&ctx.http, brush_off).await {
+                            METRICS.discord_error("send_message");
+                            warn!(error = ?why, "Error sending quiet hours reply");
+                        }
+                    }
+                    return;
+                }
+            }
+
+            let known_roles = msg.member.as_ref().map(|member| member.roles.as_slice());
+            let roles = member_roles(&ctx, msg.guild_id, msg.author.id, known_roles, &self.role_map_for(guild_settings.as_ref())).await;
+            if !roles.iter().any(|role| matches!(role, Role::Player | Role::Host)) {
+                let denial = if roles.contains(&Role::Jury) { JURY_CHAT_DENIAL } else { Role::Player.denial() };
+                if let Err(why) = msg.reply(&ctx.http, denial).await {
+                    METRICS.discord_error("send_message");
+                    warn!(error = ?why, "Error sending chat denial");
+                }
+                return;
+            }
+
+            // Throttled messages never reach the stores, so spamming can't farm Toodles's affection
+            let guild_id = msg.guild_id.map(|guild_id| guild_id.to_string());
+            if let Throttle::Limited { notify } = self.rate_limiter.check(&user_id, &msg.channel_id.to_string(), guild_id.as_deref()) {
+                METRICS.record_turn("throttled");
+                if notify {
+                    let tired = RATE_LIMITED_REPLIES[(msg.id.get() % RATE_LIMITED_REPLIES.len() as u64) as usize];
+                    if let Err(why) = msg.reply(&ctx.http, tired).await {
+                        METRICS.discord_error("send_message");
+                        warn!(error = ?why, "Error sending rate limit reply");
+                    }
+                }
+                return;
+            }
+
+            let user_message = match &self.debouncer {
+                Some(debouncer) => match debouncer.submit(&user_id, user_message.to_string()).await {
+                    Some(merged) => merged,
+                    // A newer message from this user will carry this one along with it
+                    None => return,
+                },
+                None => user_message.to_string(),
+            };
+
+            // Once shutdown has begun, new turns are dropped while running ones finish
+            let Some(_turn) = self.health.begin_turn() else {
+                return;
+            };
+            // Chat history is kept per user, so a user's turns must not overlap
+            let _guard = self.conversation_locks.lock(&user_id).await;
+            let platform = DiscordPlatform::new(ctx.http.clone());
+            let incoming = IncomingMessage::from(&msg);
+            if let Err(why) = handle_message(&platform, &incoming, user_message, &self.stores, self.llm.as_ref(), self.classifier.as_ref(), &self.reply_settings).await {
+                // The player has already been told, and the turn or Discord call counted as failed where it happened
+                warn!(error = ?why, "Error handling message");
+            }
+        }
+    }
+
+    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        let ReactionType::Unicode(emoji) = &reaction.emoji else {
+            return;
+        };
+        let (Some(sentiment), Some(host_id)) = (sentiment_for_reaction(emoji), reaction.user_id) else {
+            return;
+        };
+        let guild_settings = self.guild_settings(reaction.guild_id).await;
+        if guild_settings.as_ref().is_some_and(|settings| !settings.is_enabled(Feature::Reactions)) {
+            return;
+        }
+        let known_roles = reaction.member.as_ref().map(|member| member.roles.as_slice());
+        if !member_roles(&ctx, reaction.guild_id, host_id, known_roles, &self.role_map_for(guild_settings.as_ref())).await.contains(&Role::Host) {
+            return;
+        }
+
+        let message_id = reaction.message_id.to_string();
+        if let OverrideOutcome::Overridden(sentiment_override) = self.override_sentiment(&host_id.to_string(), &message_id, sentiment).await {
+            info!(
+                %host_id,
+                %message_id,
+                classified = sentiment_override.classified_sentiment.as_str(),
+                corrected = sentiment_override.corrected_sentiment.as_str(),
+                "Host overrode sentiment",
+            );
+            // Let the host know the correction was taken
+            let platform = DiscordPlatform::new(ctx.http.clone());
+            if let Err(why) = platform.react(&reaction.channel_id.to_string(), &message_id, "✍️").await {
+                warn!(error = ?why, "Error acknowledging override");
+            }
         }
     }
 
     async fn guild_member_addition(&self, ctx: Context, new_member: Member) {
-        // This function can be used to handle new member additions
-        println!("New member added: {}", new_member.user.name);
-        let welcome_channel_id = serenity::model::id::ChannelId::new(733545069549977621u64);
-        let _ = welcome_channel_id.say(&ctx.http, format!("Welcome, {}! 🤡 Toodles the clown is here to make you laugh.", new_member.user.name)).await;
+        info!(guild_id = %new_member.guild_id, user_id = %new_member.user.id, "New member added");
+        let guild_settings = self.stores.guild_settings.get_guild_settings(&new_member.guild_id.to_string()).await;
+        if !guild_settings.is_enabled(Feature::Welcome) {
+            return;
+        }
+        let welcome_channel_id = guild_settings
+            .welcome_channel_id
+            .and_then(|channel_id| channel_id.parse().ok())
+            .map(ChannelId::new)
+            .or(self.welcome_channel_id);
+        let Some(welcome_channel_id) = welcome_channel_id else {
+            return;
+        };
+        if let Err(why) = welcome_channel_id.say(&ctx.http, format!("Welcome, {}! 🤡 Toodles the clown is here to make you laugh.", new_member.user.name)).await {
+            METRICS.discord_error("send_message");
+            warn!(error = ?why, "Error sending welcome message");
+        }
+    }
+
+    async fn ready(&self, _ctx: Context, ready: Ready) {
+        self.health.set_gateway_connected(true);
+        info!(user = %ready.user.name, guilds = ready.guilds.len(), "Bot is ready");
     }
 
-    async fn ready(&self, ctx: Context, ready: Ready) {
-        println!("Bot is ready!");
+    async fn resume(&self, _ctx: Context, _event: ResumedEvent) {
+        self.health.set_gateway_connected(true);
+        info!("Gateway session resumed");
+    }
+
+    async fn shard_stage_update(&self, _ctx: Context, event: ShardStageUpdateEvent) {
+        self.health.set_gateway_connected(event.new == ConnectionStage::Connected);
+        if event.new != ConnectionStage::Connected {
+            warn!(shard_id = %event.shard_id, stage = %event.new, "Gateway connection changed");
+        }
     }
 }
 
 impl DiscordHandler {
 
-    pub fn new(prefix: String, chat_history_store: Arc<dyn ChatHistoryStore + Send + Sync>, user_interaction_store: Arc<dyn UserInteractionStore + Send + Sync>) -> Self {
-        DiscordHandler { prefix, chat_history_store, user_interaction_store }
+    pub fn new(prefix: String, stores: Stores) -> Self {
+        let llm: Arc<dyn LlmBackend + Send + Sync> = Arc::new(OpenAiBackend::default());
+        DiscordHandler {
+            prefix,
+            stores,
+            conversation_locks: ConversationLocks::new(),
+            debouncer: None,
+            confirmations: Arc::new(PendingConfirmations::new(FORGET_CONFIRMATION_TTL)),
+            roles: RoleMap::default(),
+            welcome_channel_id: None,
+            classifier: Arc::new(OpenAiClassifier::with_backend(llm.clone())),
+            llm,
+            reply_settings: ReplySettings::default(),
+            health: Arc::new(Health::default()),
+            rate_limiter: RateLimiter::default(),
+        }
+    }
+
+    /// Settings hosts have made for the guild a message came from. Direct messages have none.
+    pub async fn guild_settings(&self, guild_id: Option<GuildId>) -> Option<GuildSettings> {
+        match guild_id {
+            Some(guild_id) => Some(self.stores.guild_settings.get_guild_settings(&guild_id.to_string()).await),
+            None => None,
+        }
+    }
+
+    /// Overrides a message's sentiment while holding the lock of the player who sent it,
+    /// so the correction can't interleave with one of their turns.
+    pub async fn override_sentiment(&self, host_id: &str, message_id: &str, sentiment: Sentiment) -> OverrideOutcome {
+        let owner = self.stores.sentiment_log.find_sentiment_event(message_id).await.map(|event| event.user_id);
+        let _guard = match &owner {
+            Some(user_id) => Some(self.conversation_locks.lock(user_id).await),
+            None => None,
+        };
+        override_sentiment(&self.stores, host_id, message_id, sentiment).await
+    }
+
+    pub fn prefix_for<'a>(&'a self, guild_settings: Option<&'a GuildSettings>) -> &'a str {
+        guild_settings.and_then(|settings| settings.prefix.as_deref()).unwrap_or(&self.prefix)
+    }
+
+    /// The configured roles plus the guild's own host role, if it has one.
+    pub fn role_map_for(&self, guild_settings: Option<&GuildSettings>) -> RoleMap {
+        let mut roles = self.roles.clone();
+        let guild_role = guild_settings
+            .and_then(|settings| settings.host_role_id.as_ref())
+            .and_then(|role_id| role_id.parse().ok())
+            .map(RoleId::new);
+        roles.host_role_ids.extend(guild_role);
+        roles
+    }
+
+    /// Send every model call, replies and classifications alike, through `llm`.
+    pub fn with_llm(mut self, llm: Arc<dyn LlmBackend + Send + Sync>) -> Self {
+        self.classifier = Arc::new(OpenAiClassifier::with_backend(llm.clone()));
+        self.llm = llm;
+        self
+    }
+
+    /// Classify messages with `classifier` instead of the default model.
+    pub fn with_classifier(mut self, classifier: Arc<dyn Classifier + Send + Sync>) -> Self {
+        self.classifier = classifier;
+        self
+    }
+
+    /// Reply with the model and length limit in `reply_settings`.
+    pub fn with_reply_settings(mut self, reply_settings: ReplySettings) -> Self {
+        self.reply_settings = reply_settings;
+        self
+    }
+
+    /// Greet new members in `channel_id`.
+    pub fn with_welcome_channel(mut self, channel_id: ChannelId) -> Self {
+        self.welcome_channel_id = Some(channel_id);
+        self
+    }
+
+    /// Give players `ttl` to confirm `/forget`.
+    pub fn with_forget_confirmation_ttl(mut self, ttl: Duration) -> Self {
+        self.confirmations = Arc::new(PendingConfirmations::new(ttl));
+        self
+    }
+
+    /// Decide who may chat and use which commands by these Discord roles.
+    pub fn with_roles(mut self, roles: RoleMap) -> Self {
+        self.roles = roles;
+        self
+    }
+
+    /// Report gateway state to, and take turns through, `health`.
+    pub fn with_health(mut self, health: Arc<Health>) -> Self {
+        self.health = health;
+        self
+    }
+
+    /// Throttle how fast players, channels and guilds can talk to Toodles.
+    pub fn with_rate_limits(mut self, limits: RateLimits) -> Self {
+        self.rate_limiter = RateLimiter::new(limits);
+        self
+    }
+
+    /// Merge messages a user sends within `window` of each other into a single turn.
+    pub fn with_debounce_window(mut self, window: Duration) -> Self {
+        self.debouncer = Some(Debouncer::new(window));
+        self
     }
 }
\ No newline at end of file
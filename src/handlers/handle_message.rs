@@ -1,74 +1,120 @@
-use std::sync::Arc;
+use tracing::warn;
 
-use serenity::all::{Context, EditMessage, Message};
-
-use crate::{ai::{ask_toodles, classify_interaction, construct_system_prompt}, models::Sentiment, store::{ChatHistoryStore, UserInteractionStore}};
+use crate::{ai::{Classifier, LlmBackend, ReplySettings}, conversation::{record_turn, take_turn, TurnInput}, platform::{ChatPlatform, IncomingMessage}, store::Stores};
 
 
 pub async fn handle_message(
-    prefix: &str,
-    ctx: Context,
-    msg: Message,
-    chat_history_store: Arc<dyn ChatHistoryStore + Send + Sync>,
-    user_interaction_store: Arc<dyn UserInteractionStore + Send + Sync>,
+    platform: &(dyn ChatPlatform + Send + Sync),
+    msg: &IncomingMessage,
+    user_message: String,
+    stores: &Stores,
+    llm: &(dyn LlmBackend + Send + Sync),
+    classifier: &(dyn Classifier + Send + Sync),
+    reply_settings: &ReplySettings,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let user_id = msg.author.id.to_string();
-    let username = &msg.author.name;
-    let user_message = msg.content.strip_prefix(prefix).unwrap_or(&msg.content).to_string();
-
+    let username = &msg.author_name;
 
-    let mut thinking_msg = match msg.reply(&ctx.http, "🤡 Toodles is thinking...").await {
-        Ok(m) => m,
-        Err(why) => {
-            return Err(Box::new(why));
-        }
-    };
 
-    let sentiment = classify_interaction(&user_message).await?;
-    let mut chat_history = chat_history_store.get_chat_history(&user_id).await;
-    let mut user_interaction = user_interaction_store.get_user_interaction(&user_id).await;
+    let thinking_msg = platform.reply(msg, "🤡 Toodles is thinking...").await?;
 
-    match sentiment {
-        Sentiment::Positive => {
-            user_interaction.increment_positive();
-            user_interaction_store.increment_positive_interaction(&user_id).await;
-            println!("User {} sent a positive message: {}", username, user_message);
-        },
-        Sentiment::Negative => {
-            user_interaction.increment_negative();
-            user_interaction_store.increment_negative_interaction(&user_id).await;
-            println!("User {} sent a negative message: {}", username, user_message);
-        },
-        Sentiment::Neutral => {
-            user_interaction.increment_neutral();
-            user_interaction_store.increment_neutral_interaction(&user_id).await;
-            println!("User {} sent a neutral message: {}", username, user_message);
-        }
-    }
-
-    let system_message = construct_system_prompt(&username, user_interaction.num_positive, user_interaction.num_negative, user_interaction.num_neutral, false);
-    println!("Constructed system message: {}", system_message);
-    chat_history.set_system_message(system_message);
-    chat_history.add_user_message(user_message.clone());
+    let input = TurnInput {
+        user_id: msg.author_id.clone(),
+        username: username.clone(),
+        content: user_message,
+        message_id: Some(msg.id.clone()),
+        channel_id: Some(msg.channel_id.clone()),
+        guild_id: msg.guild_id.clone(),
+    };
 
-    match ask_toodles(&chat_history).await {
-        Ok(reply) => {
-            if let Err(why) = thinking_msg.edit(&ctx.http, EditMessage::new().content(&reply)).await {
-                println!("Error sending response message: {:?}", why);
+    match take_turn(stores, llm, classifier, reply_settings, &input).await {
+        Ok(outcome) => {
+            if let Err(why) = platform.edit(&thinking_msg, &outcome.reply.content).await {
+                warn!(error = ?why, "Error sending response message");
             }
 
             // Add to the chat history store
-            chat_history_store.add_user_message(&user_id, user_message.clone()).await;
-            chat_history_store.add_assistant_message(&user_id, reply.clone()).await;
+            record_turn(stores, &input, &outcome, Some(thinking_msg.id.clone())).await;
         },
         Err(e) => {
-            if let Err(why) = thinking_msg.edit(&ctx.http, EditMessage::new().content("🤡 Toodles encountered an error while thinking!")).await {
-                println!("Error sending error message: {:?}", why);
-                return Err(Box::new(why));
+            if let Err(why) = platform.edit(&thinking_msg, "🤡 Toodles encountered an error while thinking!").await {
+                warn!(error = ?why, "Error sending error message");
+                return Err(why);
             }
             return Err(e);
         }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    use async_openai::types::{CreateChatCompletionRequest, CreateChatCompletionResponse};
+    use async_trait::async_trait;
+
+    use crate::ai::{CannedBackend, KeywordClassifier};
+    use crate::models::{ChatRole, Sentiment};
+    use crate::platform::InMemoryPlatform;
+
+    struct UnreachableBackend;
+
+    #[async_trait]
+    impl LlmBackend for UnreachableBackend {
+        async fn create_chat_completion(&self, _request: CreateChatCompletionRequest) -> Result<CreateChatCompletionResponse, Box<dyn Error + Send + Sync>> {
+            Err("connection refused".into())
+        }
+    }
+
+    fn incoming(content: &str) -> IncomingMessage {
+        IncomingMessage {
+            id: "100".to_string(),
+            channel_id: "200".to_string(),
+            guild_id: Some("300".to_string()),
+            author_id: "test_user".to_string(),
+            author_name: "Tester".to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_message_replies_and_records_turn() {
+        let platform = InMemoryPlatform::new();
+        let stores = Stores::in_memory();
+        let msg = incoming("!toodles I love you Toodles!");
+
+        handle_message(&platform, &msg, "I love you Toodles!".to_string(), &stores, &CannedBackend::new("Honk honk!"), &KeywordClassifier::new(), &ReplySettings::default())
+            .await
+            .unwrap();
+
+        let sent = platform.sent_messages();
+        assert_eq!(sent.len(), 1, "Expected the thinking message to be edited rather than a second message sent");
+        assert_eq!(sent[0].reply_to.as_deref(), Some("100"));
+        assert_eq!(sent[0].content, "Honk honk!");
+        assert_eq!(sent[0].edits, vec!["🤡 Toodles is thinking...".to_string()]);
+
+        let history = stores.chat_history.get_chat_history("test_user").await.messages;
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].content, "I love you Toodles!");
+        assert_eq!(history[0].guild_id.as_deref(), Some("300"));
+        assert_eq!(history[0].sentiment, Some(Sentiment::Positive));
+        assert_eq!(history[1].role, ChatRole::Assistant);
+        assert_eq!(history[1].message_id, Some(sent[0].id.clone()));
+    }
+
+    #[tokio::test]
+    async fn test_handle_message_reports_errors_in_character() {
+        let platform = InMemoryPlatform::new();
+        let stores = Stores::in_memory();
+        let msg = incoming("!toodles hello");
+
+        let result = handle_message(&platform, &msg, "hello".to_string(), &stores, &UnreachableBackend, &KeywordClassifier::new(), &ReplySettings::default()).await;
+        assert!(result.is_err());
+
+        let sent = platform.sent_messages();
+        assert_eq!(sent[0].content, "🤡 Toodles encountered an error while thinking!");
+        assert!(stores.chat_history.get_chat_history("test_user").await.messages.is_empty(), "Expected a failed turn to stay out of the history");
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tracing::warn;
+
+use crate::ai::{Classifier, LlmBackend, ReplySettings};
+use crate::handlers::conversation_lock::ConversationLocks;
+use crate::handlers::handle_message::handle_message;
+use crate::handlers::rate_limit::{RateLimiter, Throttle, RATE_LIMITED_REPLIES};
+use crate::health::Health;
+use crate::metrics::METRICS;
+use crate::platform::{ChatPlatform, IncomingMessage, IrcLine, IrcPlatform};
+use crate::store::Stores;
+
+/// Where Toodles connects on IRC and who the nicknames there belong to.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IrcConfig {
+    /// `host:port` of a plaintext IRC server or bouncer
+    pub server: String,
+    pub nickname: String,
+    pub channels: Vec<String>,
+    /// Services accounts of players who also play elsewhere, mapped to their existing user IDs
+    /// so their standing with Toodles carries over. Nicks can be taken by anyone, so the mapping
+    /// only applies when the server reports the sender logged in to that account (IRCv3 `account-tag`).
+    /// Everyone else becomes `irc:<nick>`.
+    pub nick_user_ids: HashMap<String, String>,
+}
+
+impl Default for IrcConfig {
+    fn default() -> Self {
+        IrcConfig {
+            server: String::new(),
+            nickname: "toodles".to_string(),
+            channels: Vec::new(),
+            nick_user_ids: HashMap::new(),
+        }
+    }
+}
+
+/// Answers prefixed messages on IRC with the same stores, classifier and persona as Discord.
+/// IRC users have no Discord roles, so everyone here may chat. To keep an eliminated player from
+/// talking to Toodles under their Discord ID, remove their account from `nick_user_ids`.
+pub struct IrcHandler {
+    pub prefix: String,
+    pub config: IrcConfig,
+    pub stores: Stores,
+    pub llm: Arc<dyn LlmBackend + Send + Sync>,
+    pub classifier: Arc<dyn Classifier + Send + Sync>,
+    pub reply_settings: ReplySettings,
+    pub conversation_locks: ConversationLocks,
+    /// Shared with the other platforms so a shutdown waits for IRC turns too
+    pub health: Arc<Health>,
+    /// Shared with the other platforms so their limits add up
+    pub rate_limiter: RateLimiter,
+}
+
+impl IrcHandler {
+    pub fn new(
+        prefix: String,
+        config: IrcConfig,
+        stores: Stores,
+        llm: Arc<dyn LlmBackend + Send + Sync>,
+        classifier: Arc<dyn Classifier + Send + Sync>,
+    ) -> Self {
+        IrcHandler {
+            prefix,
+            config,
+            stores,
+            llm,
+            classifier,
+            reply_settings: ReplySettings::default(),
+            conversation_locks: ConversationLocks::new(),
+            health: Arc::new(Health::default()),
+            rate_limiter: RateLimiter::default(),
+        }
+    }
+
+    /// The user ID a sender's data is stored under, given the services `account` the server
+    /// says they are logged in to. Nicknames and accounts are case-insensitive on IRC.
+    pub fn user_id(&self, nick: &str, account: Option<&str>) -> String {
+        account
+            .and_then(|account| {
+                let account = account.to_lowercase();
+                self.config.nick_user_ids.iter().find(|(mapped, _)| mapped.to_lowercase() == account)
+            })
+            .map(|(_, user_id)| user_id.clone())
+            .unwrap_or_else(|| format!("irc:{}", nick.to_lowercase()))
+    }
+
+    /// Connects to the configured server and serves it until the connection closes.
+    pub async fn run(self: Arc<Self>) -> io::Result<()> {
+        let stream = TcpStream::connect(&self.config.server).await?;
+        self.serve(stream).await
+    }
+
+    pub async fn serve(self: Arc<Self>, stream: TcpStream) -> io::Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<String>();
+        let writer_task = tokio::spawn(async move {
+            while let Some(line) = outgoing_rx.recv().await {
+                writer.write_all(format!("{}\r\n", line).as_bytes()).await?;
+            }
+            io::Result::Ok(())
+        });
+        let platform = Arc::new(IrcPlatform::new(outgoing.clone()));
+
+        let mut nickname = self.config.nickname.clone();
+        let send = |line: String| outgoing.send(line).map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe));
+        // Ask for the sender's account on every message. Servers without IRCv3 ignore this.
+        send("CAP REQ :account-tag".to_string())?;
+        send(format!("NICK {}", nickname))?;
+        send(format!("USER {} 0 * :Toodles the clown", nickname))?;
+
+        let mut turns = JoinSet::new();
+        let mut received = 0u64;
+        let connected_at = chrono::Utc::now().timestamp_millis();
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await? {
+            let Some(line) = IrcLine::parse(&line) else {
+                continue;
+            };
+
+            match line.command.as_str() {
+                "PING" => send(format!("PONG :{}", line.params.last().map_or("", String::as_str)))?,
+                // Registration waits for capability negotiation to end, whether or not the server agreed
+                "CAP" if line.params.get(1).is_some_and(|reply| matches!(reply.as_str(), "ACK" | "NAK")) => send("CAP END".to_string())?,
+                // Welcome: registration is done, so channels can be joined
+                "001" => {
+                    for channel in &self.config.channels {
+                        send(format!("JOIN {}", channel))?;
+                    }
+                },
+                // Nickname in use
+                "433" => {
+                    nickname.push('_');
+                    send(format!("NICK {}", nickname))?;
+                },
+                "PRIVMSG" => {
+                    let (Some(nick), [target, content]) = (line.nick(), line.params.as_slice()) else {
+                        continue;
+                    };
+                    if nick.eq_ignore_ascii_case(&nickname) {
+                        continue;
+                    }
+                    let Some(user_message) = content.strip_prefix(&self.prefix) else {
+                        continue;
+                    };
+
+                    received += 1;
+                    let incoming = IncomingMessage {
+                        // IRC has no message IDs, so make ones that won't repeat across reconnects
+                        id: format!("irc:{}:{}", connected_at, received),
+                        // Private messages are answered privately
+                        channel_id: if target.eq_ignore_ascii_case(&nickname) { nick.to_string() } else { target.clone() },
+                        guild_id: None,
+                        author_id: self.user_id(nick, line.account()),
+                        author_name: nick.to_string(),
+                        content: content.clone(),
+                    };
+                    let user_message = user_message.trim().to_string();
+                    if let Throttle::Limited { notify } = self.rate_limiter.check(&incoming.author_id, &incoming.channel_id, None) {
+                        METRICS.record_turn("throttled");
+                        if notify {
+                            let tired = RATE_LIMITED_REPLIES[(received % RATE_LIMITED_REPLIES.len() as u64) as usize];
+                            if let Err(why) = platform.reply(&incoming, tired).await {
+                                warn!(error = ?why, "Error sending rate limit reply");
+                            }
+                        }
+                        continue;
+                    }
+                    let Some(turn) = self.health.begin_turn() else {
+                        continue;
+                    };
+                    let handler = self.clone();
+                    let platform = platform.clone();
+                    turns.spawn(async move {
+                        let _turn = turn;
+                        let _guard = handler.conversation_locks.lock(&incoming.author_id).await;
+                        if let Err(why) = handle_message(platform.as_ref(), &incoming, user_message, &handler.stores, handler.llm.as_ref(), handler.classifier.as_ref(), &handler.reply_settings).await {
+                            warn!(error = ?why, "Error handling IRC message");
+                        }
+                    });
+                },
+                _ => {},
+            }
+        }
+
+        // Let turns that were already running finish updating the stores
+        while turns.join_next().await.is_some() {}
+        drop(outgoing);
+        drop(platform);
+        writer_task.abort();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use tokio::io::{AsyncBufReadExt, Lines};
+    use tokio::net::TcpListener;
+    use tokio::net::tcp::OwnedReadHalf;
+
+    use crate::ai::{CannedBackend, KeywordClassifier};
+    use crate::models::Sentiment;
+
+    async fn expect_line(lines: &mut Lines<BufReader<OwnedReadHalf>>, expected: &str) {
+        let line = tokio::time::timeout(Duration::from_secs(5), lines.next_line())
+            .await
+            .expect("Timed out waiting for the bot")
+            .unwrap()
+            .expect("Expected the bot to keep the connection open");
+        assert_eq!(line, expected);
+    }
+
+    #[tokio::test]
+    async fn test_irc_handler_against_fake_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = IrcConfig {
+            server: listener.local_addr().unwrap().to_string(),
+            nickname: "toodles".to_string(),
+            channels: vec!["#circus".to_string()],
+            nick_user_ids: HashMap::from([("Alice".to_string(), "123456789".to_string())]),
+        };
+        let stores = Stores::in_memory();
+        let handler = Arc::new(IrcHandler::new(
+            "!toodles".to_string(),
+            config,
+            stores.clone(),
+            Arc::new(CannedBackend::new("Honk honk!")),
+            Arc::new(KeywordClassifier::new()),
+        ));
+        let bot = tokio::spawn(handler.run());
+
+        let (socket, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = socket.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        expect_line(&mut lines, "CAP REQ :account-tag").await;
+        expect_line(&mut lines, "NICK toodles").await;
+        expect_line(&mut lines, "USER toodles 0 * :Toodles the clown").await;
+        writer.write_all(b":fake.ircd CAP * ACK :account-tag\r\n").await.unwrap();
+        expect_line(&mut lines, "CAP END").await;
+        writer.write_all(b":fake.ircd 433 * toodles :Nickname is already in use\r\n").await.unwrap();
+        expect_line(&mut lines, "NICK toodles_").await;
+        writer.write_all(b":fake.ircd 001 toodles_ :Welcome\r\nPING :fake.ircd\r\n").await.unwrap();
+        expect_line(&mut lines, "JOIN #circus").await;
+        expect_line(&mut lines, "PONG :fake.ircd").await;
+
+        writer.write_all(b":bob!b@host PRIVMSG #circus :just chatting\r\n").await.unwrap();
+        writer.write_all(b"@account=alice :alice!a@host PRIVMSG #circus :!toodles I love you Toodles!\r\n").await.unwrap();
+        expect_line(&mut lines, "PRIVMSG #circus :🤡 Toodles is thinking...").await;
+        expect_line(&mut lines, "PRIVMSG #circus :Honk honk!").await;
+
+        // Someone else wearing Alice's nick without her login
+        writer.write_all(b":alice!x@elsewhere PRIVMSG #circus :!toodles I love you Toodles!\r\n").await.unwrap();
+        expect_line(&mut lines, "PRIVMSG #circus :🤡 Toodles is thinking...").await;
+        expect_line(&mut lines, "PRIVMSG #circus :Honk honk!").await;
+
+        writer.write_all(b":bob!b@host PRIVMSG toodles_ :!toodles go away clown\r\n").await.unwrap();
+        expect_line(&mut lines, "PRIVMSG bob :🤡 Toodles is thinking...").await;
+        expect_line(&mut lines, "PRIVMSG bob :Honk honk!").await;
+
+        drop(writer);
+        drop(lines);
+        tokio::time::timeout(Duration::from_secs(5), bot).await.unwrap().unwrap().unwrap();
+
+        assert_eq!(stores.user_interaction.get_user_interaction("123456789").await.num_positive, 1, "Expected Alice's account to map to her user ID");
+        assert_eq!(stores.user_interaction.get_user_interaction("irc:alice").await.num_positive, 1, "Expected a nick without a login to stay an IRC player");
+        let bob = stores.chat_history.get_chat_history("irc:bob").await.messages;
+        assert_eq!(bob.len(), 2);
+        assert_eq!(bob[0].content, "go away clown");
+        assert_eq!(bob[0].sentiment, Some(Sentiment::Negative));
+    }
+}
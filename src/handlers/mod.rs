@@ -1,5 +1,17 @@
+mod commands;
+mod conversation_lock;
+mod debounce;
 mod discord;
 mod handle_message;
+mod irc;
+mod permissions;
+mod rate_limit;
 
+pub use commands::*;
+pub use conversation_lock::*;
+pub use debounce::*;
 pub use discord::*;
-pub use handle_message::*;
\ No newline at end of file
+pub use handle_message::*;
+pub use irc::*;
+pub use permissions::*;
+pub use rate_limit::*;
\ No newline at end of file
@@ -0,0 +1,155 @@
+use std::fmt;
+
+use serenity::all::{Context, GuildId, RoleId, UserId};
+use tracing::warn;
+
+use crate::metrics::METRICS;
+
+/// What a member is in the game, as far as Toodles is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Still in the game
+    Player,
+    /// Voted out and sitting on the jury
+    Jury,
+    /// Runs the game
+    Host,
+    /// Sets Toodles up for the server
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Player => "player",
+            Role::Jury => "jury",
+            Role::Host => "host",
+            Role::Admin => "admin",
+        }
+    }
+
+    /// What Toodles says to someone who tried something that needs this role.
+    pub fn denial(&self) -> &'static str {
+        match self {
+            Role::Player => "🤡 No ticket, no show. Only players get to play with Toodles.",
+            Role::Jury => "🤡 Only the dearly departed sit on the jury. You're still very much alive... for now.",
+            Role::Host => "🤡 Ooh, trying to peek behind the curtain? Only the ringmasters get to do that.",
+            Role::Admin => "🤡 Moving the big top around is a job for the circus owners, not you.",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Which Discord roles make a member a player, jury member, host or admin.
+#[derive(Debug, Clone, Default)]
+pub struct RoleMap {
+    /// When empty, every member who isn't on the jury is a player
+    pub player_role_ids: Vec<RoleId>,
+    pub jury_role_ids: Vec<RoleId>,
+    pub host_role_ids: Vec<RoleId>,
+    /// Admins may also do everything hosts can. When empty, hosts are admins.
+    pub admin_role_ids: Vec<RoleId>,
+}
+
+impl RoleMap {
+    /// The game roles of a member with these Discord roles. Direct messages carry no Discord roles.
+    pub fn roles_of(&self, member_roles: &[RoleId]) -> Vec<Role> {
+        let has_any = |role_ids: &[RoleId]| member_roles.iter().any(|role| role_ids.contains(role));
+        let mut roles = Vec::new();
+        if has_any(&self.admin_role_ids) || (self.admin_role_ids.is_empty() && has_any(&self.host_role_ids)) {
+            roles.push(Role::Admin);
+        }
+        if has_any(&self.host_role_ids) || roles.contains(&Role::Admin) {
+            roles.push(Role::Host);
+        }
+        let jury = has_any(&self.jury_role_ids);
+        if jury {
+            roles.push(Role::Jury);
+        }
+        // Eliminated players keep their old roles around, but they're out of the game
+        if (has_any(&self.player_role_ids) || self.player_role_ids.is_empty()) && !jury {
+            roles.push(Role::Player);
+        }
+        roles
+    }
+
+    /// Whether a member's Discord roles could change what they're allowed to do.
+    fn is_empty(&self) -> bool {
+        self.player_role_ids.is_empty() && self.jury_role_ids.is_empty() && self.host_role_ids.is_empty() && self.admin_role_ids.is_empty()
+    }
+}
+
+/// `known_roles` avoids a member lookup when the event already carried the user's Discord roles.
+pub async fn member_roles(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    user_id: UserId,
+    known_roles: Option<&[RoleId]>,
+    role_map: &RoleMap,
+) -> Vec<Role> {
+    let Some(guild_id) = guild_id else {
+        return role_map.roles_of(&[]);
+    };
+    if let Some(roles) = known_roles {
+        return role_map.roles_of(roles);
+    }
+    if role_map.is_empty() {
+        return role_map.roles_of(&[]);
+    }
+    match guild_id.member(&ctx.http, user_id).await {
+        Ok(member) => role_map.roles_of(&member.roles),
+        Err(why) => {
+            METRICS.discord_error("get_member");
+            warn!(%user_id, error = ?why, "Error fetching member for permission check");
+            Vec::new()
+        }
+    }
+}
+
+/// Returns `None` if any of `roles` is allowed, or the denial Toodles should give otherwise.
+/// An empty `allowed` lets everyone in.
+pub fn deny(roles: &[Role], allowed: &[Role]) -> Option<&'static str> {
+    if allowed.is_empty() || roles.iter().any(|role| allowed.contains(role)) {
+        return None;
+    }
+    // Explain the most ordinary way in
+    allowed.first().map(Role::denial)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roles_of() {
+        let open = RoleMap { host_role_ids: vec![RoleId::new(2)], ..Default::default() };
+        assert_eq!(open.roles_of(&[]), vec![Role::Player], "Expected everyone to be a player without player roles");
+        assert_eq!(open.roles_of(&[RoleId::new(2)]), vec![Role::Admin, Role::Host, Role::Player], "Expected hosts to be admins without admin roles");
+
+        let roles = RoleMap {
+            player_role_ids: vec![RoleId::new(1)],
+            jury_role_ids: vec![RoleId::new(3)],
+            host_role_ids: vec![RoleId::new(2)],
+            admin_role_ids: vec![RoleId::new(4)],
+        };
+        assert_eq!(roles.roles_of(&[]), Vec::<Role>::new());
+        assert_eq!(roles.roles_of(&[RoleId::new(1)]), vec![Role::Player]);
+        assert_eq!(roles.roles_of(&[RoleId::new(2)]), vec![Role::Host]);
+        assert_eq!(roles.roles_of(&[RoleId::new(3)]), vec![Role::Jury]);
+        assert_eq!(roles.roles_of(&[RoleId::new(1), RoleId::new(3)]), vec![Role::Jury], "Expected the jury role to outrank a leftover player role");
+        assert_eq!(roles.roles_of(&[RoleId::new(4)]), vec![Role::Admin, Role::Host], "Expected admins to count as hosts");
+        assert_eq!(RoleMap { jury_role_ids: vec![RoleId::new(3)], ..Default::default() }.roles_of(&[RoleId::new(3)]), vec![Role::Jury], "Expected the jury to be out of the game");
+    }
+
+    #[test]
+    fn test_deny() {
+        assert_eq!(deny(&[Role::Player], &[Role::Player, Role::Host]), None);
+        assert_eq!(deny(&[Role::Player], &[Role::Host, Role::Admin]), Some(Role::Host.denial()));
+        assert_eq!(deny(&[], &[Role::Admin]), Some(Role::Admin.denial()));
+    }
+}
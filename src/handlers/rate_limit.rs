@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use serde::Deserialize;
+
+/// What Toodles says to a player who is talking to him too fast
+pub const RATE_LIMITED_REPLIES: [&str; 3] = [
+    "🤡 Toodles is tired of you. Go juggle something else for a while.",
+    "🤡 Honk honk, slow down. Even clowns need a breather.",
+    "🤡 The more you poke the clown, the less the clown likes poking back. Later.",
+];
+
+/// Past this many buckets, full ones are dropped since they behave like new ones
+const MAX_IDLE_BUCKETS: usize = 10_000;
+
+/// A token bucket: up to `burst` messages at once, refilled at `per_minute`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub per_minute: f64,
+}
+
+impl RateLimit {
+    /// Parses `<burst>/<per_minute>`, e.g. `5/3`.
+    pub fn parse(value: &str) -> Option<RateLimit> {
+        let (burst, per_minute) = value.trim().split_once('/')?;
+        let limit = RateLimit { burst: burst.trim().parse().ok()?, per_minute: per_minute.trim().parse().ok()? };
+        limit.is_valid().then_some(limit)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.burst > 0 && self.per_minute.is_finite() && self.per_minute > 0.0
+    }
+}
+
+/// How fast Toodles may be talked to, per player, channel and guild. Unset limits don't apply.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimits {
+    pub user: Option<RateLimit>,
+    pub channel: Option<RateLimit>,
+    pub guild: Option<RateLimit>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Scope {
+    User,
+    Channel,
+    Guild,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// Whether the player who ran it dry has been told, so spam isn't answered with spam
+    warned: bool,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.per_minute / 60.0).min(f64::from(limit.burst));
+        self.updated = now;
+    }
+}
+
+/// Whether a message may become a turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Throttle {
+    Allowed,
+    /// `notify` is set only the first time a bucket runs dry
+    Limited { notify: bool },
+}
+
+/// Token buckets shared by every platform, so a player can't dodge a limit by switching.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    limits: RateLimits,
+    buckets: Arc<Mutex<HashMap<(Scope, String), Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        RateLimiter { limits, buckets: Arc::new(Mutex::new(HashMap::new())) }
+    }
+
+    /// Takes a token from each bucket the message falls under, or none if any of them is empty.
+    pub fn check(&self, user_id: &str, channel_id: &str, guild_id: Option<&str>) -> Throttle {
+        self.check_at(user_id, channel_id, guild_id, Instant::now())
+    }
+
+    fn check_at(&self, user_id: &str, channel_id: &str, guild_id: Option<&str>, now: Instant) -> Throttle {
+        let applicable: Vec<(Scope, &str, RateLimit)> = [
+            (Scope::User, Some(user_id), self.limits.user),
+            (Scope::Channel, Some(channel_id), self.limits.channel),
+            (Scope::Guild, guild_id, self.limits.guild),
+        ]
+        .into_iter()
+        .filter_map(|(scope, id, limit)| Some((scope, id?, limit?)))
+        .collect();
+        if applicable.is_empty() {
+            return Throttle::Allowed;
+        }
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > MAX_IDLE_BUCKETS {
+            let limits = self.limits.clone();
+            buckets.retain(|(scope, _), bucket| {
+                let limit = match scope {
+                    Scope::User => limits.user,
+                    Scope::Channel => limits.channel,
+                    Scope::Guild => limits.guild,
+                };
+                limit.is_some_and(|limit| {
+                    bucket.refill(&limit, now);
+                    bucket.tokens < f64::from(limit.burst)
+                })
+            });
+        }
+
+        for (scope, id, limit) in &applicable {
+            let bucket = buckets.entry((*scope, id.to_string())).or_insert_with(|| Bucket {
+                tokens: f64::from(limit.burst),
+                updated: now,
+                warned: false,
+            });
+            bucket.refill(limit, now);
+            if bucket.tokens < 1.0 {
+                let notify = !bucket.warned;
+                bucket.warned = true;
+                return Throttle::Limited { notify };
+            }
+        }
+        for (scope, id, _) in &applicable {
+            if let Some(bucket) = buckets.get_mut(&(*scope, id.to_string())) {
+                bucket.tokens -= 1.0;
+                bucket.warned = false;
+            }
+        }
+        Throttle::Allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_token_buckets() {
+        let limiter = RateLimiter::new(RateLimits {
+            user: Some(RateLimit { burst: 2, per_minute: 6.0 }),
+            guild: Some(RateLimit { burst: 3, per_minute: 60.0 }),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        assert_eq!(limiter.check_at("1", "10", Some("9"), start), Throttle::Allowed);
+        assert_eq!(limiter.check_at("1", "10", Some("9"), start), Throttle::Allowed);
+        assert_eq!(limiter.check_at("1", "10", Some("9"), start), Throttle::Limited { notify: true });
+        assert_eq!(limiter.check_at("1", "10", Some("9"), start), Throttle::Limited { notify: false }, "Expected to be told only once");
+
+        assert_eq!(limiter.check_at("2", "10", Some("9"), start), Throttle::Allowed);
+        assert_eq!(limiter.check_at("3", "10", Some("9"), start), Throttle::Limited { notify: true }, "Expected the guild's bucket to be shared");
+        assert_eq!(limiter.check_at("3", "10", None, start), Throttle::Allowed, "Expected direct messages to skip the guild's bucket");
+
+        // One token back every 10 seconds
+        assert_eq!(limiter.check_at("1", "10", None, start + Duration::from_secs(10)), Throttle::Allowed);
+        assert_eq!(limiter.check_at("1", "10", None, start + Duration::from_secs(10)), Throttle::Limited { notify: true });
+    }
+
+    #[test]
+    fn test_parse_rate_limit() {
+        assert_eq!(RateLimit::parse("5/3"), Some(RateLimit { burst: 5, per_minute: 3.0 }));
+        assert_eq!(RateLimit::parse(" 2 / 0.5 "), Some(RateLimit { burst: 2, per_minute: 0.5 }));
+        assert_eq!(RateLimit::parse("0/3"), None);
+        assert_eq!(RateLimit::parse("5"), None);
+        assert_eq!(RateLimiter::default().check("1", "10", Some("9")), Throttle::Allowed, "Expected no limits by default");
+    }
+}
@@ -0,0 +1,214 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Serialize;
+use sqlx::PgPool;
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tokio::sync::Notify;
+
+/// How long readiness waits on the database before calling it unreachable
+const DATABASE_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What the rest of the bot reports about its connections, and the turns it is in the middle of.
+/// Shared by every platform so a shutdown drains them all.
+#[derive(Default)]
+pub struct Health {
+    gateway_connected: AtomicBool,
+    llm_failing: AtomicBool,
+    draining: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Notify,
+    /// `None` when the stores live in memory
+    pool: Option<PgPool>,
+}
+
+/// Held for the length of a turn. Dropping it lets a shutdown know the turn is done.
+pub struct TurnGuard {
+    health: Arc<Health>,
+}
+
+impl Drop for TurnGuard {
+    fn drop(&mut self) {
+        if self.health.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.health.idle.notify_waiters();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentState {
+    Ok,
+    Down,
+    /// Not used by this deployment
+    NotConfigured,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    /// The Discord gateway connection
+    pub gateway: ComponentState,
+    pub database: ComponentState,
+    /// Down while the last model call failed
+    pub llm: ComponentState,
+    /// Shutting down and no longer taking turns
+    pub draining: bool,
+    pub in_flight_turns: usize,
+}
+
+impl HealthReport {
+    pub fn is_ready(&self) -> bool {
+        !self.draining && [self.gateway, self.database, self.llm].iter().all(|state| *state != ComponentState::Down)
+    }
+}
+
+impl Health {
+    pub fn new(pool: Option<PgPool>) -> Self {
+        Health { pool, ..Default::default() }
+    }
+
+    pub fn set_gateway_connected(&self, connected: bool) {
+        self.gateway_connected.store(connected, Ordering::SeqCst);
+    }
+
+    pub fn record_llm_result(&self, succeeded: bool) {
+        self.llm_failing.store(!succeeded, Ordering::SeqCst);
+    }
+
+    /// Counts a turn as in flight, or returns `None` once shutdown has begun.
+    pub fn begin_turn(self: &Arc<Self>) -> Option<TurnGuard> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        // Counted before checking, so a shutdown can't miss a turn that slipped past the check
+        let guard = TurnGuard { health: self.clone() };
+        if self.is_draining() {
+            return None;
+        }
+        Some(guard)
+    }
+
+    /// Stops new turns from starting. Turns already running carry on.
+    pub fn start_draining(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Waits until no turn is in flight.
+    pub async fn wait_idle(&self) {
+        loop {
+            let notified = self.idle.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.in_flight.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    pub async fn report(&self) -> HealthReport {
+        let database = match &self.pool {
+            None => ComponentState::NotConfigured,
+            Some(pool) if pool.is_closed() => ComponentState::Down,
+            Some(pool) => match tokio::time::timeout(DATABASE_CHECK_TIMEOUT, sqlx::query("SELECT 1").execute(pool)).await {
+                Ok(Ok(_)) => ComponentState::Ok,
+                _ => ComponentState::Down,
+            },
+        };
+        HealthReport {
+            gateway: if self.gateway_connected.load(Ordering::SeqCst) { ComponentState::Ok } else { ComponentState::Down },
+            database,
+            llm: if self.llm_failing.load(Ordering::SeqCst) { ComponentState::Down } else { ComponentState::Ok },
+            draining: self.is_draining(),
+            in_flight_turns: self.in_flight.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// Always 200 while the process is up, with the same report as readiness for humans.
+async fn healthz(State(health): State<Arc<Health>>) -> Json<HealthReport> {
+    Json(health.report().await)
+}
+
+/// 503 while Toodles can't take turns, so traffic and alerts can go elsewhere.
+async fn readyz(State(health): State<Arc<Health>>) -> impl IntoResponse {
+    let report = health.report().await;
+    let status = if report.is_ready() { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(report))
+}
+
+/// `GET /healthz` for liveness and `GET /readyz` for readiness.
+pub fn router(health: Arc<Health>) -> Router {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(health)
+}
+
+pub async fn serve(addr: impl ToSocketAddrs, health: Arc<Health>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    axum::serve(listener, router(health)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn readiness(health: &Arc<Health>) -> (StatusCode, serde_json::Value) {
+        let response = router(health.clone()).oneshot(Request::get("/readyz").body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_readiness() {
+        let health = Arc::new(Health::new(None));
+        let (status, body) = readiness(&health).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE, "Expected not to be ready before the gateway connects");
+        assert_eq!(body["gateway"], "down");
+        assert_eq!(body["database"], "not_configured");
+
+        health.set_gateway_connected(true);
+        assert_eq!(readiness(&health).await.0, StatusCode::OK);
+
+        health.record_llm_result(false);
+        let (status, body) = readiness(&health).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["llm"], "down");
+        health.record_llm_result(true);
+
+        health.start_draining();
+        assert_eq!(readiness(&health).await.0, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_draining_waits_for_turns_in_flight() {
+        let health = Arc::new(Health::new(None));
+        let turn = health.begin_turn().expect("Expected turns to be accepted before shutdown");
+        health.start_draining();
+        assert!(health.begin_turn().is_none(), "Expected new turns to be refused while draining");
+        assert_eq!(health.report().await.in_flight_turns, 1);
+
+        let waiter = tokio::spawn({
+            let health = health.clone();
+            async move { health.wait_idle().await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished(), "Expected shutdown to wait for the running turn");
+
+        drop(turn);
+        tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+    }
+}
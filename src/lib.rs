@@ -0,0 +1,17 @@
+pub mod handlers;
+pub mod ai;
+pub mod api;
+pub mod auth;
+pub mod config;
+pub mod conversation;
+pub mod dashboard;
+pub mod eval;
+pub mod farming;
+pub mod health;
+pub mod logging;
+pub mod metrics;
+pub mod models;
+pub mod platform;
+pub mod player_data;
+pub mod retention;
+pub mod store;
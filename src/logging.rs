@@ -0,0 +1,75 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Deserialize;
+use tracing_subscriber::EnvFilter;
+
+/// Player-written text is kept out of logs unless this is turned off.
+static REDACT_CONTENT: AtomicBool = AtomicBool::new(true);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, with the current span's fields, for log collectors
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(value: &str) -> Option<LogFormat> {
+        match value.to_lowercase().as_str() {
+            "text" => Some(LogFormat::Text),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Installs the global subscriber. `RUST_LOG`, if set, takes precedence over `filter`.
+pub fn init(format: LogFormat, filter: &str, redact_content: bool) {
+    set_redact_content(redact_content);
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(filter));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    match format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().flatten_event(true).with_current_span(true).with_span_list(false).init(),
+    }
+}
+
+pub fn set_redact_content(redact: bool) {
+    REDACT_CONTENT.store(redact, Ordering::Relaxed);
+}
+
+/// Wraps player-written text for a log field, e.g. `debug!(content = %content(&message), ...)`.
+pub fn content(text: &str) -> Content<'_> {
+    Content(text)
+}
+
+pub struct Content<'a>(&'a str);
+
+impl fmt::Display for Content<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if REDACT_CONTENT.load(Ordering::Relaxed) {
+            write!(f, "[redacted {} chars]", self.0.chars().count())
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_redaction() {
+        assert_eq!(content("I love you Toodles!").to_string(), "[redacted 19 chars]");
+        set_redact_content(false);
+        assert_eq!(content("I love you Toodles!").to_string(), "I love you Toodles!");
+        set_redact_content(true);
+        assert_eq!(LogFormat::parse("JSON"), Some(LogFormat::Json));
+        assert_eq!(LogFormat::parse("xml"), None);
+    }
+}
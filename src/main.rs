@@ -1,15 +1,22 @@
-mod handlers;
-mod ai;
-mod models;
-mod store;
-
 use std::sync::Arc;
+use std::time::Duration;
 
 use dotenv::dotenv;
 
-use handlers::DiscordHandler;
-use serenity::{all::GatewayIntents, Client};
+use serenity::{all::ChannelId, Client};
 use sqlx::PgPool;
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{error, info, warn};
+use toodle_bot::ai::{MeteredBackend, OpenAiClassifier};
+use toodle_bot::api::{self, ApiState};
+use toodle_bot::config::{Config, StoreBackend};
+use toodle_bot::dashboard::{self, DashboardState};
+use toodle_bot::handlers::{DiscordHandler, IrcHandler};
+use toodle_bot::health::{self, Health};
+use toodle_bot::logging;
+use toodle_bot::metrics;
+use toodle_bot::retention;
+use toodle_bot::store::Stores;
 
 
 
@@ -19,43 +26,147 @@ async fn main() {
     // Load environment variables from .env file
     dotenv().ok();
 
-    let token = std::env::var("DISCORD_TOODLE_BOT_TOKEN")
-        .expect("Expected DISCORD_TOODLE_BOT_TOKEN in .env file");
-    let app_env = std::env::var("APP_ENV")
-        .unwrap_or_else(|_| "development".to_string());
-
-    let chat_history_store: Arc<dyn store::ChatHistoryStore + Send + Sync> = match app_env.as_str() {
-        "development" => Arc::new(store::InMemoryChatHistoryStore::new()),
-        "production" => {
-            let db_url = std::env::var("DATABASE_URL")
-                .expect("Expected DATABASE_URL in .env file for production");
-            let pool = PgPool::connect(&db_url).await.expect("Failed to connect to database");
-            Arc::new(store::PostgresChatHistoryStore::new(pool)) 
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(why) => {
+            eprintln!("{}", why);
+            std::process::exit(1);
         },
-        _ => panic!("Unknown APP_ENV: {}", app_env),
     };
+    logging::init(config.logging.format, &config.logging.filter, config.logging.redact_content);
 
-    let user_interaction_store: Arc<dyn store::UserInteractionStore + Send + Sync> = match app_env.as_str() {
-        "development" => Arc::new(store::InMemoryUserInteractionStore::new()),
-        "production" => {
-            let db_url = std::env::var("DATABASE_URL")
-                .expect("Expected DATABASE_URL in .env file for production");
-            let pool = PgPool::connect(&db_url).await.expect("Failed to connect to database");
-            Arc::new(store::PostgresUserInteractionStore::new(pool))
+    // The memory backend loses everything on restart, postgres shares one pool across the stores
+    let pool = match config.store.backend {
+        StoreBackend::Memory => None,
+        StoreBackend::Postgres => {
+            let database_url = config.store.database_url.as_deref().expect("Validated config has a database URL");
+            Some(PgPool::connect(database_url).await.expect("Failed to connect to database"))
         },
-        _ => panic!("Unknown APP_ENV: {}", app_env),
     };
-    let handler = DiscordHandler::new("!toodles".to_string(), chat_history_store, user_interaction_store);
+    let stores = match &pool {
+        Some(pool) => Stores::postgres(pool.clone()),
+        None => Stores::in_memory(),
+    };
+    // Shared by every platform, so readiness sees all of them and shutdown drains all of them
+    let health = Arc::new(Health::new(pool.clone()));
+
+    let retention_policy = config.retention.policy();
+    if !retention_policy.is_unbounded() {
+        retention::spawn_retention_task(stores.chat_history.clone(), stores.sentiment_overrides.clone(), retention_policy, config.retention.interval(), config.retention.archive_dir.clone());
+    }
+
+    // Record or replay model calls, e.g. to capture fixtures for offline tests
+    let llm = Arc::new(MeteredBackend::new(config.models.mode.backend(&config.models.fixtures_dir)).with_health(health.clone()));
+    let mut handler = DiscordHandler::new(config.discord.prefix.clone(), stores)
+        .with_llm(llm.clone())
+        .with_classifier(Arc::new(OpenAiClassifier::new(llm, config.models.classifier.clone())))
+        .with_reply_settings(config.reply_settings())
+        .with_roles(config.roles.role_map())
+        .with_forget_confirmation_ttl(Duration::from_secs(config.limits.forget_confirmation_secs))
+        .with_health(health.clone())
+        .with_rate_limits(config.rate_limits.clone());
+    if let Some(channel_id) = config.discord.welcome_channel_id {
+        handler = handler.with_welcome_channel(ChannelId::new(channel_id));
+    }
+    // Optional window for merging a user's rapid-fire messages into one turn
+    if let Some(debounce_ms) = config.limits.debounce_ms {
+        handler = handler.with_debounce_window(Duration::from_millis(debounce_ms));
+    }
+
+    // Optionally answer on an IRC server too, sharing the stores and model with Discord
+    if let Some(irc_config) = config.irc.clone() {
+        let mut irc_handler = IrcHandler::new(handler.prefix.clone(), irc_config, handler.stores.clone(), handler.llm.clone(), handler.classifier.clone());
+        irc_handler.reply_settings = handler.reply_settings.clone();
+        // A player mapped to the same user ID on both platforms still gets one turn at a time
+        irc_handler.conversation_locks = handler.conversation_locks.clone();
+        irc_handler.health = health.clone();
+        irc_handler.rate_limiter = handler.rate_limiter.clone();
+        let irc_handler = Arc::new(irc_handler);
+        tokio::spawn(async move {
+            if let Err(why) = irc_handler.run().await {
+                error!(error = ?why, "IRC connection failed");
+            }
+        });
+    }
+
+    // Optional HTTP API for other services, e.g. the game website
+    if let Some(api_config) = config.api.clone() {
+        let state = ApiState {
+            stores: handler.stores.clone(),
+            llm: handler.llm.clone(),
+            classifier: handler.classifier.clone(),
+            reply_settings: handler.reply_settings.clone(),
+            conversation_locks: handler.conversation_locks.clone(),
+            health: health.clone(),
+            rate_limiter: handler.rate_limiter.clone(),
+            token: api_config.token,
+        };
+        tokio::spawn(async move {
+            if let Err(why) = api::serve(api_config.addr, state).await {
+                error!(error = ?why, "API server failed");
+            }
+        });
+    }
 
-    let intents = GatewayIntents::GUILD_MESSAGES
-        | GatewayIntents::DIRECT_MESSAGES
-        | GatewayIntents::MESSAGE_CONTENT
-        | GatewayIntents::GUILD_MEMBERS;
-    let mut client = Client::builder(&token, intents).event_handler(handler).await.expect("Error creating client");
+    // Optional web dashboard for hosts
+    if let Some(dashboard_config) = config.dashboard.clone() {
+        let state = DashboardState { stores: handler.stores.clone(), hosts: Arc::new(dashboard_config.hosts) };
+        tokio::spawn(async move {
+            if let Err(why) = dashboard::serve(dashboard_config.addr, state).await {
+                error!(error = ?why, "Dashboard server failed");
+            }
+        });
+    }
+
+    // Optional Prometheus scrape endpoint
+    if let Some(metrics_config) = config.metrics.clone() {
+        tokio::spawn(async move {
+            if let Err(why) = metrics::serve(metrics_config.addr).await {
+                error!(error = ?why, "Metrics server failed");
+            }
+        });
+    }
+
+    if let Some(health_config) = config.health.clone() {
+        let health = health.clone();
+        tokio::spawn(async move {
+            if let Err(why) = health::serve(health_config.addr, health).await {
+                error!(error = ?why, "Health server failed");
+            }
+        });
+    }
+
+    let mut client = Client::builder(&config.discord.token, config.discord.gateway_intents()).event_handler(handler).await.expect("Error creating client");
 
+    // On SIGTERM or Ctrl-C, stop taking turns, let running replies finish, then disconnect
+    let shard_manager = client.shard_manager.clone();
+    let grace = Duration::from_secs(config.limits.shutdown_grace_secs);
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        info!("Shutting down, no longer taking turns");
+        health.start_draining();
+        if tokio::time::timeout(grace, health.wait_idle()).await.is_err() {
+            warn!(grace_secs = grace.as_secs(), "Gave up waiting for replies in flight");
+        }
+        health.set_gateway_connected(false);
+        shard_manager.shutdown_all().await;
+    });
+
+    info!("Starting Discord client");
     if let Err(why) = client.start().await {
-        println!("Client error: {:?}", why);
+        error!(error = ?why, "Discord client failed");
     }
 
-    println!("Bot is running!");
+    if let Some(pool) = pool {
+        pool.close().await;
+    }
+    info!("Toodles has left the circus");
+}
+
+async fn shutdown_signal() {
+    let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+    tokio::select! {
+        _ = terminate.recv() => {},
+        _ = tokio::signal::ctrl_c() => {},
+    }
 }
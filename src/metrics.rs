@@ -0,0 +1,136 @@
+use std::future::Future;
+use std::sync::LazyLock;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use axum::http::header::CONTENT_TYPE;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use prometheus::{Encoder, Gauge, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder};
+use tokio::net::{TcpListener, ToSocketAddrs};
+
+/// Everything Toodles reports to Prometheus, registered once for the whole process.
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+pub struct Metrics {
+    registry: Registry,
+    /// Turns Toodles took, by outcome ("replied" or "error")
+    pub turns: IntCounterVec,
+    /// Classified messages, by sentiment
+    pub sentiments: IntCounterVec,
+    /// Compliments left out of the counters as suspected farming, by flag
+    pub farming_flags: IntCounterVec,
+    /// Chat completion latency, by model
+    pub llm_latency: HistogramVec,
+    /// Failed chat completions, by model
+    pub llm_errors: IntCounterVec,
+    /// Tokens spent, by model and kind ("prompt" or "completion")
+    pub llm_tokens: IntCounterVec,
+    /// Store call latency on the turn path, by operation
+    pub store_latency: HistogramVec,
+    /// Failed Discord API calls, by operation
+    pub discord_errors: IntCounterVec,
+    /// Unix time of the last turn Toodles answered, for noticing when Toodles goes quiet
+    pub last_turn: Gauge,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        let metrics = Metrics {
+            turns: IntCounterVec::new(Opts::new("toodles_turns_total", "Messages Toodles handled, by outcome"), &["outcome"]).unwrap(),
+            sentiments: IntCounterVec::new(Opts::new("toodles_sentiments_total", "Classified messages, by sentiment"), &["sentiment"]).unwrap(),
+            farming_flags: IntCounterVec::new(Opts::new("toodles_farming_flags_total", "Compliments not counted as suspected farming, by flag"), &["flag"]).unwrap(),
+            llm_latency: HistogramVec::new(
+                HistogramOpts::new("toodles_llm_request_duration_seconds", "Chat completion latency, by model")
+                    .buckets(vec![0.1, 0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 15.0, 30.0]),
+                &["model"],
+            ).unwrap(),
+            llm_errors: IntCounterVec::new(Opts::new("toodles_llm_errors_total", "Failed chat completions, by model"), &["model"]).unwrap(),
+            llm_tokens: IntCounterVec::new(Opts::new("toodles_llm_tokens_total", "Tokens spent, by model and kind"), &["model", "kind"]).unwrap(),
+            store_latency: HistogramVec::new(
+                HistogramOpts::new("toodles_store_query_duration_seconds", "Store call latency, by operation")
+                    .buckets(vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0]),
+                &["operation"],
+            ).unwrap(),
+            discord_errors: IntCounterVec::new(Opts::new("toodles_discord_api_errors_total", "Failed Discord API calls, by operation"), &["operation"]).unwrap(),
+            last_turn: Gauge::new("toodles_last_turn_timestamp_seconds", "Unix time of the last turn Toodles answered").unwrap(),
+            registry,
+        };
+        metrics.registry.register(Box::new(metrics.turns.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.sentiments.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.farming_flags.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.llm_latency.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.llm_errors.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.llm_tokens.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.store_latency.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.discord_errors.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.last_turn.clone())).unwrap();
+        metrics
+    }
+
+    pub fn record_turn(&self, outcome: &str) {
+        self.turns.with_label_values(&[outcome]).inc();
+        if outcome == "replied" {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+            self.last_turn.set(now.as_secs_f64());
+        }
+    }
+
+    pub fn discord_error(&self, operation: &str) {
+        self.discord_errors.with_label_values(&[operation]).inc();
+    }
+
+    /// The Prometheus text exposition of every metric.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer).expect("Failed to encode metrics");
+        String::from_utf8(buffer).expect("Metrics should be UTF-8")
+    }
+}
+
+/// Awaits a store call and records how long it took under `operation`.
+pub async fn time_store<T>(operation: &str, call: impl Future<Output = T>) -> T {
+    let started = Instant::now();
+    let result = call.await;
+    METRICS.store_latency.with_label_values(&[operation]).observe(started.elapsed().as_secs_f64());
+    result
+}
+
+async fn metrics() -> impl IntoResponse {
+    ([(CONTENT_TYPE, TextEncoder::new().format_type().to_string())], METRICS.render())
+}
+
+/// `GET /metrics` for Prometheus to scrape.
+pub fn router() -> Router {
+    Router::new().route("/metrics", get(metrics))
+}
+
+pub async fn serve(addr: impl ToSocketAddrs) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    axum::serve(listener, router()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        METRICS.record_turn("replied");
+        METRICS.discord_error("reply");
+        assert_eq!(time_store("test_operation", async { 7 }).await, 7);
+
+        let response = router().oneshot(Request::get("/metrics").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("toodles_turns_total{outcome=\"replied\"}"));
+        assert!(body.contains("toodles_discord_api_errors_total{operation=\"reply\"}"));
+        assert!(body.contains("toodles_store_query_duration_seconds_count{operation=\"test_operation\"} 1"));
+        assert!(body.contains("toodles_last_turn_timestamp_seconds"));
+    }
+}
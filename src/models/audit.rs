@@ -0,0 +1,74 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// A player erased everything Toodles stored about them
+    ForgetPlayer,
+    /// A host overwrote one of a player's interaction counters
+    SetCounter,
+    /// A host moved one of a player's interaction counters up or down
+    AdjustCounter,
+    /// A host reset all of a player's interaction counters
+    ResetPlayer,
+    /// A host corrected the classified sentiment of a player's message
+    OverrideSentiment,
+    /// A host recorded whether Toodles gave a player his immunity idol
+    SetIdolGiven,
+    /// An admin changed a guild's settings. The target is the guild rather than a player.
+    ChangeSetting,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::ForgetPlayer => "forget_player",
+            AuditAction::SetCounter => "set_counter",
+            AuditAction::AdjustCounter => "adjust_counter",
+            AuditAction::ResetPlayer => "reset_player",
+            AuditAction::OverrideSentiment => "override_sentiment",
+            AuditAction::SetIdolGiven => "set_idol_given",
+            AuditAction::ChangeSetting => "change_setting",
+        }
+    }
+}
+
+impl From<&str> for AuditAction {
+    fn from(value: &str) -> Self {
+        match value {
+            "forget_player" => AuditAction::ForgetPlayer,
+            "set_counter" => AuditAction::SetCounter,
+            "adjust_counter" => AuditAction::AdjustCounter,
+            "reset_player" => AuditAction::ResetPlayer,
+            "override_sentiment" => AuditAction::OverrideSentiment,
+            "set_idol_given" => AuditAction::SetIdolGiven,
+            "change_setting" => AuditAction::ChangeSetting,
+            _ => panic!("Invalid audit action value"),
+        }
+    }
+}
+
+/// A record of who changed or removed a player's data, and what they did.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// User who performed the action
+    pub actor_id: String,
+    pub action: AuditAction,
+    /// Player whose data was affected, or the guild for setting changes
+    pub target_user_id: String,
+    pub details: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditEntry {
+    pub fn new(actor_id: &str, action: AuditAction, target_user_id: &str, details: String) -> Self {
+        AuditEntry {
+            actor_id: actor_id.to_string(),
+            action,
+            target_user_id: target_user_id.to_string(),
+            details,
+            created_at: Utc::now(),
+        }
+    }
+}
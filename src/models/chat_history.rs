@@ -1,18 +1,52 @@
 use std::fmt;
 
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use async_openai::types::{ChatCompletionRequestAssistantMessage, ChatCompletionRequestMessage, ChatCompletionRequestSystemMessage, ChatCompletionRequestUserMessage};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+use crate::models::Sentiment;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ChatRole {
     System,
     User,
     Assistant
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: ChatRole,
     pub content: String,
+    /// Platform ID of the message this was sent as. For assistant messages this is Toodles's reply.
+    pub message_id: Option<String>,
+    pub channel_id: Option<String>,
+    /// `None` for direct messages
+    pub guild_id: Option<String>,
+    /// Classified sentiment of a user message
+    pub sentiment: Option<Sentiment>,
+    /// Model that generated an assistant message
+    pub model: Option<String>,
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl ChatMessage {
+    pub fn new(role: ChatRole, content: String) -> Self {
+        ChatMessage {
+            role,
+            content,
+            message_id: None,
+            channel_id: None,
+            guild_id: None,
+            sentiment: None,
+            model: None,
+            prompt_tokens: None,
+            completion_tokens: None,
+            created_at: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -20,9 +54,9 @@ pub struct ChatHistory {
     pub messages: Vec<ChatMessage>,
 }
 
-impl Into<Vec<ChatCompletionRequestMessage>> for ChatHistory {
-    fn into(self) -> Vec<ChatCompletionRequestMessage> {
-        self.messages.into_iter().map(|msg| match msg.role {
+impl From<ChatHistory> for Vec<ChatCompletionRequestMessage> {
+    fn from(history: ChatHistory) -> Self {
+        history.messages.into_iter().map(|msg| match msg.role {
             ChatRole::System => ChatCompletionRequestMessage::System(
                 ChatCompletionRequestSystemMessage {
                     content: async_openai::types::ChatCompletionRequestSystemMessageContent::Text(msg.content),
@@ -48,11 +82,11 @@ impl Into<Vec<ChatCompletionRequestMessage>> for ChatHistory {
 impl ChatHistory {
 
     pub fn set_system_message(&mut self, content: String) {
-        self.messages.insert(0, ChatMessage { role: ChatRole::System, content: content });
+        self.messages.insert(0, ChatMessage::new(ChatRole::System, content));
     }
 
     pub fn add_message(&mut self, role: ChatRole, content: String) {
-        self.messages.push(ChatMessage { role, content });
+        self.messages.push(ChatMessage::new(role, content));
     }
 
     pub fn add_user_message(&mut self, content: String) {
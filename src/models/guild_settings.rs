@@ -0,0 +1,296 @@
+use std::fmt;
+
+use chrono::{DateTime, NaiveTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Parts of Toodles a guild can switch off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    /// Answering players' messages
+    Chat,
+    /// Greeting new members
+    Welcome,
+    /// Hosts correcting sentiments by reacting to messages
+    Reactions,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 3] = [Feature::Chat, Feature::Welcome, Feature::Reactions];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Feature::Chat => "chat",
+            Feature::Welcome => "welcome",
+            Feature::Reactions => "reactions",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Feature> {
+        Feature::ALL.into_iter().find(|feature| feature.as_str().eq_ignore_ascii_case(value))
+    }
+}
+
+impl From<&str> for Feature {
+    fn from(value: &str) -> Self {
+        Feature::parse(value).expect("Invalid feature value")
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// What Toodles does when called during quiet hours.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuietMode {
+    /// Pretend not to hear
+    Ignore,
+    /// Answer with a short in-character brush-off, without a turn
+    #[default]
+    BrushOff,
+}
+
+impl QuietMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuietMode::Ignore => "ignore",
+            QuietMode::BrushOff => "brush_off",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<QuietMode> {
+        match value.to_lowercase().replace('-', "_").as_str() {
+            "ignore" => Some(QuietMode::Ignore),
+            "brush_off" | "brushoff" => Some(QuietMode::BrushOff),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for QuietMode {
+    fn from(value: &str) -> Self {
+        QuietMode::parse(value).expect("Invalid quiet mode value")
+    }
+}
+
+impl fmt::Display for QuietMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A daily window when Toodles doesn't chat, in the guild's local time. It may run past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    /// The guild's offset from UTC
+    pub utc_offset_minutes: i32,
+}
+
+impl QuietHours {
+    pub fn contains(&self, now: DateTime<Utc>) -> bool {
+        let local = (now + TimeDelta::minutes(self.utc_offset_minutes.into())).time();
+        if self.start <= self.end {
+            self.start <= local && local < self.end
+        } else {
+            self.start <= local || local < self.end
+        }
+    }
+}
+
+impl fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let offset = self.utc_offset_minutes.abs();
+        write!(
+            f,
+            "{}-{} UTC{}{:02}:{:02}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M"),
+            if self.utc_offset_minutes < 0 { '-' } else { '+' },
+            offset / 60,
+            offset % 60,
+        )
+    }
+}
+
+/// How Toodles behaves in one guild. Unset values fall back to the bot-wide configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuildSettings {
+    pub guild_id: String,
+    pub prefix: Option<String>,
+    /// Channels Toodles chats in; empty means every channel. Commands work everywhere.
+    pub enabled_channel_ids: Vec<String>,
+    /// Channels Toodles never chats in, even if they are also enabled
+    pub denied_channel_ids: Vec<String>,
+    pub quiet_hours: Option<QuietHours>,
+    pub quiet_mode: QuietMode,
+    /// Extra instructions added to Toodles's system prompt in this guild
+    pub persona: Option<String>,
+    pub welcome_channel_id: Option<String>,
+    /// Members with this role may use host commands, on top of the configured host roles
+    pub host_role_id: Option<String>,
+    /// Longest reply Toodles may give here, in tokens
+    pub max_reply_tokens: Option<u16>,
+    pub disabled_features: Vec<Feature>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// One edit to a guild's settings, as made by a host command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SettingChange {
+    Prefix(Option<String>),
+    EnableChannel(String),
+    DisableChannel(String),
+    DenyChannel(String),
+    UndenyChannel(String),
+    /// Let Toodles chat in every channel again
+    ClearChannels,
+    QuietHours(Option<QuietHours>),
+    QuietMode(QuietMode),
+    Persona(Option<String>),
+    WelcomeChannel(Option<String>),
+    HostRole(Option<String>),
+    MaxReplyTokens(Option<u16>),
+    Feature(Feature, bool),
+}
+
+impl GuildSettings {
+    pub fn new(guild_id: &str) -> Self {
+        GuildSettings {
+            guild_id: guild_id.to_string(),
+            prefix: None,
+            enabled_channel_ids: Vec::new(),
+            denied_channel_ids: Vec::new(),
+            quiet_hours: None,
+            quiet_mode: QuietMode::default(),
+            persona: None,
+            welcome_channel_id: None,
+            host_role_id: None,
+            max_reply_tokens: None,
+            disabled_features: Vec::new(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    pub fn apply(&mut self, change: SettingChange) {
+        match change {
+            SettingChange::Prefix(prefix) => self.prefix = prefix,
+            SettingChange::EnableChannel(channel_id) => {
+                if !self.enabled_channel_ids.contains(&channel_id) {
+                    self.enabled_channel_ids.push(channel_id);
+                }
+            },
+            SettingChange::DisableChannel(channel_id) => self.enabled_channel_ids.retain(|enabled| *enabled != channel_id),
+            SettingChange::DenyChannel(channel_id) => {
+                if !self.denied_channel_ids.contains(&channel_id) {
+                    self.denied_channel_ids.push(channel_id);
+                }
+            },
+            SettingChange::UndenyChannel(channel_id) => self.denied_channel_ids.retain(|denied| *denied != channel_id),
+            SettingChange::ClearChannels => {
+                self.enabled_channel_ids.clear();
+                self.denied_channel_ids.clear();
+            },
+            SettingChange::QuietHours(quiet_hours) => self.quiet_hours = quiet_hours,
+            SettingChange::QuietMode(quiet_mode) => self.quiet_mode = quiet_mode,
+            SettingChange::Persona(persona) => self.persona = persona,
+            SettingChange::WelcomeChannel(channel_id) => self.welcome_channel_id = channel_id,
+            SettingChange::HostRole(role_id) => self.host_role_id = role_id,
+            SettingChange::MaxReplyTokens(max_tokens) => self.max_reply_tokens = max_tokens,
+            SettingChange::Feature(feature, enabled) => {
+                self.disabled_features.retain(|disabled| *disabled != feature);
+                if !enabled {
+                    self.disabled_features.push(feature);
+                }
+            },
+        }
+        self.updated_at = Utc::now();
+    }
+
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        !self.disabled_features.contains(&feature)
+    }
+
+    /// Whether Toodles chats in the channel. Commands are answered in every channel regardless.
+    pub fn chats_in(&self, channel_id: &str) -> bool {
+        self.is_enabled(Feature::Chat)
+            && !self.denied_channel_ids.iter().any(|denied| denied == channel_id)
+            && (self.enabled_channel_ids.is_empty() || self.enabled_channel_ids.iter().any(|enabled| enabled == channel_id))
+    }
+
+    pub fn is_quiet(&self, now: DateTime<Utc>) -> bool {
+        self.quiet_hours.is_some_and(|quiet_hours| quiet_hours.contains(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_setting_changes() {
+        let mut settings = GuildSettings::new("1");
+        assert!(settings.chats_in("10"), "Expected Toodles to chat everywhere by default");
+
+        settings.apply(SettingChange::EnableChannel("10".to_string()));
+        settings.apply(SettingChange::EnableChannel("10".to_string()));
+        assert_eq!(settings.enabled_channel_ids, vec!["10".to_string()]);
+        assert!(settings.chats_in("10"));
+        assert!(!settings.chats_in("11"));
+
+        settings.apply(SettingChange::Feature(Feature::Chat, false));
+        settings.apply(SettingChange::Feature(Feature::Chat, false));
+        assert_eq!(settings.disabled_features, vec![Feature::Chat]);
+        assert!(!settings.chats_in("10"));
+        settings.apply(SettingChange::Feature(Feature::Chat, true));
+        assert!(settings.is_enabled(Feature::Chat));
+
+        settings.apply(SettingChange::DisableChannel("10".to_string()));
+        assert!(settings.chats_in("11"));
+
+        settings.apply(SettingChange::Prefix(Some("!clown".to_string())));
+        settings.apply(SettingChange::MaxReplyTokens(Some(80)));
+        assert_eq!(settings.prefix.as_deref(), Some("!clown"));
+        assert_eq!(settings.max_reply_tokens, Some(80));
+
+        settings.apply(SettingChange::DenyChannel("12".to_string()));
+        assert!(!settings.chats_in("12"), "Expected a denied channel to be skipped");
+        settings.apply(SettingChange::EnableChannel("12".to_string()));
+        assert!(!settings.chats_in("12"), "Expected the deny list to win over the allow list");
+        settings.apply(SettingChange::ClearChannels);
+        assert!(settings.chats_in("12"));
+
+        assert_eq!(Feature::parse("Reactions"), Some(Feature::Reactions));
+        assert_eq!(Feature::parse("juggling"), None);
+    }
+
+    #[test]
+    fn test_quiet_hours() {
+        let at = |time: &str| DateTime::parse_from_rfc3339(&format!("2025-07-26T{}:00Z", time)).unwrap().with_timezone(&Utc);
+        let overnight = QuietHours {
+            start: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            utc_offset_minutes: 0,
+        };
+        assert!(overnight.contains(at("23:30")));
+        assert!(overnight.contains(at("07:59")));
+        assert!(!overnight.contains(at("08:00")));
+        assert!(!overnight.contains(at("12:00")));
+
+        // 22:00-08:00 at UTC-5 is 03:00-13:00 UTC
+        let eastern = QuietHours { utc_offset_minutes: -300, ..overnight };
+        assert!(eastern.contains(at("12:00")));
+        assert!(!eastern.contains(at("23:30")));
+        assert_eq!(eastern.to_string(), "22:00-08:00 UTC-05:00");
+
+        let mut settings = GuildSettings::new("1");
+        assert!(!settings.is_quiet(at("23:30")));
+        settings.apply(SettingChange::QuietHours(Some(overnight)));
+        assert!(settings.is_quiet(at("23:30")));
+    }
+}
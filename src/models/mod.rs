@@ -1,5 +1,17 @@
+mod audit;
 mod chat_history;
+mod guild_settings;
+mod retention;
+mod sentiment_event;
+mod sentiment_override;
+mod usage;
 mod user_interaction;
 
+pub use audit::*;
 pub use chat_history::*;
+pub use guild_settings::*;
+pub use retention::*;
+pub use sentiment_event::*;
+pub use sentiment_override::*;
+pub use usage::*;
 pub use user_interaction::*;
\ No newline at end of file
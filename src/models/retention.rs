@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::ChatMessage;
+
+/// How much chat history to keep. Limits that are `None` are not enforced.
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    /// Messages older than this are pruned
+    pub max_age: Option<Duration>,
+    /// Only the newest this many messages are kept for each user
+    pub max_messages_per_user: Option<usize>,
+}
+
+impl RetentionPolicy {
+    pub fn is_unbounded(&self) -> bool {
+        self.max_age.is_none() && self.max_messages_per_user.is_none()
+    }
+
+    /// Messages created before the returned time are past `max_age`. An age reaching back
+    /// further than time can be counted has no cutoff, since nothing is that old.
+    pub fn cutoff(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.max_age
+            .and_then(|max_age| chrono::Duration::from_std(max_age).ok())
+            .and_then(|max_age| now.checked_sub_signed(max_age))
+    }
+}
+
+/// A chat message removed by retention, kept together with its owner so it can be archived.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrunedMessage {
+    pub user_id: String,
+    pub message: ChatMessage,
+}
@@ -0,0 +1,88 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::Sentiment;
+
+/// A single classification of a player's message toward Toodles.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SentimentEvent {
+    pub user_id: String,
+    /// Platform ID of the classified message
+    pub message_id: Option<String>,
+    pub sentiment: Sentiment,
+    /// Probability the classifier gave its answer, when the backend reports it
+    pub confidence: Option<f32>,
+    /// Set when the classification was left out of the player's counters as suspected farming
+    pub farming: Option<FarmingFlag>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl SentimentEvent {
+    pub fn new(user_id: &str, message_id: Option<String>, sentiment: Sentiment, confidence: Option<f32>) -> Self {
+        SentimentEvent {
+            user_id: user_id.to_string(),
+            message_id,
+            sentiment,
+            confidence,
+            farming: None,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// Why a compliment looked like a player grinding for the idol rather than talking to Toodles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FarmingFlag {
+    /// Nearly the same as one of the player's recent messages
+    Duplicate,
+    /// One compliment too many in a short time
+    Diminished,
+}
+
+impl FarmingFlag {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FarmingFlag::Duplicate => "duplicate",
+            FarmingFlag::Diminished => "diminished",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<FarmingFlag> {
+        match value.to_lowercase().as_str() {
+            "duplicate" => Some(FarmingFlag::Duplicate),
+            "diminished" => Some(FarmingFlag::Diminished),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for FarmingFlag {
+    fn from(value: &str) -> Self {
+        FarmingFlag::parse(value).expect("Invalid farming flag value")
+    }
+}
+
+impl fmt::Display for FarmingFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// How far a player's standing moved within a time window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentimentSwing {
+    pub user_id: String,
+    pub num_positive: usize,
+    pub num_negative: usize,
+    pub num_neutral: usize,
+}
+
+impl SentimentSwing {
+    /// Positive minus negative classifications. The sign says which way the player moved.
+    pub fn net(&self) -> i64 {
+        self.num_positive as i64 - self.num_negative as i64
+    }
+}
@@ -0,0 +1,20 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::Sentiment;
+
+/// A host's correction of a classified message. Doubles as a labeled example for classifier evaluation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SentimentOverride {
+    pub message_id: String,
+    /// Player who sent the message
+    pub user_id: String,
+    /// Message text, if it was still in the chat history when overridden
+    pub content: Option<String>,
+    /// What the classifier originally said, kept across repeated overrides
+    pub classified_sentiment: Sentiment,
+    /// The host's label
+    pub corrected_sentiment: Sentiment,
+    pub overridden_by: String,
+    pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,163 @@
+use std::fmt;
+
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Why Toodles called a model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageKind {
+    Reply,
+    Classification,
+}
+
+impl UsageKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UsageKind::Reply => "reply",
+            UsageKind::Classification => "classification",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<UsageKind> {
+        match value.to_lowercase().as_str() {
+            "reply" => Some(UsageKind::Reply),
+            "classification" => Some(UsageKind::Classification),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for UsageKind {
+    fn from(value: &str) -> Self {
+        UsageKind::parse(value).expect("Invalid usage kind value")
+    }
+}
+
+impl fmt::Display for UsageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Tokens one model call spent, as reported by the API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub model: String,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+}
+
+/// One model call made on a player's behalf.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub user_id: String,
+    /// `None` for direct messages and other platforms
+    pub guild_id: Option<String>,
+    pub kind: UsageKind,
+    pub model: String,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub created_at: DateTime<Utc>,
+}
+
+impl UsageRecord {
+    pub fn new(user_id: &str, guild_id: Option<String>, kind: UsageKind, usage: TokenUsage) -> Self {
+        UsageRecord {
+            user_id: user_id.to_string(),
+            guild_id,
+            kind,
+            model: usage.model,
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// Whose spending to look at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UsageScope {
+    /// A player, wherever they talked to Toodles
+    User(String),
+    Guild(String),
+}
+
+/// What one player spent on one model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageSummary {
+    pub user_id: String,
+    pub model: String,
+    pub calls: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl UsageSummary {
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+/// A calendar window budgets are counted over, in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetPeriod {
+    Day,
+    Month,
+}
+
+impl BudgetPeriod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BudgetPeriod::Day => "daily",
+            BudgetPeriod::Month => "monthly",
+        }
+    }
+
+    /// When the period containing `now` began.
+    pub fn start(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let day = match self {
+            BudgetPeriod::Day => now.day(),
+            BudgetPeriod::Month => 1,
+        };
+        Utc.with_ymd_and_hms(now.year(), now.month(), day, 0, 0, 0).single().expect("Midnight UTC always exists")
+    }
+}
+
+/// Token limits on everything Toodles spends for a player or server, classifying their
+/// messages included. Once one is spent Toodles replies with `fallback_model`, or with
+/// canned lines and no model at all if there is none.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Budget {
+    pub user_daily_tokens: Option<u64>,
+    pub user_monthly_tokens: Option<u64>,
+    pub guild_daily_tokens: Option<u64>,
+    pub guild_monthly_tokens: Option<u64>,
+    pub fallback_model: Option<String>,
+}
+
+impl Budget {
+    /// The limits that apply to `scope`, by period.
+    pub fn limits(&self, scope: &UsageScope) -> [(BudgetPeriod, Option<u64>); 2] {
+        match scope {
+            UsageScope::User(_) => [(BudgetPeriod::Day, self.user_daily_tokens), (BudgetPeriod::Month, self.user_monthly_tokens)],
+            UsageScope::Guild(_) => [(BudgetPeriod::Day, self.guild_daily_tokens), (BudgetPeriod::Month, self.guild_monthly_tokens)],
+        }
+    }
+
+    pub fn is_unbounded(&self) -> bool {
+        [self.user_daily_tokens, self.user_monthly_tokens, self.guild_daily_tokens, self.guild_monthly_tokens].iter().all(Option::is_none)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_period_start() {
+        let now = DateTime::parse_from_rfc3339("2025-07-30T18:45:12Z").unwrap().with_timezone(&Utc);
+        assert_eq!(BudgetPeriod::Day.start(now).to_rfc3339(), "2025-07-30T00:00:00+00:00");
+        assert_eq!(BudgetPeriod::Month.start(now).to_rfc3339(), "2025-07-01T00:00:00+00:00");
+    }
+}
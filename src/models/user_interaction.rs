@@ -1,5 +1,9 @@
+use std::fmt;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Sentiment {
     Positive,
     Negative,
@@ -8,23 +12,112 @@ pub enum Sentiment {
 
 impl From<&str> for Sentiment {
     fn from(value: &str) -> Self {
+        Sentiment::parse(value).expect("Invalid sentiment value")
+    }
+}
+
+impl Sentiment {
+    /// Like `From<&str>`, but returns `None` for unknown values instead of panicking.
+    pub fn parse(value: &str) -> Option<Sentiment> {
         match value.to_lowercase().as_str() {
-            "positive" => Sentiment::Positive,
-            "negative" => Sentiment::Negative,
-            "neutral" => Sentiment::Neutral,
-            _ => panic!("Invalid sentiment value"),
+            "positive" => Some(Sentiment::Positive),
+            "negative" => Some(Sentiment::Negative),
+            "neutral" => Some(Sentiment::Neutral),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Sentiment::Positive => "positive",
+            Sentiment::Negative => "negative",
+            Sentiment::Neutral => "neutral",
         }
     }
 }
 
-#[derive(Debug, Clone, Default)]
+impl fmt::Display for Sentiment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct UserInteraction {
     pub num_positive: usize,
     pub num_negative: usize,
     pub num_neutral: usize,
+    /// Whether a host has recorded that Toodles gave this player his immunity idol
+    #[serde(default)]
+    pub idol_given: bool,
+}
+
+/// How Toodles feels about a player, which decides the persona he answers them with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tier {
+    Favored,
+    Neutral,
+    Despised,
+}
+
+impl Tier {
+    /// A player needs a lead of more than two over both other kinds of interaction to leave neutral.
+    pub fn from_counts(num_positive: usize, num_negative: usize, num_neutral: usize) -> Tier {
+        if num_positive > num_negative + 2 && num_positive > num_neutral + 2 {
+            Tier::Favored
+        } else if num_negative > num_positive + 2 && num_negative > num_neutral + 2 {
+            Tier::Despised
+        } else {
+            Tier::Neutral
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Tier::Favored => "favored",
+            Tier::Neutral => "neutral",
+            Tier::Despised => "despised",
+        }
+    }
+}
+
+impl fmt::Display for Tier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 impl UserInteraction {
+    /// The largest a counter can be, since the database keeps them as 32-bit integers
+    pub const MAX_COUNT: usize = i32::MAX as usize;
+
+    pub fn tier(&self) -> Tier {
+        Tier::from_counts(self.num_positive, self.num_negative, self.num_neutral)
+    }
+
+    pub fn count(&self, sentiment: &Sentiment) -> usize {
+        match sentiment {
+            Sentiment::Positive => self.num_positive,
+            Sentiment::Negative => self.num_negative,
+            Sentiment::Neutral => self.num_neutral,
+        }
+    }
+
+    pub fn set_count(&mut self, sentiment: &Sentiment, value: usize) {
+        match sentiment {
+            Sentiment::Positive => self.num_positive = value,
+            Sentiment::Negative => self.num_negative = value,
+            Sentiment::Neutral => self.num_neutral = value,
+        }
+    }
+
+    /// Moves a counter by `delta`, stopping at zero and at [`UserInteraction::MAX_COUNT`].
+    pub fn adjust_count(&mut self, sentiment: &Sentiment, delta: i64) {
+        let value = (self.count(sentiment) as i64).saturating_add(delta).clamp(0, Self::MAX_COUNT as i64) as usize;
+        self.set_count(sentiment, value);
+    }
+
     pub fn increment_positive(&mut self) {
         self.num_positive += 1;
     }
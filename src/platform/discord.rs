@@ -0,0 +1,74 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serenity::all::{ChannelId, CreateAllowedMentions, CreateMessage, EditMessage, Http, Message, MessageId, ReactionType};
+
+use super::{ChatPlatform, IncomingMessage, SentMessage};
+use crate::metrics::METRICS;
+
+impl From<&Message> for IncomingMessage {
+    fn from(message: &Message) -> Self {
+        IncomingMessage {
+            id: message.id.to_string(),
+            channel_id: message.channel_id.to_string(),
+            guild_id: message.guild_id.map(|id| id.to_string()),
+            author_id: message.author.id.to_string(),
+            author_name: message.author.name.clone(),
+            content: message.content.clone(),
+        }
+    }
+}
+
+/// Talks to Discord over serenity's HTTP client.
+#[derive(Clone)]
+pub struct DiscordPlatform {
+    http: Arc<Http>,
+}
+
+impl DiscordPlatform {
+    pub fn new(http: Arc<Http>) -> Self {
+        DiscordPlatform { http }
+    }
+}
+
+fn channel_id(id: &str) -> Result<ChannelId, Box<dyn Error + Send + Sync>> {
+    Ok(ChannelId::new(id.parse()?))
+}
+
+fn message_id(id: &str) -> Result<MessageId, Box<dyn Error + Send + Sync>> {
+    Ok(MessageId::new(id.parse()?))
+}
+
+#[async_trait]
+impl ChatPlatform for DiscordPlatform {
+    async fn reply(&self, message: &IncomingMessage, content: &str) -> Result<SentMessage, Box<dyn Error + Send + Sync>> {
+        let channel_id = channel_id(&message.channel_id)?;
+        // An inline reply that doesn't ping the author, like `Message::reply`
+        let builder = CreateMessage::new()
+            .content(content)
+            .reference_message((channel_id, message_id(&message.id)?))
+            .allowed_mentions(CreateAllowedMentions::new().replied_user(false).everyone(true).all_users(true).all_roles(true));
+        let sent = channel_id.send_message(&self.http, builder).await.inspect_err(|_| METRICS.discord_error("send_message"))?;
+        Ok(SentMessage {
+            id: sent.id.to_string(),
+            channel_id: sent.channel_id.to_string(),
+        })
+    }
+
+    async fn edit(&self, message: &SentMessage, content: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        channel_id(&message.channel_id)?
+            .edit_message(&self.http, message_id(&message.id)?, EditMessage::new().content(content))
+            .await
+            .inspect_err(|_| METRICS.discord_error("edit_message"))?;
+        Ok(())
+    }
+
+    async fn react(&self, channel: &str, message: &str, emoji: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        channel_id(channel)?
+            .create_reaction(&self.http, message_id(message)?, ReactionType::Unicode(emoji.to_string()))
+            .await
+            .inspect_err(|_| METRICS.discord_error("create_reaction"))?;
+        Ok(())
+    }
+}
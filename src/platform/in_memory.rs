@@ -0,0 +1,113 @@
+use std::error::Error;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use super::{ChatPlatform, IncomingMessage, SentMessage};
+
+/// A message Toodles sent through [`InMemoryPlatform`], with its latest content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedMessage {
+    pub id: String,
+    pub channel_id: String,
+    /// The message this one replies to
+    pub reply_to: Option<String>,
+    pub content: String,
+    /// Every earlier content, oldest first
+    pub edits: Vec<String>,
+}
+
+#[derive(Default)]
+struct PlatformState {
+    next_id: u64,
+    sent: Vec<RecordedMessage>,
+    reactions: Vec<(String, String, String)>,
+}
+
+/// Keeps everything Toodles does in memory, for tests and tools that have no real chat platform.
+#[derive(Default)]
+pub struct InMemoryPlatform {
+    state: Mutex<PlatformState>,
+}
+
+impl InMemoryPlatform {
+    pub fn new() -> Self {
+        InMemoryPlatform {
+            state: Mutex::new(PlatformState::default()),
+        }
+    }
+
+    /// Messages Toodles sent, oldest first.
+    pub fn sent_messages(&self) -> Vec<RecordedMessage> {
+        self.state.lock().unwrap().sent.clone()
+    }
+
+    /// Reactions Toodles added, as `(channel_id, message_id, emoji)`.
+    pub fn reactions(&self) -> Vec<(String, String, String)> {
+        self.state.lock().unwrap().reactions.clone()
+    }
+}
+
+#[async_trait]
+impl ChatPlatform for InMemoryPlatform {
+    async fn reply(&self, message: &IncomingMessage, content: &str) -> Result<SentMessage, Box<dyn Error + Send + Sync>> {
+        let mut state = self.state.lock().unwrap();
+        state.next_id += 1;
+        let sent = RecordedMessage {
+            id: format!("sent-{}", state.next_id),
+            channel_id: message.channel_id.clone(),
+            reply_to: Some(message.id.clone()),
+            content: content.to_string(),
+            edits: Vec::new(),
+        };
+        state.sent.push(sent.clone());
+        Ok(SentMessage { id: sent.id, channel_id: sent.channel_id })
+    }
+
+    async fn edit(&self, message: &SentMessage, content: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut state = self.state.lock().unwrap();
+        let sent = state
+            .sent
+            .iter_mut()
+            .find(|sent| sent.id == message.id && sent.channel_id == message.channel_id)
+            .ok_or_else(|| format!("Unknown message {}", message.id))?;
+        let previous = std::mem::replace(&mut sent.content, content.to_string());
+        sent.edits.push(previous);
+        Ok(())
+    }
+
+    async fn react(&self, channel_id: &str, message_id: &str, emoji: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut state = self.state.lock().unwrap();
+        state.reactions.push((channel_id.to_string(), message_id.to_string(), emoji.to_string()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_in_memory_platform() {
+        let platform = InMemoryPlatform::new();
+        let incoming = IncomingMessage {
+            id: "1".to_string(),
+            channel_id: "general".to_string(),
+            ..Default::default()
+        };
+
+        let sent = platform.reply(&incoming, "Thinking...").await.unwrap();
+        platform.edit(&sent, "Honk.").await.unwrap();
+        platform.react("general", "1", "✍️").await.unwrap();
+
+        let messages = platform.sent_messages();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].reply_to.as_deref(), Some("1"));
+        assert_eq!(messages[0].content, "Honk.");
+        assert_eq!(messages[0].edits, vec!["Thinking...".to_string()]);
+        assert_eq!(platform.reactions(), vec![("general".to_string(), "1".to_string(), "✍️".to_string())]);
+
+        let unknown = SentMessage { id: "missing".to_string(), channel_id: "general".to_string() };
+        assert!(platform.edit(&unknown, "Honk?").await.is_err());
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use async_trait::async_trait;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{ChatPlatform, IncomingMessage, SentMessage};
+
+/// IRC servers cut lines at 512 bytes including the command, target and CRLF.
+const MAX_TEXT_BYTES: usize = 400;
+
+/// One line of the IRC protocol, e.g. `:nick!user@host PRIVMSG #channel :hello there`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IrcLine {
+    /// IRCv3 message tags, e.g. `account` once the `account-tag` capability is enabled
+    pub tags: HashMap<String, String>,
+    pub prefix: Option<String>,
+    pub command: String,
+    pub params: Vec<String>,
+}
+
+impl IrcLine {
+    pub fn parse(line: &str) -> Option<IrcLine> {
+        let mut rest = line.trim_end_matches(['\r', '\n']);
+        let tags = match rest.strip_prefix('@') {
+            Some(stripped) => {
+                let (tags, after) = stripped.split_once(' ')?;
+                rest = after;
+                tags.split(';')
+                    .filter(|tag| !tag.is_empty())
+                    .map(|tag| {
+                        let (key, value) = tag.split_once('=').unwrap_or((tag, ""));
+                        (key.to_string(), unescape_tag_value(value))
+                    })
+                    .collect()
+            },
+            None => HashMap::new(),
+        };
+        let prefix = match rest.strip_prefix(':') {
+            Some(stripped) => {
+                let (prefix, after) = stripped.split_once(' ')?;
+                rest = after;
+                Some(prefix.to_string())
+            },
+            None => None,
+        };
+
+        let (rest, trailing) = match rest.split_once(" :") {
+            Some((rest, trailing)) => (rest, Some(trailing)),
+            None => (rest, None),
+        };
+        let mut words = rest.split(' ').filter(|word| !word.is_empty());
+        let command = words.next()?.to_uppercase();
+        let mut params: Vec<String> = words.map(str::to_string).collect();
+        params.extend(trailing.map(str::to_string));
+
+        Some(IrcLine { tags, prefix, command, params })
+    }
+
+    /// The services account the sender is logged in to, as vouched for by the server.
+    pub fn account(&self) -> Option<&str> {
+        self.tags.get("account").map(String::as_str).filter(|account| !account.is_empty() && *account != "*")
+    }
+
+    /// The nickname part of a `nick!user@host` prefix.
+    pub fn nick(&self) -> Option<&str> {
+        self.prefix.as_deref().map(|prefix| prefix.split('!').next().unwrap_or(prefix))
+    }
+}
+
+fn unescape_tag_value(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => unescaped.push(';'),
+            Some('s') => unescaped.push(' '),
+            Some('r') => unescaped.push('\r'),
+            Some('n') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => {},
+        }
+    }
+    unescaped
+}
+
+/// Splits `content` into PRIVMSG-sized pieces, since IRC has no multi-line messages.
+/// Replies come from the model and can be steered by players, so any line break ends a piece
+/// and other control characters are dropped. Otherwise a stray `\r` could smuggle in a command.
+pub fn irc_text_lines(content: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    for line in content.split(['\r', '\n']).map(str::trim_end).filter(|line| !line.is_empty()) {
+        let mut current = String::new();
+        for c in line.chars().filter(|c| !c.is_control()) {
+            if current.len() + c.len_utf8() > MAX_TEXT_BYTES {
+                lines.push(std::mem::take(&mut current));
+            }
+            current.push(c);
+        }
+        if !current.is_empty() {
+            lines.push(current);
+        }
+    }
+    lines
+}
+
+/// Sends Toodles's messages to an IRC connection.
+/// IRC can't edit or react, so edits are sent as follow-up messages and reactions are dropped.
+pub struct IrcPlatform {
+    outgoing: UnboundedSender<String>,
+    next_id: AtomicU64,
+}
+
+impl IrcPlatform {
+    /// `outgoing` receives raw protocol lines without the trailing CRLF.
+    pub fn new(outgoing: UnboundedSender<String>) -> Self {
+        IrcPlatform {
+            outgoing,
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    fn privmsg(&self, target: &str, content: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        for line in irc_text_lines(content) {
+            self.outgoing.send(format!("PRIVMSG {} :{}", target, line))?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ChatPlatform for IrcPlatform {
+    async fn reply(&self, message: &IncomingMessage, content: &str) -> Result<SentMessage, Box<dyn Error + Send + Sync>> {
+        self.privmsg(&message.channel_id, content)?;
+        Ok(SentMessage {
+            // IRC messages have no IDs, so these are only unique per connection
+            id: format!("irc-{}", self.next_id.fetch_add(1, Ordering::SeqCst) + 1),
+            channel_id: message.channel_id.clone(),
+        })
+    }
+
+    async fn edit(&self, message: &SentMessage, content: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.privmsg(&message.channel_id, content)
+    }
+
+    async fn react(&self, _channel_id: &str, _message_id: &str, _emoji: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_irc_line() {
+        let line = IrcLine::parse(":alice!a@circus.example PRIVMSG #circus :!toodles hi there\r\n").unwrap();
+        assert_eq!(line.nick(), Some("alice"));
+        assert_eq!(line.command, "PRIVMSG");
+        assert_eq!(line.params, vec!["#circus".to_string(), "!toodles hi there".to_string()]);
+
+        let ping = IrcLine::parse("PING :irc.example").unwrap();
+        assert_eq!(ping.prefix, None);
+        assert_eq!(ping.params, vec!["irc.example".to_string()]);
+
+        assert_eq!(IrcLine::parse(""), None);
+
+        let tagged = IrcLine::parse("@account=alice;msgid=a\\sb :alice!a@host PRIVMSG #circus :hi").unwrap();
+        assert_eq!(tagged.account(), Some("alice"));
+        assert_eq!(tagged.tags.get("msgid").map(String::as_str), Some("a b"));
+        assert_eq!(tagged.params, vec!["#circus".to_string(), "hi".to_string()]);
+        assert_eq!(line.account(), None);
+    }
+
+    #[test]
+    fn test_irc_text_lines() {
+        assert_eq!(irc_text_lines("Honk.\n\nWho goes there?"), vec!["Honk.".to_string(), "Who goes there?".to_string()]);
+
+        let long = "🤡".repeat(150);
+        let lines = irc_text_lines(&long);
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.len() <= MAX_TEXT_BYTES));
+        assert_eq!(lines.concat(), long);
+
+        assert_eq!(
+            irc_text_lines("hi\rQUIT :bye\0\u{1}"),
+            vec!["hi".to_string(), "QUIT :bye".to_string()],
+            "Expected a bare carriage return to end the line instead of starting a command",
+        );
+    }
+
+    #[test]
+    fn test_privmsg_cannot_inject_commands() {
+        let (outgoing, mut sent) = tokio::sync::mpsc::unbounded_channel();
+        IrcPlatform::new(outgoing).privmsg("#circus", "hi\rQUIT :bye").unwrap();
+        let mut lines = Vec::new();
+        while let Ok(line) = sent.try_recv() {
+            lines.push(line);
+        }
+        assert_eq!(lines, vec!["PRIVMSG #circus :hi".to_string(), "PRIVMSG #circus :QUIT :bye".to_string()]);
+        assert!(lines.iter().all(|line| !line.contains(['\r', '\n', '\0'])));
+    }
+}
@@ -0,0 +1,41 @@
+mod discord;
+mod in_memory;
+mod irc;
+
+pub use discord::*;
+pub use in_memory::*;
+pub use irc::*;
+
+use std::error::Error;
+
+use async_trait::async_trait;
+
+/// A message someone sent on a chat platform, reduced to what Toodles needs.
+#[derive(Debug, Clone, Default)]
+pub struct IncomingMessage {
+    pub id: String,
+    pub channel_id: String,
+    /// `None` for direct messages
+    pub guild_id: Option<String>,
+    pub author_id: String,
+    pub author_name: String,
+    pub content: String,
+}
+
+/// A message Toodles sent, which can be edited afterwards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentMessage {
+    pub id: String,
+    pub channel_id: String,
+}
+
+/// What Toodles can do on a chat platform.
+#[async_trait]
+pub trait ChatPlatform {
+    /// Replies to `message` in its channel.
+    async fn reply(&self, message: &IncomingMessage, content: &str) -> Result<SentMessage, Box<dyn Error + Send + Sync>>;
+    /// Replaces the content of a message Toodles sent.
+    async fn edit(&self, message: &SentMessage, content: &str) -> Result<(), Box<dyn Error + Send + Sync>>;
+    /// Adds an emoji reaction to any message in a channel.
+    async fn react(&self, channel_id: &str, message_id: &str, emoji: &str) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
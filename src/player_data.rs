@@ -0,0 +1,321 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{AuditAction, AuditEntry, ChatMessage, Sentiment, SentimentEvent, SentimentOverride, Tier, UserInteraction};
+use crate::store::Stores;
+
+/// Everything Toodles stores about a player, in the shape it is handed back to them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerDataExport {
+    pub user_id: String,
+    pub exported_at: DateTime<Utc>,
+    pub interaction: Option<UserInteraction>,
+    pub chat_history: Vec<ChatMessage>,
+    pub sentiment_events: Vec<SentimentEvent>,
+    /// Hosts' corrections of the player's messages, with the text they were made on
+    pub sentiment_overrides: Vec<SentimentOverride>,
+}
+
+/// What was removed when a player asked Toodles to forget them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForgetStats {
+    pub chat_messages_deleted: usize,
+    pub interaction_deleted: bool,
+    pub sentiment_events_deleted: usize,
+    pub overrides_deleted: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum OverrideOutcome {
+    /// No classification was recorded for the message, e.g. it wasn't addressed to Toodles
+    UnknownMessage,
+    /// The message already has the requested sentiment
+    Unchanged,
+    Overridden(SentimentOverride),
+}
+
+/// A host's view of where a player stands with Toodles.
+#[derive(Debug, Clone)]
+pub struct PlayerSummary {
+    pub user_id: String,
+    pub interaction: UserInteraction,
+    pub tier: Tier,
+    /// The player's latest messages with Toodles, oldest first
+    pub recent_history: Vec<ChatMessage>,
+    /// Compliments left out of the counters as suspected farming
+    pub suspected_farming: usize,
+}
+
+pub async fn summarize_player(stores: &Stores, user_id: &str, recent_messages: usize) -> PlayerSummary {
+    let interaction = stores.user_interaction.get_user_interaction(user_id).await;
+    let mut recent_history = stores.chat_history.get_chat_history(user_id).await.messages;
+    recent_history.drain(..recent_history.len().saturating_sub(recent_messages));
+    let suspected_farming = stores.sentiment_log.get_sentiment_timeline(user_id, None).await
+        .iter()
+        .filter(|event| event.farming.is_some())
+        .count();
+
+    PlayerSummary {
+        user_id: user_id.to_string(),
+        tier: interaction.tier(),
+        interaction,
+        recent_history,
+        suspected_farming,
+    }
+}
+
+pub async fn set_player_counter(stores: &Stores, actor_id: &str, user_id: &str, sentiment: &Sentiment, value: usize) -> UserInteraction {
+    let mut interaction = stores.user_interaction.get_user_interaction(user_id).await;
+    let previous = interaction.count(sentiment);
+    interaction.set_count(sentiment, value);
+    stores.user_interaction.set_user_interaction(user_id, interaction.clone()).await;
+
+    let details = format!("Set {} from {} to {}", sentiment, previous, value);
+    stores.audit_log.record_audit_entry(AuditEntry::new(actor_id, AuditAction::SetCounter, user_id, details)).await;
+    interaction
+}
+
+pub async fn adjust_player_counter(stores: &Stores, actor_id: &str, user_id: &str, sentiment: &Sentiment, delta: i64) -> UserInteraction {
+    let interaction = stores.user_interaction.adjust_user_interaction(user_id, sentiment, delta).await;
+
+    let details = format!("Adjusted {} by {:+} to {}", sentiment, delta, interaction.count(sentiment));
+    stores.audit_log.record_audit_entry(AuditEntry::new(actor_id, AuditAction::AdjustCounter, user_id, details)).await;
+    interaction
+}
+
+/// Zeroes the player's counters. Their chat history is kept; use `forget_player` to erase everything.
+pub async fn reset_player(stores: &Stores, actor_id: &str, user_id: &str) {
+    let previous = stores.user_interaction.get_user_interaction(user_id).await;
+    stores.user_interaction.reset_user_interaction(user_id).await;
+
+    let details = format!(
+        "Reset counters from positive {}, negative {}, neutral {}",
+        previous.num_positive, previous.num_negative, previous.num_neutral,
+    );
+    stores.audit_log.record_audit_entry(AuditEntry::new(actor_id, AuditAction::ResetPlayer, user_id, details)).await;
+}
+
+/// Records whether Toodles gave the player his immunity idol. Once given, the persona stops offering it.
+pub async fn set_idol_given(stores: &Stores, actor_id: &str, user_id: &str, idol_given: bool) {
+    stores.user_interaction.set_idol_given(user_id, idol_given).await;
+
+    let details = if idol_given { "Marked the idol as given" } else { "Marked the idol as not given" };
+    stores.audit_log.record_audit_entry(AuditEntry::new(actor_id, AuditAction::SetIdolGiven, user_id, details.to_string())).await;
+}
+
+/// Replaces the recorded sentiment of a classified message, moves the player's counters to match
+/// and keeps the correction as a labeled example. Callers should hold the player's conversation lock,
+/// since a turn running at the same time would overwrite the counters.
+pub async fn override_sentiment(stores: &Stores, host_id: &str, message_id: &str, sentiment: Sentiment) -> OverrideOutcome {
+    let Some(event) = stores.sentiment_log.find_sentiment_event(message_id).await else {
+        return OverrideOutcome::UnknownMessage;
+    };
+    if event.sentiment == sentiment {
+        return OverrideOutcome::Unchanged;
+    }
+
+    // Suspected farming never reached the counters, so there is nothing to take back. The host's
+    // word replaces the heuristic's, though, so the corrected sentiment counts either way.
+    if event.farming.is_none() {
+        stores.user_interaction.adjust_user_interaction(&event.user_id, &event.sentiment, -1).await;
+    }
+    stores.user_interaction.adjust_user_interaction(&event.user_id, &sentiment, 1).await;
+    stores.sentiment_log.set_event_sentiment(message_id, sentiment.clone()).await;
+    stores.chat_history.set_message_sentiment(message_id, sentiment.clone()).await;
+
+    // After repeated overrides the event no longer holds what the classifier said
+    let classified_sentiment = match stores.sentiment_overrides.get_override(message_id).await {
+        Some(previous) => previous.classified_sentiment,
+        None => event.sentiment.clone(),
+    };
+    let content = stores.chat_history.get_chat_history(&event.user_id).await.messages
+        .into_iter()
+        .find(|message| message.message_id.as_deref() == Some(message_id))
+        .map(|message| message.content);
+    let sentiment_override = SentimentOverride {
+        message_id: message_id.to_string(),
+        user_id: event.user_id.clone(),
+        content,
+        classified_sentiment,
+        corrected_sentiment: sentiment.clone(),
+        overridden_by: host_id.to_string(),
+        created_at: Utc::now(),
+    };
+    stores.sentiment_overrides.record_override(sentiment_override.clone()).await;
+
+    let details = format!("Changed message {} from {} to {}", message_id, event.sentiment, sentiment);
+    stores.audit_log.record_audit_entry(AuditEntry::new(host_id, AuditAction::OverrideSentiment, &event.user_id, details)).await;
+
+    OverrideOutcome::Overridden(sentiment_override)
+}
+
+pub async fn export_player_data(stores: &Stores, user_id: &str) -> PlayerDataExport {
+    PlayerDataExport {
+        user_id: user_id.to_string(),
+        exported_at: Utc::now(),
+        interaction: stores.user_interaction.export_user_interaction(user_id).await,
+        chat_history: stores.chat_history.export_chat_history(user_id).await,
+        sentiment_events: stores.sentiment_log.get_sentiment_timeline(user_id, None).await,
+        sentiment_overrides: stores.sentiment_overrides.get_user_overrides(user_id).await,
+    }
+}
+
+/// Deletes the player's rows from every store and records who asked for it in the audit log.
+pub async fn forget_player(stores: &Stores, actor_id: &str, user_id: &str) -> ForgetStats {
+    let stats = ForgetStats {
+        chat_messages_deleted: stores.chat_history.delete_chat_history(user_id).await,
+        interaction_deleted: stores.user_interaction.delete_user_interaction(user_id).await,
+        sentiment_events_deleted: stores.sentiment_log.delete_sentiment_events(user_id).await,
+        overrides_deleted: stores.sentiment_overrides.delete_overrides(user_id).await,
+    };
+
+    let details = format!(
+        "Deleted {} chat messages, {} sentiment events and {} interaction record",
+        stats.chat_messages_deleted,
+        stats.sentiment_events_deleted,
+        if stats.interaction_deleted { "the" } else { "no" },
+    );
+    stores.audit_log.record_audit_entry(AuditEntry::new(actor_id, AuditAction::ForgetPlayer, user_id, details)).await;
+
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Sentiment;
+
+    #[tokio::test]
+    async fn test_export_then_forget_player() {
+        let stores = Stores::in_memory();
+        let user_id = "test_user";
+
+        stores.chat_history.add_user_message(user_id, "Hello, Toodles!".to_string()).await;
+        stores.chat_history.add_assistant_message(user_id, "Hello, friend.".to_string()).await;
+        stores.user_interaction.increment_positive_interaction(user_id).await;
+        stores.sentiment_log.record_sentiment(SentimentEvent::new(user_id, None, Sentiment::Positive, None)).await;
+        stores.sentiment_overrides.record_override(SentimentOverride {
+            message_id: "42".to_string(),
+            user_id: user_id.to_string(),
+            content: Some("Hello, Toodles!".to_string()),
+            classified_sentiment: Sentiment::Neutral,
+            corrected_sentiment: Sentiment::Positive,
+            overridden_by: "host".to_string(),
+            created_at: Utc::now(),
+        }).await;
+        stores.chat_history.add_user_message("other_user", "Hi".to_string()).await;
+
+        let export = export_player_data(&stores, user_id).await;
+        assert_eq!(export.chat_history.len(), 2);
+        assert_eq!(export.interaction.map(|interaction| interaction.num_positive), Some(1));
+        assert_eq!(export.sentiment_events.len(), 1);
+        assert_eq!(export.sentiment_overrides.len(), 1, "Expected the override's copy of the message to be exported");
+        assert_eq!(export.sentiment_overrides[0].content.as_deref(), Some("Hello, Toodles!"));
+
+        let stats = forget_player(&stores, user_id, user_id).await;
+        assert_eq!(stats, ForgetStats { chat_messages_deleted: 2, interaction_deleted: true, sentiment_events_deleted: 1, overrides_deleted: 1 });
+
+        let export = export_player_data(&stores, user_id).await;
+        assert!(export.chat_history.is_empty() && export.interaction.is_none() && export.sentiment_events.is_empty() && export.sentiment_overrides.is_empty());
+        assert_eq!(stores.chat_history.get_chat_history("other_user").await.messages.len(), 1);
+
+        let audit = stores.audit_log.get_audit_log(Some(user_id), 10).await;
+        assert_eq!(audit.len(), 1, "Expected the deletion to be audited");
+        assert_eq!(audit[0].action, AuditAction::ForgetPlayer);
+        assert_eq!(audit[0].actor_id, user_id);
+    }
+
+    #[tokio::test]
+    async fn test_host_adjustments_are_audited() {
+        let stores = Stores::in_memory();
+        let (host_id, user_id) = ("host", "test_user");
+
+        for _ in 0..4 {
+            stores.user_interaction.increment_positive_interaction(user_id).await;
+        }
+        for i in 0..6 {
+            stores.chat_history.add_user_message(user_id, format!("Message {}", i)).await;
+        }
+
+        let summary = summarize_player(&stores, user_id, 3).await;
+        assert_eq!(summary.tier, Tier::Favored);
+        let recent: Vec<&str> = summary.recent_history.iter().map(|message| message.content.as_str()).collect();
+        assert_eq!(recent, vec!["Message 3", "Message 4", "Message 5"]);
+
+        let interaction = set_player_counter(&stores, host_id, user_id, &Sentiment::Negative, 9).await;
+        assert_eq!(interaction.num_negative, 9);
+        assert_eq!(interaction.tier(), Tier::Despised);
+
+        let interaction = adjust_player_counter(&stores, host_id, user_id, &Sentiment::Negative, -2).await;
+        assert_eq!(interaction.num_negative, 7);
+
+        reset_player(&stores, host_id, user_id).await;
+        assert_eq!(summarize_player(&stores, user_id, 3).await.interaction.num_positive, 0);
+
+        let audit = stores.audit_log.get_audit_log(Some(user_id), 10).await;
+        let actions: Vec<AuditAction> = audit.iter().map(|entry| entry.action.clone()).collect();
+        assert_eq!(actions, vec![AuditAction::ResetPlayer, AuditAction::AdjustCounter, AuditAction::SetCounter]);
+        assert!(audit.iter().all(|entry| entry.actor_id == host_id));
+    }
+
+    #[tokio::test]
+    async fn test_override_sentiment_corrects_counters() {
+        let stores = Stores::in_memory();
+        let user_id = "test_user";
+
+        // Playful teasing the classifier took badly
+        stores.chat_history.add_chat_message(user_id, ChatMessage {
+            message_id: Some("42".to_string()),
+            ..ChatMessage::new(crate::models::ChatRole::User, "toodles you absolute gremlin 😂".to_string())
+        }).await;
+        stores.sentiment_log.record_sentiment(SentimentEvent::new(user_id, Some("42".to_string()), Sentiment::Negative, Some(0.6))).await;
+        stores.user_interaction.increment_negative_interaction(user_id).await;
+
+        assert!(matches!(override_sentiment(&stores, "host", "missing", Sentiment::Positive).await, OverrideOutcome::UnknownMessage));
+        assert!(matches!(override_sentiment(&stores, "host", "42", Sentiment::Negative).await, OverrideOutcome::Unchanged));
+
+        let OverrideOutcome::Overridden(first) = override_sentiment(&stores, "host", "42", Sentiment::Positive).await else {
+            panic!("Expected the message to be overridden");
+        };
+        assert_eq!(first.content.as_deref(), Some("toodles you absolute gremlin 😂"));
+        let interaction = stores.user_interaction.get_user_interaction(user_id).await;
+        assert_eq!((interaction.num_positive, interaction.num_negative), (1, 0));
+        assert_eq!(stores.sentiment_log.find_sentiment_event("42").await.unwrap().sentiment, Sentiment::Positive);
+        let history = stores.chat_history.get_chat_history(user_id).await.messages;
+        assert_eq!(history[0].sentiment, Some(Sentiment::Positive), "Expected the chat history to show the corrected sentiment");
+
+        // A second override moves the counters again but keeps the classifier's original answer
+        let OverrideOutcome::Overridden(second) = override_sentiment(&stores, "host", "42", Sentiment::Neutral).await else {
+            panic!("Expected the message to be overridden again");
+        };
+        assert_eq!(second.classified_sentiment, Sentiment::Negative);
+        assert_eq!(second.corrected_sentiment, Sentiment::Neutral);
+        let interaction = stores.user_interaction.get_user_interaction(user_id).await;
+        assert_eq!((interaction.num_positive, interaction.num_negative, interaction.num_neutral), (0, 0, 1));
+
+        assert_eq!(stores.sentiment_overrides.list_overrides().await.len(), 1);
+        assert_eq!(stores.audit_log.get_audit_log(Some(user_id), 10).await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_override_sentiment_of_suspected_farming() {
+        let stores = Stores::in_memory();
+        let user_id = "test_user";
+
+        // A heartfelt message the heuristic took for a repeat, so it never counted
+        stores.sentiment_log.record_sentiment(SentimentEvent {
+            farming: Some(crate::models::FarmingFlag::Duplicate),
+            ..SentimentEvent::new(user_id, Some("42".to_string()), Sentiment::Positive, Some(0.9))
+        }).await;
+
+        assert!(matches!(override_sentiment(&stores, "host", "42", Sentiment::Neutral).await, OverrideOutcome::Overridden(_)));
+        let interaction = stores.user_interaction.get_user_interaction(user_id).await;
+        assert_eq!((interaction.num_positive, interaction.num_negative, interaction.num_neutral), (0, 0, 1));
+        assert_eq!(stores.sentiment_log.find_sentiment_event("42").await.unwrap().farming, None, "Expected the override to clear the farming flag");
+
+        // Now that it counts, a further override moves it like any other message
+        override_sentiment(&stores, "host", "42", Sentiment::Negative).await;
+        let interaction = stores.user_interaction.get_user_interaction(user_id).await;
+        assert_eq!((interaction.num_positive, interaction.num_negative, interaction.num_neutral), (0, 1, 0));
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::future::Future;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::models::{ChatRole, PrunedMessage, RetentionPolicy};
+use crate::store::{ChatHistoryStore, SentimentOverrideStore};
+
+/// What a single retention run removed.
+#[derive(Debug, Clone, Default)]
+pub struct PruneStats {
+    pub messages_deleted: usize,
+    pub users_affected: usize,
+    /// Overrides whose copy of a pruned message was dropped along with it
+    pub overrides_cleared: usize,
+    /// Where the deleted messages were archived, if archiving is enabled and anything was deleted
+    pub archive: Option<PathBuf>,
+    pub elapsed: Duration,
+}
+
+/// Suffix of an archive whose messages may not have been deleted yet
+const STAGED_SUFFIX: &str = "partial";
+
+/// Writes `pruned` as gzip-compressed JSON lines into `dir`, one file per run. The file is staged
+/// under a `.partial` name; [`publish_archive`] gives it its real name once the deletion is committed.
+/// Returns `None` without creating a file when there is nothing to archive.
+pub fn archive_pruned_messages(dir: &Path, pruned: &[PrunedMessage], now: DateTime<Utc>) -> io::Result<Option<PathBuf>> {
+    if pruned.is_empty() {
+        return Ok(None);
+    }
+
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("chat_messages-{}.jsonl.gz.{}", now.format("%Y%m%dT%H%M%S%.3fZ"), STAGED_SUFFIX));
+    let mut encoder = GzEncoder::new(BufWriter::new(File::create_new(&path)?), Compression::default());
+    for message in pruned {
+        serde_json::to_writer(&mut encoder, message)?;
+        encoder.write_all(b"\n")?;
+    }
+    encoder.finish()?.flush()?;
+
+    Ok(Some(path))
+}
+
+/// Renames a staged archive to its final `.jsonl.gz` name.
+pub async fn publish_archive(staged: &Path) -> io::Result<PathBuf> {
+    let path = staged.with_extension("");
+    tokio::fs::rename(staged, &path).await?;
+    Ok(path)
+}
+
+/// Applies `policy` once, archiving to `archive_dir` before anything is deleted. Overrides keep a
+/// copy of the message they corrected, so that copy goes with the message.
+pub async fn prune_chat_history(
+    chat_history_store: &(dyn ChatHistoryStore + Send + Sync),
+    sentiment_override_store: &(dyn SentimentOverrideStore + Send + Sync),
+    policy: &RetentionPolicy,
+    archive_dir: Option<&Path>,
+) -> Result<PruneStats, Box<dyn std::error::Error + Send + Sync>> {
+    let started = Instant::now();
+    let staged = Arc::new(Mutex::new(None));
+    let before_prune = |pruned: &[PrunedMessage]| -> Pin<Box<dyn Future<Output = io::Result<()>> + Send>> {
+        let (dir, pruned, staged) = (archive_dir.map(Path::to_path_buf), pruned.to_vec(), staged.clone());
+        Box::pin(async move {
+            if let Some(dir) = dir {
+                let path = tokio::task::spawn_blocking(move || archive_pruned_messages(&dir, &pruned, Utc::now()))
+                    .await
+                    .map_err(io::Error::other)??;
+                *staged.lock().unwrap() = path;
+            }
+            Ok(())
+        })
+    };
+
+    let result = chat_history_store.prune_chat_history(policy, &before_prune).await;
+    let staged = staged.lock().unwrap().take();
+    let pruned = match result {
+        Ok(pruned) => pruned,
+        Err(why) => {
+            // Nothing was deleted, so the next run archives these messages again
+            if let Some(staged) = staged {
+                let _ = tokio::fs::remove_file(staged).await;
+            }
+            return Err(why);
+        },
+    };
+    let archive = match staged {
+        Some(staged) => Some(publish_archive(&staged).await?),
+        None => None,
+    };
+    let users_affected = pruned.iter().map(|pruned| pruned.user_id.as_str()).collect::<HashSet<_>>().len();
+    let message_ids: Vec<String> = pruned
+        .iter()
+        .filter(|pruned| pruned.message.role == ChatRole::User)
+        .filter_map(|pruned| pruned.message.message_id.clone())
+        .collect();
+    let overrides_cleared = sentiment_override_store.clear_override_content(&message_ids).await;
+
+    Ok(PruneStats {
+        messages_deleted: pruned.len(),
+        users_affected,
+        overrides_cleared,
+        archive,
+        elapsed: started.elapsed(),
+    })
+}
+
+/// Prunes chat history every `interval` until the returned task is aborted.
+pub fn spawn_retention_task(
+    chat_history_store: Arc<dyn ChatHistoryStore + Send + Sync>,
+    sentiment_override_store: Arc<dyn SentimentOverrideStore + Send + Sync>,
+    policy: RetentionPolicy,
+    interval: Duration,
+    archive_dir: Option<PathBuf>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match prune_chat_history(chat_history_store.as_ref(), sentiment_override_store.as_ref(), &policy, archive_dir.as_deref()).await {
+                Ok(stats) => info!(
+                    messages_deleted = stats.messages_deleted,
+                    users_affected = stats.users_affected,
+                    overrides_cleared = stats.overrides_cleared,
+                    elapsed_ms = stats.elapsed.as_millis() as u64,
+                    archive = stats.archive.as_ref().map(|path| path.display().to_string()),
+                    "Retention pruned chat history",
+                ),
+                Err(why) => warn!(error = ?why, "Error pruning chat history"),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+
+    use flate2::read::GzDecoder;
+
+    use crate::models::{ChatHistory, ChatMessage, Sentiment, SentimentOverride};
+    use crate::store::{BeforePrune, InMemoryChatHistoryStore, InMemorySentimentOverrideStore};
+
+    #[tokio::test]
+    async fn test_prune_archives_before_deleting() {
+        let store = InMemoryChatHistoryStore::new();
+        for i in 0..4 {
+            let message = ChatMessage { message_id: Some(i.to_string()), ..ChatMessage::new(ChatRole::User, format!("Message {}", i)) };
+            store.add_chat_message("test_user", message).await;
+        }
+        store.add_assistant_message("other_user", "Honk.".to_string()).await;
+        let overrides = InMemorySentimentOverrideStore::new();
+        for message_id in ["0", "3"] {
+            overrides.record_override(SentimentOverride {
+                message_id: message_id.to_string(),
+                user_id: "test_user".to_string(),
+                content: Some(format!("Message {}", message_id)),
+                classified_sentiment: Sentiment::Negative,
+                corrected_sentiment: Sentiment::Positive,
+                overridden_by: "host".to_string(),
+                created_at: Utc::now(),
+            }).await;
+        }
+
+        let archive_dir = tempfile::tempdir().unwrap();
+        let policy = RetentionPolicy { max_age: None, max_messages_per_user: Some(1) };
+        let stats = prune_chat_history(&store, &overrides, &policy, Some(archive_dir.path())).await.unwrap();
+
+        assert_eq!(stats.messages_deleted, 3);
+        assert_eq!(stats.users_affected, 1);
+        assert_eq!(stats.overrides_cleared, 1);
+        assert!(overrides.get_override("0").await.unwrap().content.is_none(), "Expected the override's copy of a pruned message to go with it");
+        assert_eq!(overrides.get_override("3").await.unwrap().content.as_deref(), Some("Message 3"));
+
+        let archive = stats.archive.expect("Expected an archive file to be written");
+        assert!(archive.to_string_lossy().ends_with(".jsonl.gz"), "Expected the archive to be published once the deletion went through");
+        assert_eq!(fs::read_dir(archive_dir.path()).unwrap().count(), 1, "Expected no staged archive to be left behind");
+        let lines: Vec<PrunedMessage> = BufReader::new(GzDecoder::new(File::open(archive).unwrap()))
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].user_id, "test_user");
+        assert_eq!(lines[0].message.role, ChatRole::User);
+        assert_eq!(lines[0].message.content, "Message 0");
+    }
+
+    /// Archives like Postgres does, then fails to commit the deletion
+    struct FailsToCommit;
+
+    #[async_trait::async_trait]
+    impl ChatHistoryStore for FailsToCommit {
+        async fn add_chat_message(&self, _user_id: &str, _message: ChatMessage) {}
+
+        async fn get_chat_history(&self, _user_id: &str) -> ChatHistory {
+            ChatHistory::default()
+        }
+
+        async fn delete_chat_history(&self, _user_id: &str) -> usize {
+            0
+        }
+
+        async fn set_message_sentiment(&self, _message_id: &str, _sentiment: Sentiment) {}
+
+        async fn prune_chat_history(&self, _policy: &RetentionPolicy, before_prune: BeforePrune<'_>) -> Result<Vec<PrunedMessage>, Box<dyn std::error::Error + Send + Sync>> {
+            let message = ChatMessage::new(ChatRole::User, "Hello".to_string());
+            before_prune(&[PrunedMessage { user_id: "test_user".to_string(), message }]).await?;
+            Err("commit failed".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_prune_leaves_no_archive() {
+        let archive_dir = tempfile::tempdir().unwrap();
+        let policy = RetentionPolicy { max_age: None, max_messages_per_user: Some(1) };
+        assert!(prune_chat_history(&FailsToCommit, &InMemorySentimentOverrideStore::new(), &policy, Some(archive_dir.path())).await.is_err());
+        assert_eq!(fs::read_dir(archive_dir.path()).unwrap().count(), 0, "Expected the staged archive to be removed so the next run doesn't archive twice");
+    }
+
+    #[tokio::test]
+    async fn test_prune_without_matches_writes_no_archive() {
+        let store = InMemoryChatHistoryStore::new();
+        store.add_user_message("test_user", "Hello".to_string()).await;
+
+        let archive_dir = tempfile::tempdir().unwrap();
+        let policy = RetentionPolicy { max_age: Some(Duration::from_secs(60)), max_messages_per_user: None };
+        let stats = prune_chat_history(&store, &InMemorySentimentOverrideStore::new(), &policy, Some(archive_dir.path())).await.unwrap();
+
+        assert_eq!(stats.messages_deleted, 0);
+        assert!(stats.archive.is_none());
+        assert_eq!(fs::read_dir(archive_dir.path()).unwrap().count(), 0);
+    }
+}
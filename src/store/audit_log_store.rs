@@ -0,0 +1,126 @@
+use async_trait::async_trait;
+use tokio::sync::RwLock;
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+
+use crate::models::{AuditAction, AuditEntry};
+
+#[async_trait]
+pub trait AuditLogStore {
+    async fn record_audit_entry(&self, entry: AuditEntry);
+    /// Most recent entries first, optionally only those affecting `target_user_id`.
+    async fn get_audit_log(&self, target_user_id: Option<&str>, limit: usize) -> Vec<AuditEntry>;
+}
+
+#[derive(Default)]
+pub struct InMemoryAuditLogStore {
+    store: Arc<RwLock<Vec<AuditEntry>>>,
+}
+
+impl InMemoryAuditLogStore {
+    pub fn new() -> Self {
+        InMemoryAuditLogStore {
+            store: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+}
+
+#[async_trait]
+impl AuditLogStore for InMemoryAuditLogStore {
+    async fn record_audit_entry(&self, entry: AuditEntry) {
+        let mut store = self.store.write().await;
+        store.push(entry);
+    }
+
+    async fn get_audit_log(&self, target_user_id: Option<&str>, limit: usize) -> Vec<AuditEntry> {
+        let store = self.store.read().await;
+        store
+            .iter()
+            .rev()
+            .filter(|entry| target_user_id.is_none_or(|target| entry.target_user_id == target))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+pub struct PostgresAuditLogStore {
+    pool: PgPool,
+}
+
+impl PostgresAuditLogStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl AuditLogStore for PostgresAuditLogStore {
+    async fn record_audit_entry(&self, entry: AuditEntry) {
+        let query = r#"
+            INSERT INTO audit_log (actor_id, action, target_user_id, details, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+        "#;
+        sqlx::query(query)
+            .bind(entry.actor_id)
+            .bind(entry.action.as_str())
+            .bind(entry.target_user_id)
+            .bind(entry.details)
+            .bind(entry.created_at)
+            .execute(&self.pool)
+            .await
+            .expect("Failed to record audit entry");
+    }
+
+    async fn get_audit_log(&self, target_user_id: Option<&str>, limit: usize) -> Vec<AuditEntry> {
+        let query = r#"
+            SELECT actor_id, action, target_user_id, details, created_at
+            FROM audit_log
+            WHERE $1::TEXT IS NULL OR target_user_id = $1
+            ORDER BY created_at DESC, id DESC
+            LIMIT $2
+        "#;
+        let rows = sqlx::query(query)
+            .bind(target_user_id)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await
+            .expect("Failed to fetch audit log");
+
+        rows.into_iter()
+            .map(|row| {
+                let action: String = row.get("action");
+                AuditEntry {
+                    actor_id: row.get("actor_id"),
+                    action: AuditAction::from(action.as_str()),
+                    target_user_id: row.get("target_user_id"),
+                    details: row.get("details"),
+                    created_at: row.get("created_at"),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_in_memory_audit_log_store() {
+        let store = InMemoryAuditLogStore::new();
+        assert!(store.get_audit_log(None, 10).await.is_empty());
+
+        store.record_audit_entry(AuditEntry::new("user_1", AuditAction::ForgetPlayer, "user_1", "first".to_string())).await;
+        store.record_audit_entry(AuditEntry::new("user_2", AuditAction::ForgetPlayer, "user_2", "second".to_string())).await;
+        store.record_audit_entry(AuditEntry::new("user_1", AuditAction::ForgetPlayer, "user_1", "third".to_string())).await;
+
+        let all = store.get_audit_log(None, 2).await;
+        assert_eq!(all.len(), 2, "Expected the limit to be applied");
+        assert_eq!(all[0].details, "third", "Expected the newest entry first");
+
+        let user_1 = store.get_audit_log(Some("user_1"), 10).await;
+        let details: Vec<&str> = user_1.iter().map(|entry| entry.details.as_str()).collect();
+        assert_eq!(details, vec!["third", "first"]);
+    }
+}
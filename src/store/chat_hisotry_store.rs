@@ -1,32 +1,48 @@
 use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use std::collections::HashMap;
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
 use tokio::sync::RwLock;
+use sqlx::postgres::PgRow;
 use sqlx::{PgPool, Row};
 use std::sync::Arc;
 
 
-use crate::models::{ChatHistory, ChatMessage, ChatRole};
+use crate::models::{ChatHistory, ChatMessage, ChatRole, PrunedMessage, RetentionPolicy, Sentiment};
+
+/// Called with the messages a prune is about to delete, before they are gone.
+/// Returning an error leaves the history untouched. The in-memory store doesn't hold its lock
+/// while the returned future runs, so it may do slow IO.
+pub type BeforePrune<'a> = &'a (dyn Fn(&[PrunedMessage]) -> Pin<Box<dyn Future<Output = std::io::Result<()>> + Send>> + Send + Sync);
 
 #[async_trait]
 pub trait ChatHistoryStore {
     async fn add_chat_message(&self, user_id: &str, message: ChatMessage);
 
     async fn add_user_message(&self, user_id: &str, content: String) {
-        self.add_chat_message(user_id, ChatMessage {
-            role: ChatRole::User,
-            content,
-        }).await;
+        self.add_chat_message(user_id, ChatMessage::new(ChatRole::User, content)).await;
     }
 
     async fn add_assistant_message(&self, user_id: &str, content: String) {
-        self.add_chat_message(user_id, ChatMessage {
-            role: ChatRole::Assistant,
-            content,
-        }).await;
+        self.add_chat_message(user_id, ChatMessage::new(ChatRole::Assistant, content)).await;
     }
     async fn get_chat_history(&self, user_id: &str) -> ChatHistory;
+
+    /// Every stored message for the user, for handing their data back to them.
+    async fn export_chat_history(&self, user_id: &str) -> Vec<ChatMessage> {
+        self.get_chat_history(user_id).await.messages
+    }
+    /// Removes all of the user's messages and returns how many were deleted.
+    async fn delete_chat_history(&self, user_id: &str) -> usize;
+    /// Replaces the recorded sentiment of a player's message, e.g. after a host corrects it.
+    async fn set_message_sentiment(&self, message_id: &str, sentiment: Sentiment);
+    /// Deletes every message that falls outside `policy` and returns what was deleted.
+    async fn prune_chat_history(&self, policy: &RetentionPolicy, before_prune: BeforePrune<'_>) -> Result<Vec<PrunedMessage>, Box<dyn Error + Send + Sync>>;
 }
 
+#[derive(Default)]
 pub struct InMemoryChatHistoryStore {
     store: Arc<RwLock<HashMap<String, ChatHistory>>>,
 }
@@ -42,16 +58,67 @@ impl InMemoryChatHistoryStore {
 #[async_trait]
 impl ChatHistoryStore for InMemoryChatHistoryStore {
 
-    async fn add_chat_message(&self, user_id: &str, message: ChatMessage) {
+    async fn add_chat_message(&self, user_id: &str, mut message: ChatMessage) {
         let mut store = self.store.write().await;
         let history = store.entry(user_id.to_string()).or_insert_with(ChatHistory::default);
-        history.add_message(message.role.clone(), message.content);
+        message.created_at.get_or_insert_with(Utc::now);
+        history.messages.push(message);
     }
 
     async fn get_chat_history(&self, user_id: &str) -> ChatHistory {
         let store = self.store.read().await;
         store.get(user_id).cloned().unwrap_or_default()
     }
+
+    async fn delete_chat_history(&self, user_id: &str) -> usize {
+        let mut store = self.store.write().await;
+        store.remove(user_id).map_or(0, |history| history.messages.len())
+    }
+
+    async fn set_message_sentiment(&self, message_id: &str, sentiment: Sentiment) {
+        let mut store = self.store.write().await;
+        let messages = store.values_mut().flat_map(|history| history.messages.iter_mut());
+        for message in messages.filter(|message| message.role == ChatRole::User && message.message_id.as_deref() == Some(message_id)) {
+            message.sentiment = Some(sentiment.clone());
+        }
+    }
+
+    async fn prune_chat_history(&self, policy: &RetentionPolicy, before_prune: BeforePrune<'_>) -> Result<Vec<PrunedMessage>, Box<dyn Error + Send + Sync>> {
+        let cutoff = policy.cutoff(Utc::now());
+        let is_expired = |total: usize, index: usize, message: &ChatMessage| {
+            let too_old = cutoff.zip(message.created_at).is_some_and(|(cutoff, created_at)| created_at < cutoff);
+            let over_limit = policy.max_messages_per_user.is_some_and(|max| total - index > max);
+            too_old || over_limit
+        };
+
+        let mut pruned = Vec::new();
+        for (user_id, history) in self.store.read().await.iter() {
+            let total = history.messages.len();
+            for (index, message) in history.messages.iter().enumerate() {
+                if is_expired(total, index, message) {
+                    pruned.push(PrunedMessage { user_id: user_id.clone(), message: message.clone() });
+                }
+            }
+        }
+        before_prune(&pruned).await?;
+
+        // Only what the hook saw is deleted. Messages added or forgotten in the meantime are left alone.
+        let mut store = self.store.write().await;
+        for PrunedMessage { user_id, message } in &pruned {
+            if let Some(history) = store.get_mut(user_id)
+                && let Some(position) = history.messages.iter().position(|kept| is_same_message(kept, message))
+            {
+                history.messages.remove(position);
+            }
+        }
+        store.retain(|_, history| !history.messages.is_empty());
+
+        Ok(pruned)
+    }
+}
+
+fn is_same_message(a: &ChatMessage, b: &ChatMessage) -> bool {
+    a.role == b.role && a.created_at == b.created_at && a.message_id == b.message_id && a.content == b.content
 }
 
 pub struct PostgresChatHistoryStore {
@@ -67,7 +134,11 @@ impl PostgresChatHistoryStore {
 #[async_trait]
 impl ChatHistoryStore for PostgresChatHistoryStore {
     async fn add_chat_message(&self, user_id: &str, message: ChatMessage) {
-        let query = "INSERT INTO chat_messages (user_id, role, content) VALUES ($1, $2, $3)";
+        let query = r#"
+            INSERT INTO chat_messages
+                (user_id, role, content, message_id, channel_id, guild_id, sentiment, model, prompt_tokens, completion_tokens)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        "#;
         let role = match message.role {
             ChatRole::User => "user",
             ChatRole::Assistant => "assistant",
@@ -78,13 +149,25 @@ impl ChatHistoryStore for PostgresChatHistoryStore {
             .bind(user_id)
             .bind(role)
             .bind(message.content)
+            .bind(message.message_id)
+            .bind(message.channel_id)
+            .bind(message.guild_id)
+            .bind(message.sentiment.as_ref().map(Sentiment::as_str))
+            .bind(message.model)
+            .bind(message.prompt_tokens.map(|tokens| tokens as i32))
+            .bind(message.completion_tokens.map(|tokens| tokens as i32))
             .execute(&self.pool)
             .await
             .unwrap();
     }
 
     async fn get_chat_history(&self, user_id: &str) -> ChatHistory {
-        let query = "SELECT role, content FROM chat_messages WHERE user_id = $1 ORDER BY timestamp ASC";
+        let query = r#"
+            SELECT role, content, message_id, channel_id, guild_id, sentiment, model, prompt_tokens, completion_tokens, timestamp
+            FROM chat_messages
+            WHERE user_id = $1
+            ORDER BY timestamp ASC, id ASC
+        "#;
         let rows = sqlx::query(query)
             .bind(user_id)
             .fetch_all(&self.pool)
@@ -93,26 +176,95 @@ impl ChatHistoryStore for PostgresChatHistoryStore {
 
         let messages = rows
             .unwrap_or_default()
-            .into_iter()
-            .map(|row| {
-                let role_str: String = row.get("role");
-                let role = match role_str.as_str() {
-                    "user" => ChatRole::User,
-                    "assistant" => ChatRole::Assistant,
-                    "system" => ChatRole::System,
-                    _ => ChatRole::Assistant, // Default to Assistant if role is unknown
-                };
-
-                let content: String = row.get("content");
-                ChatMessage {
-                    role,
-                    content,
-                }
-            })
+            .iter()
+            .map(chat_message_from_row)
             .collect();
 
         ChatHistory { messages }
     }
+
+    async fn delete_chat_history(&self, user_id: &str) -> usize {
+        let query = "DELETE FROM chat_messages WHERE user_id = $1";
+        let result = sqlx::query(query)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .expect("Failed to delete chat history");
+        result.rows_affected() as usize
+    }
+
+    async fn set_message_sentiment(&self, message_id: &str, sentiment: Sentiment) {
+        let query = "UPDATE chat_messages SET sentiment = $2 WHERE message_id = $1 AND role = 'user'";
+        sqlx::query(query)
+            .bind(message_id)
+            .bind(sentiment.as_str())
+            .execute(&self.pool)
+            .await
+            .expect("Failed to update message sentiment");
+    }
+
+    async fn prune_chat_history(&self, policy: &RetentionPolicy, before_prune: BeforePrune<'_>) -> Result<Vec<PrunedMessage>, Box<dyn Error + Send + Sync>> {
+        // Rank each user's messages newest first so the per-user limit can be applied in one pass
+        let query = r#"
+            DELETE FROM chat_messages
+            WHERE id IN (
+                SELECT id FROM (
+                    SELECT id, timestamp, ROW_NUMBER() OVER (PARTITION BY user_id ORDER BY timestamp DESC, id DESC) AS rank
+                    FROM chat_messages
+                ) ranked
+                WHERE ($1::TIMESTAMP IS NOT NULL AND ranked.timestamp < $1)
+                   OR ($2::BIGINT IS NOT NULL AND ranked.rank > $2)
+            )
+            RETURNING user_id, role, content, message_id, channel_id, guild_id, sentiment, model, prompt_tokens, completion_tokens, timestamp
+        "#;
+        let cutoff = policy.cutoff(Utc::now()).map(|cutoff| cutoff.naive_utc());
+        let max_messages = policy.max_messages_per_user.map(|max| max as i64);
+
+        // Hold the deletion in a transaction until `before_prune` has seen the rows
+        let mut tx = self.pool.begin().await?;
+        let rows = sqlx::query(query)
+            .bind(cutoff)
+            .bind(max_messages)
+            .fetch_all(&mut tx)
+            .await?;
+
+        let pruned: Vec<PrunedMessage> = rows
+            .iter()
+            .map(|row| PrunedMessage {
+                user_id: row.get("user_id"),
+                message: chat_message_from_row(row),
+            })
+            .collect();
+        before_prune(&pruned).await?;
+        tx.commit().await?;
+
+        Ok(pruned)
+    }
+}
+
+fn chat_message_from_row(row: &PgRow) -> ChatMessage {
+    let role_str: String = row.get("role");
+    let role = match role_str.as_str() {
+        "user" => ChatRole::User,
+        "assistant" => ChatRole::Assistant,
+        "system" => ChatRole::System,
+        _ => ChatRole::Assistant, // Default to Assistant if role is unknown
+    };
+
+    let content: String = row.get("content");
+    let sentiment: Option<String> = row.get("sentiment");
+    let timestamp: Option<NaiveDateTime> = row.get("timestamp");
+    ChatMessage {
+        message_id: row.get("message_id"),
+        channel_id: row.get("channel_id"),
+        guild_id: row.get("guild_id"),
+        sentiment: sentiment.map(|s| Sentiment::from(s.as_str())),
+        model: row.get("model"),
+        prompt_tokens: row.get::<Option<i32>, _>("prompt_tokens").map(|tokens| tokens as u32),
+        completion_tokens: row.get::<Option<i32>, _>("completion_tokens").map(|tokens| tokens as u32),
+        created_at: timestamp.map(|t| DateTime::from_naive_utc_and_offset(t, Utc)),
+        ..ChatMessage::new(role, content)
+    }
 }
 
 #[cfg(test)]
@@ -129,10 +281,7 @@ mod tests {
         assert!(store.get_chat_history(user_id).await.messages.is_empty());
 
         // Create a message and add it to the store
-        let message = ChatMessage {
-            role: ChatRole::User,
-            content: "Hello, Toodles!".to_string(),
-        };
+        let message = ChatMessage::new(ChatRole::User, "Hello, Toodles!".to_string());
         store.add_chat_message(user_id, message).await;
 
         // Retrieve the chat history for the user
@@ -140,6 +289,84 @@ mod tests {
         assert_eq!(history.messages.len(), 1, "Expected one message in chat history");
         assert_eq!(history.messages[0].role, ChatRole::User, "Expected message role to be User");
         assert_eq!(history.messages[0].content, "Hello, Toodles!", "Expected message content to match");
+        assert!(history.messages[0].created_at.is_some(), "Expected the store to timestamp the message");
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_chat_history_store_keeps_metadata() {
+        let store = InMemoryChatHistoryStore::new();
+        let user_id = "test_user";
+
+        let message = ChatMessage {
+            message_id: Some("1234".to_string()),
+            channel_id: Some("5678".to_string()),
+            guild_id: Some("9012".to_string()),
+            model: Some("gpt-3.5-turbo".to_string()),
+            prompt_tokens: Some(120),
+            completion_tokens: Some(30),
+            ..ChatMessage::new(ChatRole::Assistant, "Honk honk.".to_string())
+        };
+        store.add_chat_message(user_id, message).await;
+
+        let history = store.get_chat_history(user_id).await;
+        let stored = &history.messages[0];
+        assert_eq!(stored.message_id.as_deref(), Some("1234"));
+        assert_eq!(stored.channel_id.as_deref(), Some("5678"));
+        assert_eq!(stored.guild_id.as_deref(), Some("9012"));
+        assert_eq!(stored.model.as_deref(), Some("gpt-3.5-turbo"));
+        assert_eq!(stored.prompt_tokens, Some(120));
+        assert_eq!(stored.completion_tokens, Some(30));
+        assert_eq!(stored.sentiment, None);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_export_and_delete_chat_history() {
+        let store = InMemoryChatHistoryStore::new();
+        store.add_user_message("test_user", "Hello, Toodles!".to_string()).await;
+        store.add_assistant_message("test_user", "Hello, friend.".to_string()).await;
+        store.add_user_message("other_user", "Hi".to_string()).await;
+
+        let exported = store.export_chat_history("test_user").await;
+        assert_eq!(exported.len(), 2);
+        assert_eq!(exported[1].role, ChatRole::Assistant);
+
+        assert_eq!(store.delete_chat_history("test_user").await, 2);
+        assert!(store.export_chat_history("test_user").await.is_empty());
+        assert_eq!(store.delete_chat_history("test_user").await, 0, "Expected a second delete to find nothing");
+        assert_eq!(store.get_chat_history("other_user").await.messages.len(), 1, "Expected other users to be untouched");
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_prune_chat_history() {
+        let store = InMemoryChatHistoryStore::new();
+        let now = Utc::now();
+
+        let old = ChatMessage {
+            created_at: Some(now - chrono::Duration::days(400)),
+            ..ChatMessage::new(ChatRole::User, "Remember me?".to_string())
+        };
+        store.add_chat_message("departed_user", old).await;
+        for i in 0..5 {
+            store.add_user_message("chatty_user", format!("Message {}", i)).await;
+        }
+
+        let policy = RetentionPolicy {
+            max_age: Some(std::time::Duration::from_secs(365 * 24 * 60 * 60)),
+            max_messages_per_user: Some(3),
+        };
+
+        // A failing hook must leave everything in place
+        let failed = store.prune_chat_history(&policy, &|_| Box::pin(async { Err(std::io::Error::other("archive unavailable")) })).await;
+        assert!(failed.is_err());
+        assert_eq!(store.get_chat_history("chatty_user").await.messages.len(), 5);
+
+        let pruned = store.prune_chat_history(&policy, &|_| Box::pin(async { Ok(()) })).await.unwrap();
+        assert_eq!(pruned.len(), 3, "Expected the old message and the two oldest over the limit to be pruned");
+        assert!(store.get_chat_history("departed_user").await.messages.is_empty());
+
+        let history = store.get_chat_history("chatty_user").await;
+        let contents: Vec<&str> = history.messages.iter().map(|message| message.content.as_str()).collect();
+        assert_eq!(contents, vec!["Message 2", "Message 3", "Message 4"], "Expected the newest messages to be kept");
     }
 
     #[tokio::test]
@@ -153,10 +380,7 @@ mod tests {
         assert!(chat_history.messages.is_empty(), "Expected empty chat history for new user");
 
         // Create a message and add it to the store
-        let message = ChatMessage {
-            role: ChatRole::User,
-            content: "Hello, Toodles!".to_string(),
-        };
+        let message = ChatMessage::new(ChatRole::User, "Hello, Toodles!".to_string());
         store.add_chat_message(user_id, message).await;
 
         // Retrieve the chat history for the user
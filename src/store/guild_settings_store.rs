@@ -0,0 +1,261 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+use chrono::NaiveTime;
+
+use crate::models::{Feature, GuildSettings, QuietHours, QuietMode};
+
+#[async_trait]
+pub trait GuildSettingsStore {
+    /// The guild's settings, or defaults if no host has changed them.
+    async fn get_guild_settings(&self, guild_id: &str) -> GuildSettings;
+    async fn set_guild_settings(&self, settings: GuildSettings);
+}
+
+#[derive(Default)]
+pub struct InMemoryGuildSettingsStore {
+    store: Arc<RwLock<HashMap<String, GuildSettings>>>,
+}
+
+impl InMemoryGuildSettingsStore {
+    pub fn new() -> Self {
+        InMemoryGuildSettingsStore {
+            store: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+}
+
+#[async_trait]
+impl GuildSettingsStore for InMemoryGuildSettingsStore {
+    async fn get_guild_settings(&self, guild_id: &str) -> GuildSettings {
+        let store = self.store.read().await;
+        store.get(guild_id).cloned().unwrap_or_else(|| GuildSettings::new(guild_id))
+    }
+
+    async fn set_guild_settings(&self, settings: GuildSettings) {
+        let mut store = self.store.write().await;
+        store.insert(settings.guild_id.clone(), settings);
+    }
+}
+
+pub struct PostgresGuildSettingsStore {
+    pool: PgPool,
+}
+
+impl PostgresGuildSettingsStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl GuildSettingsStore for PostgresGuildSettingsStore {
+    async fn get_guild_settings(&self, guild_id: &str) -> GuildSettings {
+        let query = r#"
+            SELECT guild_id, prefix, enabled_channel_ids, denied_channel_ids, quiet_start, quiet_end, quiet_utc_offset_minutes, quiet_mode, persona, welcome_channel_id, host_role_id, max_reply_tokens, disabled_features, updated_at
+            FROM guild_settings
+            WHERE guild_id = $1
+        "#;
+        sqlx::query(query)
+            .bind(guild_id)
+            .fetch_optional(&self.pool)
+            .await
+            .expect("Failed to fetch guild settings")
+            .map(|row| guild_settings_from_row(&row))
+            .unwrap_or_else(|| GuildSettings::new(guild_id))
+    }
+
+    async fn set_guild_settings(&self, settings: GuildSettings) {
+        let query = r#"
+            INSERT INTO guild_settings
+                (guild_id, prefix, enabled_channel_ids, denied_channel_ids, quiet_start, quiet_end, quiet_utc_offset_minutes, quiet_mode,
+                 persona, welcome_channel_id, host_role_id, max_reply_tokens, disabled_features, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+            ON CONFLICT (guild_id)
+            DO UPDATE SET
+                prefix = EXCLUDED.prefix,
+                enabled_channel_ids = EXCLUDED.enabled_channel_ids,
+                denied_channel_ids = EXCLUDED.denied_channel_ids,
+                quiet_start = EXCLUDED.quiet_start,
+                quiet_end = EXCLUDED.quiet_end,
+                quiet_utc_offset_minutes = EXCLUDED.quiet_utc_offset_minutes,
+                quiet_mode = EXCLUDED.quiet_mode,
+                persona = EXCLUDED.persona,
+                welcome_channel_id = EXCLUDED.welcome_channel_id,
+                host_role_id = EXCLUDED.host_role_id,
+                max_reply_tokens = EXCLUDED.max_reply_tokens,
+                disabled_features = EXCLUDED.disabled_features,
+                updated_at = EXCLUDED.updated_at
+        "#;
+        let disabled_features: Vec<String> = settings.disabled_features.iter().map(|feature| feature.as_str().to_string()).collect();
+        sqlx::query(query)
+            .bind(settings.guild_id)
+            .bind(settings.prefix)
+            .bind(settings.enabled_channel_ids)
+            .bind(settings.denied_channel_ids)
+            .bind(settings.quiet_hours.map(|quiet_hours| quiet_hours.start))
+            .bind(settings.quiet_hours.map(|quiet_hours| quiet_hours.end))
+            .bind(settings.quiet_hours.map_or(0, |quiet_hours| quiet_hours.utc_offset_minutes))
+            .bind(settings.quiet_mode.as_str())
+            .bind(settings.persona)
+            .bind(settings.welcome_channel_id)
+            .bind(settings.host_role_id)
+            .bind(settings.max_reply_tokens.map(i32::from))
+            .bind(disabled_features)
+            .bind(settings.updated_at)
+            .execute(&self.pool)
+            .await
+            .expect("Failed to save guild settings");
+    }
+}
+
+/// Keeps guilds' settings in memory in front of another store, since they are read for every
+/// message Toodles sees. Changes made through this store are written through to the cache.
+pub struct CachedGuildSettingsStore {
+    inner: Arc<dyn GuildSettingsStore + Send + Sync>,
+    cache: RwLock<HashMap<String, GuildSettings>>,
+}
+
+impl CachedGuildSettingsStore {
+    pub fn new(inner: Arc<dyn GuildSettingsStore + Send + Sync>) -> Self {
+        CachedGuildSettingsStore {
+            inner,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl GuildSettingsStore for CachedGuildSettingsStore {
+    async fn get_guild_settings(&self, guild_id: &str) -> GuildSettings {
+        if let Some(settings) = self.cache.read().await.get(guild_id) {
+            return settings.clone();
+        }
+        let settings = self.inner.get_guild_settings(guild_id).await;
+        // A change that landed while this read was in flight is newer, so it wins
+        self.cache.write().await.entry(guild_id.to_string()).or_insert(settings).clone()
+    }
+
+    async fn set_guild_settings(&self, settings: GuildSettings) {
+        let guild_id = settings.guild_id.clone();
+        // Held throughout so concurrent changes reach the cache in the order they reached the store
+        let mut cache = self.cache.write().await;
+        self.inner.set_guild_settings(settings).await;
+        // Read back so anything the inner store fills in (like `updated_at`) is kept
+        let settings = self.inner.get_guild_settings(&guild_id).await;
+        cache.insert(guild_id, settings);
+    }
+}
+
+fn guild_settings_from_row(row: &PgRow) -> GuildSettings {
+    let max_reply_tokens: Option<i32> = row.get("max_reply_tokens");
+    let disabled_features: Vec<String> = row.get("disabled_features");
+    let quiet_start: Option<NaiveTime> = row.get("quiet_start");
+    let quiet_end: Option<NaiveTime> = row.get("quiet_end");
+    let quiet_mode: String = row.get("quiet_mode");
+    GuildSettings {
+        guild_id: row.get("guild_id"),
+        prefix: row.get("prefix"),
+        enabled_channel_ids: row.get("enabled_channel_ids"),
+        denied_channel_ids: row.get("denied_channel_ids"),
+        quiet_hours: quiet_start.zip(quiet_end).map(|(start, end)| QuietHours {
+            start,
+            end,
+            utc_offset_minutes: row.get("quiet_utc_offset_minutes"),
+        }),
+        quiet_mode: QuietMode::from(quiet_mode.as_str()),
+        persona: row.get("persona"),
+        welcome_channel_id: row.get("welcome_channel_id"),
+        host_role_id: row.get("host_role_id"),
+        max_reply_tokens: max_reply_tokens.map(|max_tokens| max_tokens as u16),
+        disabled_features: disabled_features.iter().map(|feature| Feature::from(feature.as_str())).collect(),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tokio::sync::Notify;
+
+    use crate::models::SettingChange;
+
+    #[tokio::test]
+    async fn test_in_memory_guild_settings_store() {
+        let store = InMemoryGuildSettingsStore::new();
+        assert_eq!(store.get_guild_settings("1").await.prefix, None);
+
+        let mut settings = store.get_guild_settings("1").await;
+        settings.apply(SettingChange::Prefix(Some("!clown".to_string())));
+        store.set_guild_settings(settings).await;
+
+        assert_eq!(store.get_guild_settings("1").await.prefix.as_deref(), Some("!clown"));
+        assert_eq!(store.get_guild_settings("2").await.prefix, None, "Expected settings to be kept per guild");
+    }
+
+    #[tokio::test]
+    async fn test_cached_guild_settings_store() {
+        let inner = Arc::new(InMemoryGuildSettingsStore::new());
+        let store = CachedGuildSettingsStore::new(inner.clone());
+        assert_eq!(store.get_guild_settings("1").await.prefix, None);
+
+        let mut settings = inner.get_guild_settings("1").await;
+        settings.apply(SettingChange::Prefix(Some("!sneaky".to_string())));
+        inner.set_guild_settings(settings).await;
+        assert_eq!(store.get_guild_settings("1").await.prefix, None, "Expected repeat reads to be served from the cache");
+
+        let mut settings = store.get_guild_settings("1").await;
+        settings.apply(SettingChange::Prefix(Some("!clown".to_string())));
+        store.set_guild_settings(settings).await;
+        assert_eq!(store.get_guild_settings("1").await.prefix.as_deref(), Some("!clown"), "Expected a change to replace the cached copy");
+    }
+
+    /// Stalls the first read after it has fetched, until told to go on
+    #[derive(Default)]
+    struct StalledRead {
+        inner: InMemoryGuildSettingsStore,
+        stall: AtomicBool,
+        fetched: Notify,
+        resume: Notify,
+    }
+
+    #[async_trait]
+    impl GuildSettingsStore for StalledRead {
+        async fn get_guild_settings(&self, guild_id: &str) -> GuildSettings {
+            let settings = self.inner.get_guild_settings(guild_id).await;
+            if self.stall.swap(false, Ordering::SeqCst) {
+                self.fetched.notify_one();
+                self.resume.notified().await;
+            }
+            settings
+        }
+
+        async fn set_guild_settings(&self, settings: GuildSettings) {
+            self.inner.set_guild_settings(settings).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cached_read_racing_a_change() {
+        let inner = Arc::new(StalledRead { stall: AtomicBool::new(true), ..Default::default() });
+        let store = Arc::new(CachedGuildSettingsStore::new(inner.clone()));
+
+        // A read misses the cache and fetches the old settings, then a host changes them before it finishes
+        let read = tokio::spawn({
+            let store = store.clone();
+            async move { store.get_guild_settings("1").await }
+        });
+        inner.fetched.notified().await;
+        let mut settings = GuildSettings::new("1");
+        settings.apply(SettingChange::Prefix(Some("!clown".to_string())));
+        store.set_guild_settings(settings).await;
+        inner.resume.notify_one();
+        read.await.unwrap();
+
+        assert_eq!(store.get_guild_settings("1").await.prefix.as_deref(), Some("!clown"), "Expected the stale read not to overwrite the change");
+    }
+}
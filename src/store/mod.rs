@@ -1,5 +1,57 @@
+mod audit_log_store;
 mod chat_hisotry_store;
+mod guild_settings_store;
+mod sentiment_log_store;
+mod sentiment_override_store;
+mod usage_store;
 mod user_interaction_store;
 
+pub use audit_log_store::*;
 pub use chat_hisotry_store::*;
-pub use user_interaction_store::*;
\ No newline at end of file
+pub use guild_settings_store::*;
+pub use sentiment_log_store::*;
+pub use sentiment_override_store::*;
+pub use usage_store::*;
+pub use user_interaction_store::*;
+
+use std::sync::Arc;
+
+use sqlx::PgPool;
+
+/// Every store Toodles reads and writes, backed by the same storage.
+#[derive(Clone)]
+pub struct Stores {
+    pub chat_history: Arc<dyn ChatHistoryStore + Send + Sync>,
+    pub user_interaction: Arc<dyn UserInteractionStore + Send + Sync>,
+    pub sentiment_log: Arc<dyn SentimentLogStore + Send + Sync>,
+    pub audit_log: Arc<dyn AuditLogStore + Send + Sync>,
+    pub sentiment_overrides: Arc<dyn SentimentOverrideStore + Send + Sync>,
+    pub guild_settings: Arc<dyn GuildSettingsStore + Send + Sync>,
+    pub usage: Arc<dyn UsageStore + Send + Sync>,
+}
+
+impl Stores {
+    pub fn in_memory() -> Self {
+        Stores {
+            chat_history: Arc::new(InMemoryChatHistoryStore::new()),
+            user_interaction: Arc::new(InMemoryUserInteractionStore::new()),
+            sentiment_log: Arc::new(InMemorySentimentLogStore::new()),
+            audit_log: Arc::new(InMemoryAuditLogStore::new()),
+            sentiment_overrides: Arc::new(InMemorySentimentOverrideStore::new()),
+            guild_settings: Arc::new(InMemoryGuildSettingsStore::new()),
+            usage: Arc::new(InMemoryUsageStore::new()),
+        }
+    }
+
+    pub fn postgres(pool: PgPool) -> Self {
+        Stores {
+            chat_history: Arc::new(PostgresChatHistoryStore::new(pool.clone())),
+            user_interaction: Arc::new(PostgresUserInteractionStore::new(pool.clone())),
+            sentiment_log: Arc::new(PostgresSentimentLogStore::new(pool.clone())),
+            audit_log: Arc::new(PostgresAuditLogStore::new(pool.clone())),
+            sentiment_overrides: Arc::new(PostgresSentimentOverrideStore::new(pool.clone())),
+            guild_settings: Arc::new(CachedGuildSettingsStore::new(Arc::new(PostgresGuildSettingsStore::new(pool.clone())))),
+            usage: Arc::new(PostgresUsageStore::new(pool)),
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+
+use crate::models::{FarmingFlag, Sentiment, SentimentEvent, SentimentSwing};
+
+#[async_trait]
+pub trait SentimentLogStore {
+    async fn record_sentiment(&self, event: SentimentEvent);
+    /// A player's classifications in chronological order, optionally only those at or after `since`.
+    async fn get_sentiment_timeline(&self, user_id: &str, since: Option<DateTime<Utc>>) -> Vec<SentimentEvent>;
+    /// Players whose standing moved the most between `from` (inclusive) and `to` (exclusive),
+    /// ordered by the size of their net positive/negative change.
+    async fn get_biggest_swings(&self, from: DateTime<Utc>, to: DateTime<Utc>, limit: usize) -> Vec<SentimentSwing>;
+    /// Removes the user's classifications and returns how many were deleted.
+    async fn delete_sentiment_events(&self, user_id: &str) -> usize;
+    /// The classification of a specific platform message.
+    async fn find_sentiment_event(&self, message_id: &str) -> Option<SentimentEvent>;
+    /// Replaces the recorded sentiment of a message, e.g. after a host corrects it. The host's
+    /// judgement also clears any farming flag, so the message counts from then on.
+    async fn set_event_sentiment(&self, message_id: &str, sentiment: Sentiment);
+}
+
+#[derive(Default)]
+pub struct InMemorySentimentLogStore {
+    store: Arc<RwLock<Vec<SentimentEvent>>>,
+}
+
+impl InMemorySentimentLogStore {
+    pub fn new() -> Self {
+        InMemorySentimentLogStore {
+            store: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+}
+
+#[async_trait]
+impl SentimentLogStore for InMemorySentimentLogStore {
+    async fn record_sentiment(&self, event: SentimentEvent) {
+        let mut store = self.store.write().await;
+        store.push(event);
+    }
+
+    async fn get_sentiment_timeline(&self, user_id: &str, since: Option<DateTime<Utc>>) -> Vec<SentimentEvent> {
+        let store = self.store.read().await;
+        let mut timeline: Vec<SentimentEvent> = store
+            .iter()
+            .filter(|event| event.user_id == user_id && since.is_none_or(|since| event.created_at >= since))
+            .cloned()
+            .collect();
+        timeline.sort_by_key(|event| event.created_at);
+        timeline
+    }
+
+    async fn get_biggest_swings(&self, from: DateTime<Utc>, to: DateTime<Utc>, limit: usize) -> Vec<SentimentSwing> {
+        let store = self.store.read().await;
+        let mut swings: HashMap<&str, SentimentSwing> = HashMap::new();
+        for event in store.iter().filter(|event| event.created_at >= from && event.created_at < to) {
+            let swing = swings.entry(&event.user_id).or_insert_with(|| SentimentSwing {
+                user_id: event.user_id.clone(),
+                num_positive: 0,
+                num_negative: 0,
+                num_neutral: 0,
+            });
+            match event.sentiment {
+                Sentiment::Positive => swing.num_positive += 1,
+                Sentiment::Negative => swing.num_negative += 1,
+                Sentiment::Neutral => swing.num_neutral += 1,
+            }
+        }
+
+        let mut swings: Vec<SentimentSwing> = swings.into_values().collect();
+        swings.sort_by(|a, b| b.net().abs().cmp(&a.net().abs()).then_with(|| a.user_id.cmp(&b.user_id)));
+        swings.truncate(limit);
+        swings
+    }
+
+    async fn delete_sentiment_events(&self, user_id: &str) -> usize {
+        let mut store = self.store.write().await;
+        let before = store.len();
+        store.retain(|event| event.user_id != user_id);
+        before - store.len()
+    }
+
+    async fn find_sentiment_event(&self, message_id: &str) -> Option<SentimentEvent> {
+        let store = self.store.read().await;
+        store.iter().find(|event| event.message_id.as_deref() == Some(message_id)).cloned()
+    }
+
+    async fn set_event_sentiment(&self, message_id: &str, sentiment: Sentiment) {
+        let mut store = self.store.write().await;
+        for event in store.iter_mut().filter(|event| event.message_id.as_deref() == Some(message_id)) {
+            event.sentiment = sentiment.clone();
+            event.farming = None;
+        }
+    }
+}
+
+pub struct PostgresSentimentLogStore {
+    pool: PgPool,
+}
+
+impl PostgresSentimentLogStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl SentimentLogStore for PostgresSentimentLogStore {
+    async fn record_sentiment(&self, event: SentimentEvent) {
+        let query = r#"
+            INSERT INTO sentiment_events (user_id, message_id, sentiment, confidence, farming, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+        "#;
+        sqlx::query(query)
+            .bind(event.user_id)
+            .bind(event.message_id)
+            .bind(event.sentiment.as_str())
+            .bind(event.confidence)
+            .bind(event.farming.map(|flag| flag.as_str()))
+            .bind(event.created_at)
+            .execute(&self.pool)
+            .await
+            .expect("Failed to record sentiment event");
+    }
+
+    async fn get_sentiment_timeline(&self, user_id: &str, since: Option<DateTime<Utc>>) -> Vec<SentimentEvent> {
+        let query = r#"
+            SELECT user_id, message_id, sentiment, confidence, farming, created_at
+            FROM sentiment_events
+            WHERE user_id = $1 AND ($2::TIMESTAMPTZ IS NULL OR created_at >= $2)
+            ORDER BY created_at ASC, id ASC
+        "#;
+        let rows = sqlx::query(query)
+            .bind(user_id)
+            .bind(since)
+            .fetch_all(&self.pool)
+            .await
+            .expect("Failed to fetch sentiment timeline");
+
+        rows.iter().map(sentiment_event_from_row).collect()
+    }
+
+    async fn get_biggest_swings(&self, from: DateTime<Utc>, to: DateTime<Utc>, limit: usize) -> Vec<SentimentSwing> {
+        let query = r#"
+            SELECT
+                user_id,
+                COUNT(*) FILTER (WHERE sentiment = 'positive') AS num_positive,
+                COUNT(*) FILTER (WHERE sentiment = 'negative') AS num_negative,
+                COUNT(*) FILTER (WHERE sentiment = 'neutral') AS num_neutral
+            FROM sentiment_events
+            WHERE created_at >= $1 AND created_at < $2
+            GROUP BY user_id
+            ORDER BY ABS(COUNT(*) FILTER (WHERE sentiment = 'positive') - COUNT(*) FILTER (WHERE sentiment = 'negative')) DESC, user_id ASC
+            LIMIT $3
+        "#;
+        let rows = sqlx::query(query)
+            .bind(from)
+            .bind(to)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await
+            .expect("Failed to fetch sentiment swings");
+
+        rows.into_iter()
+            .map(|row| SentimentSwing {
+                user_id: row.get("user_id"),
+                num_positive: row.get::<i64, _>("num_positive") as usize,
+                num_negative: row.get::<i64, _>("num_negative") as usize,
+                num_neutral: row.get::<i64, _>("num_neutral") as usize,
+            })
+            .collect()
+    }
+
+    async fn delete_sentiment_events(&self, user_id: &str) -> usize {
+        let query = "DELETE FROM sentiment_events WHERE user_id = $1";
+        let result = sqlx::query(query)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .expect("Failed to delete sentiment events");
+        result.rows_affected() as usize
+    }
+
+    async fn find_sentiment_event(&self, message_id: &str) -> Option<SentimentEvent> {
+        let query = r#"
+            SELECT user_id, message_id, sentiment, confidence, farming, created_at
+            FROM sentiment_events
+            WHERE message_id = $1
+            ORDER BY id ASC
+            LIMIT 1
+        "#;
+        sqlx::query(query)
+            .bind(message_id)
+            .fetch_optional(&self.pool)
+            .await
+            .expect("Failed to fetch sentiment event")
+            .map(|row| sentiment_event_from_row(&row))
+    }
+
+    async fn set_event_sentiment(&self, message_id: &str, sentiment: Sentiment) {
+        let query = "UPDATE sentiment_events SET sentiment = $2, farming = NULL WHERE message_id = $1";
+        sqlx::query(query)
+            .bind(message_id)
+            .bind(sentiment.as_str())
+            .execute(&self.pool)
+            .await
+            .expect("Failed to update sentiment event");
+    }
+}
+
+fn sentiment_event_from_row(row: &PgRow) -> SentimentEvent {
+    let sentiment: String = row.get("sentiment");
+    let farming: Option<String> = row.get("farming");
+    SentimentEvent {
+        user_id: row.get("user_id"),
+        message_id: row.get("message_id"),
+        sentiment: Sentiment::from(sentiment.as_str()),
+        confidence: row.get("confidence"),
+        farming: farming.as_deref().map(FarmingFlag::from),
+        created_at: row.get("created_at"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn event_at(user_id: &str, sentiment: Sentiment, created_at: DateTime<Utc>) -> SentimentEvent {
+        SentimentEvent {
+            created_at,
+            ..SentimentEvent::new(user_id, None, sentiment, Some(0.9))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_sentiment_timeline() {
+        let store = InMemorySentimentLogStore::new();
+        let now = Utc::now();
+
+        assert!(store.get_sentiment_timeline("test_user", None).await.is_empty());
+
+        store.record_sentiment(event_at("test_user", Sentiment::Negative, now)).await;
+        store.record_sentiment(event_at("test_user", Sentiment::Positive, now - Duration::hours(2))).await;
+        store.record_sentiment(event_at("other_user", Sentiment::Neutral, now)).await;
+
+        let timeline = store.get_sentiment_timeline("test_user", None).await;
+        assert_eq!(timeline.len(), 2, "Expected only the test user's events");
+        assert_eq!(timeline[0].sentiment, Sentiment::Positive, "Expected events in chronological order");
+        assert_eq!(timeline[1].sentiment, Sentiment::Negative);
+
+        let recent = store.get_sentiment_timeline("test_user", Some(now - Duration::hours(1))).await;
+        assert_eq!(recent.len(), 1, "Expected events before `since` to be excluded");
+        assert_eq!(recent[0].sentiment, Sentiment::Negative);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_biggest_swings() {
+        let store = InMemorySentimentLogStore::new();
+        let now = Utc::now();
+
+        for _ in 0..3 {
+            store.record_sentiment(event_at("fan", Sentiment::Positive, now)).await;
+        }
+        for _ in 0..2 {
+            store.record_sentiment(event_at("hater", Sentiment::Negative, now)).await;
+        }
+        store.record_sentiment(event_at("fence_sitter", Sentiment::Positive, now)).await;
+        store.record_sentiment(event_at("fence_sitter", Sentiment::Negative, now)).await;
+        // Outside the window
+        for _ in 0..5 {
+            store.record_sentiment(event_at("hater", Sentiment::Negative, now - Duration::days(2))).await;
+        }
+
+        let swings = store.get_biggest_swings(now - Duration::days(1), now + Duration::seconds(1), 2).await;
+        assert_eq!(swings.len(), 2, "Expected the limit to be applied");
+        assert_eq!(swings[0].user_id, "fan");
+        assert_eq!(swings[0].net(), 3);
+        assert_eq!(swings[1].user_id, "hater");
+        assert_eq!(swings[1].net(), -2, "Expected events outside the window to be ignored");
+    }
+}
@@ -0,0 +1,239 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+
+use crate::models::{Sentiment, SentimentOverride};
+
+#[async_trait]
+pub trait SentimentOverrideStore {
+    /// Stores the override, replacing any earlier override of the same message.
+    async fn record_override(&self, sentiment_override: SentimentOverride);
+    async fn get_override(&self, message_id: &str) -> Option<SentimentOverride>;
+    /// Every override, oldest first.
+    async fn list_overrides(&self) -> Vec<SentimentOverride>;
+    /// Overrides of the user's messages, oldest first.
+    async fn get_user_overrides(&self, user_id: &str) -> Vec<SentimentOverride>;
+    /// Drops the copied text of these messages, e.g. once retention has pruned them, and returns how many were cleared.
+    async fn clear_override_content(&self, message_ids: &[String]) -> usize;
+    /// Removes overrides of the user's messages and returns how many were deleted.
+    async fn delete_overrides(&self, user_id: &str) -> usize;
+}
+
+#[derive(Default)]
+pub struct InMemorySentimentOverrideStore {
+    store: Arc<RwLock<HashMap<String, SentimentOverride>>>,
+}
+
+impl InMemorySentimentOverrideStore {
+    pub fn new() -> Self {
+        InMemorySentimentOverrideStore {
+            store: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+}
+
+#[async_trait]
+impl SentimentOverrideStore for InMemorySentimentOverrideStore {
+    async fn record_override(&self, sentiment_override: SentimentOverride) {
+        let mut store = self.store.write().await;
+        store.insert(sentiment_override.message_id.clone(), sentiment_override);
+    }
+
+    async fn get_override(&self, message_id: &str) -> Option<SentimentOverride> {
+        let store = self.store.read().await;
+        store.get(message_id).cloned()
+    }
+
+    async fn list_overrides(&self) -> Vec<SentimentOverride> {
+        let store = self.store.read().await;
+        let mut overrides: Vec<SentimentOverride> = store.values().cloned().collect();
+        overrides.sort_by_key(|sentiment_override| sentiment_override.created_at);
+        overrides
+    }
+
+    async fn get_user_overrides(&self, user_id: &str) -> Vec<SentimentOverride> {
+        let store = self.store.read().await;
+        let mut overrides: Vec<SentimentOverride> = store.values().filter(|sentiment_override| sentiment_override.user_id == user_id).cloned().collect();
+        overrides.sort_by_key(|sentiment_override| sentiment_override.created_at);
+        overrides
+    }
+
+    async fn clear_override_content(&self, message_ids: &[String]) -> usize {
+        let mut store = self.store.write().await;
+        message_ids
+            .iter()
+            .filter(|message_id| store.get_mut(*message_id).and_then(|sentiment_override| sentiment_override.content.take()).is_some())
+            .count()
+    }
+
+    async fn delete_overrides(&self, user_id: &str) -> usize {
+        let mut store = self.store.write().await;
+        let before = store.len();
+        store.retain(|_, sentiment_override| sentiment_override.user_id != user_id);
+        before - store.len()
+    }
+}
+
+pub struct PostgresSentimentOverrideStore {
+    pool: PgPool,
+}
+
+impl PostgresSentimentOverrideStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl SentimentOverrideStore for PostgresSentimentOverrideStore {
+    async fn record_override(&self, sentiment_override: SentimentOverride) {
+        let query = r#"
+            INSERT INTO sentiment_overrides
+                (message_id, user_id, content, classified_sentiment, corrected_sentiment, overridden_by, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (message_id)
+            DO UPDATE SET
+                corrected_sentiment = EXCLUDED.corrected_sentiment,
+                overridden_by = EXCLUDED.overridden_by,
+                created_at = EXCLUDED.created_at
+        "#;
+        sqlx::query(query)
+            .bind(sentiment_override.message_id)
+            .bind(sentiment_override.user_id)
+            .bind(sentiment_override.content)
+            .bind(sentiment_override.classified_sentiment.as_str())
+            .bind(sentiment_override.corrected_sentiment.as_str())
+            .bind(sentiment_override.overridden_by)
+            .bind(sentiment_override.created_at)
+            .execute(&self.pool)
+            .await
+            .expect("Failed to record sentiment override");
+    }
+
+    async fn get_override(&self, message_id: &str) -> Option<SentimentOverride> {
+        let query = r#"
+            SELECT message_id, user_id, content, classified_sentiment, corrected_sentiment, overridden_by, created_at
+            FROM sentiment_overrides
+            WHERE message_id = $1
+        "#;
+        sqlx::query(query)
+            .bind(message_id)
+            .fetch_optional(&self.pool)
+            .await
+            .expect("Failed to fetch sentiment override")
+            .map(|row| sentiment_override_from_row(&row))
+    }
+
+    async fn list_overrides(&self) -> Vec<SentimentOverride> {
+        let query = r#"
+            SELECT message_id, user_id, content, classified_sentiment, corrected_sentiment, overridden_by, created_at
+            FROM sentiment_overrides
+            ORDER BY created_at ASC, id ASC
+        "#;
+        sqlx::query(query)
+            .fetch_all(&self.pool)
+            .await
+            .expect("Failed to fetch sentiment overrides")
+            .iter()
+            .map(sentiment_override_from_row)
+            .collect()
+    }
+
+    async fn get_user_overrides(&self, user_id: &str) -> Vec<SentimentOverride> {
+        let query = r#"
+            SELECT message_id, user_id, content, classified_sentiment, corrected_sentiment, overridden_by, created_at
+            FROM sentiment_overrides
+            WHERE user_id = $1
+            ORDER BY created_at ASC, id ASC
+        "#;
+        sqlx::query(query)
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+            .expect("Failed to fetch sentiment overrides")
+            .iter()
+            .map(sentiment_override_from_row)
+            .collect()
+    }
+
+    async fn clear_override_content(&self, message_ids: &[String]) -> usize {
+        if message_ids.is_empty() {
+            return 0;
+        }
+        let query = "UPDATE sentiment_overrides SET content = NULL WHERE message_id = ANY($1) AND content IS NOT NULL";
+        let result = sqlx::query(query)
+            .bind(message_ids.to_vec())
+            .execute(&self.pool)
+            .await
+            .expect("Failed to clear sentiment override content");
+        result.rows_affected() as usize
+    }
+
+    async fn delete_overrides(&self, user_id: &str) -> usize {
+        let query = "DELETE FROM sentiment_overrides WHERE user_id = $1";
+        let result = sqlx::query(query)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .expect("Failed to delete sentiment overrides");
+        result.rows_affected() as usize
+    }
+}
+
+fn sentiment_override_from_row(row: &PgRow) -> SentimentOverride {
+    let classified: String = row.get("classified_sentiment");
+    let corrected: String = row.get("corrected_sentiment");
+    SentimentOverride {
+        message_id: row.get("message_id"),
+        user_id: row.get("user_id"),
+        content: row.get("content"),
+        classified_sentiment: Sentiment::from(classified.as_str()),
+        corrected_sentiment: Sentiment::from(corrected.as_str()),
+        overridden_by: row.get("overridden_by"),
+        created_at: row.get("created_at"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn sentiment_override(message_id: &str, corrected_sentiment: Sentiment) -> SentimentOverride {
+        SentimentOverride {
+            message_id: message_id.to_string(),
+            user_id: "test_user".to_string(),
+            content: Some("you're a menace, toodles 😂".to_string()),
+            classified_sentiment: Sentiment::Negative,
+            corrected_sentiment,
+            overridden_by: "host".to_string(),
+            created_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_sentiment_override_store() {
+        let store = InMemorySentimentOverrideStore::new();
+        assert!(store.get_override("1").await.is_none());
+
+        store.record_override(sentiment_override("1", Sentiment::Positive)).await;
+        store.record_override(sentiment_override("2", Sentiment::Neutral)).await;
+        store.record_override(sentiment_override("1", Sentiment::Neutral)).await;
+
+        let overrides = store.list_overrides().await;
+        assert_eq!(overrides.len(), 2, "Expected a repeated override to replace the earlier one");
+        assert_eq!(store.get_override("1").await.unwrap().corrected_sentiment, Sentiment::Neutral);
+        assert_eq!(store.get_user_overrides("test_user").await.len(), 2);
+        assert!(store.get_user_overrides("other_user").await.is_empty());
+
+        assert_eq!(store.clear_override_content(&["1".to_string(), "3".to_string()]).await, 1);
+        assert!(store.get_override("1").await.unwrap().content.is_none());
+        assert!(store.get_override("2").await.unwrap().content.is_some());
+
+        assert_eq!(store.delete_overrides("test_user").await, 2);
+        assert!(store.list_overrides().await.is_empty());
+    }
+}
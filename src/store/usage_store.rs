@@ -0,0 +1,156 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+
+use crate::models::{UsageRecord, UsageScope, UsageSummary};
+
+/// Tokens spent on model calls. Records hold no message content and outlive `/forget`,
+/// so forgetting can't be used to reset a budget.
+#[async_trait]
+pub trait UsageStore {
+    async fn record_usage(&self, record: UsageRecord);
+    /// What was spent in `scope` at or after `since`, per player and model, biggest spender first.
+    async fn summarize_usage(&self, scope: &UsageScope, since: DateTime<Utc>) -> Vec<UsageSummary>;
+}
+
+/// Tokens spent in `scope` at or after `since`, across players and models.
+pub async fn tokens_spent(store: &(dyn UsageStore + Send + Sync), scope: &UsageScope, since: DateTime<Utc>) -> u64 {
+    store.summarize_usage(scope, since).await.iter().map(UsageSummary::total_tokens).sum()
+}
+
+#[derive(Default)]
+pub struct InMemoryUsageStore {
+    store: Arc<RwLock<Vec<UsageRecord>>>,
+}
+
+impl InMemoryUsageStore {
+    pub fn new() -> Self {
+        InMemoryUsageStore {
+            store: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+}
+
+#[async_trait]
+impl UsageStore for InMemoryUsageStore {
+    async fn record_usage(&self, record: UsageRecord) {
+        let mut store = self.store.write().await;
+        store.push(record);
+    }
+
+    async fn summarize_usage(&self, scope: &UsageScope, since: DateTime<Utc>) -> Vec<UsageSummary> {
+        let store = self.store.read().await;
+        let in_scope = |record: &UsageRecord| match scope {
+            UsageScope::User(user_id) => record.user_id == *user_id,
+            UsageScope::Guild(guild_id) => record.guild_id.as_ref() == Some(guild_id),
+        };
+        let mut summaries: HashMap<(&str, &str), UsageSummary> = HashMap::new();
+        for record in store.iter().filter(|record| in_scope(record) && record.created_at >= since) {
+            let summary = summaries.entry((&record.user_id, &record.model)).or_insert_with(|| UsageSummary {
+                user_id: record.user_id.clone(),
+                model: record.model.clone(),
+                calls: 0,
+                prompt_tokens: 0,
+                completion_tokens: 0,
+            });
+            summary.calls += 1;
+            summary.prompt_tokens += u64::from(record.prompt_tokens);
+            summary.completion_tokens += u64::from(record.completion_tokens);
+        }
+
+        let mut summaries: Vec<UsageSummary> = summaries.into_values().collect();
+        summaries.sort_by(|a, b| b.total_tokens().cmp(&a.total_tokens()).then_with(|| a.user_id.cmp(&b.user_id)).then_with(|| a.model.cmp(&b.model)));
+        summaries
+    }
+}
+
+pub struct PostgresUsageStore {
+    pool: PgPool,
+}
+
+impl PostgresUsageStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl UsageStore for PostgresUsageStore {
+    async fn record_usage(&self, record: UsageRecord) {
+        let query = r#"
+            INSERT INTO usage_records (user_id, guild_id, kind, model, prompt_tokens, completion_tokens, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#;
+        sqlx::query(query)
+            .bind(record.user_id)
+            .bind(record.guild_id)
+            .bind(record.kind.as_str())
+            .bind(record.model)
+            .bind(record.prompt_tokens as i32)
+            .bind(record.completion_tokens as i32)
+            .bind(record.created_at)
+            .execute(&self.pool)
+            .await
+            .expect("Failed to record usage");
+    }
+
+    async fn summarize_usage(&self, scope: &UsageScope, since: DateTime<Utc>) -> Vec<UsageSummary> {
+        let (column, id) = match scope {
+            UsageScope::User(user_id) => ("user_id", user_id),
+            UsageScope::Guild(guild_id) => ("guild_id", guild_id),
+        };
+        let query = format!(r#"
+            SELECT user_id, model, COUNT(*) AS calls, SUM(prompt_tokens)::BIGINT AS prompt_tokens, SUM(completion_tokens)::BIGINT AS completion_tokens
+            FROM usage_records
+            WHERE {} = $1 AND created_at >= $2
+            GROUP BY user_id, model
+            ORDER BY SUM(prompt_tokens + completion_tokens) DESC, user_id ASC, model ASC
+        "#, column);
+        let rows = sqlx::query(&query)
+            .bind(id)
+            .bind(since)
+            .fetch_all(&self.pool)
+            .await
+            .expect("Failed to summarize usage");
+
+        rows.into_iter()
+            .map(|row| UsageSummary {
+                user_id: row.get("user_id"),
+                model: row.get("model"),
+                calls: row.get::<i64, _>("calls") as usize,
+                prompt_tokens: row.get::<i64, _>("prompt_tokens") as u64,
+                completion_tokens: row.get::<i64, _>("completion_tokens") as u64,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{TokenUsage, UsageKind};
+
+    fn usage(model: &str, prompt_tokens: u32, completion_tokens: u32) -> TokenUsage {
+        TokenUsage { model: model.to_string(), prompt_tokens, completion_tokens }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_usage_store() {
+        let store = InMemoryUsageStore::new();
+        let start = Utc::now();
+        store.record_usage(UsageRecord::new("1", Some("9".to_string()), UsageKind::Reply, usage("big", 100, 50))).await;
+        store.record_usage(UsageRecord::new("1", Some("9".to_string()), UsageKind::Classification, usage("big", 30, 1))).await;
+        store.record_usage(UsageRecord::new("2", Some("9".to_string()), UsageKind::Reply, usage("small", 10, 5))).await;
+        store.record_usage(UsageRecord::new("1", None, UsageKind::Reply, usage("big", 20, 10))).await;
+
+        let guild = store.summarize_usage(&UsageScope::Guild("9".to_string()), start).await;
+        assert_eq!(guild.len(), 2);
+        assert_eq!((guild[0].user_id.as_str(), guild[0].calls, guild[0].total_tokens()), ("1", 2, 181), "Expected the biggest spender first");
+        assert_eq!(tokens_spent(&store, &UsageScope::Guild("9".to_string()), start).await, 196);
+        assert_eq!(tokens_spent(&store, &UsageScope::User("1".to_string()), start).await, 211, "Expected a player's spending to include direct messages");
+        assert_eq!(tokens_spent(&store, &UsageScope::User("1".to_string()), Utc::now() + chrono::TimeDelta::seconds(1)).await, 0);
+    }
+}